@@ -0,0 +1,267 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+extern crate calamp;
+#[macro_use]
+extern crate criterion;
+
+use std::hint::black_box;
+use std::net::{SocketAddr, UdpSocket};
+use std::thread;
+use std::time::Duration;
+
+use calamp::accumulators::Accumulators;
+use calamp::flags::{CommState, FixStatus, Inputs, UnitStatus};
+use calamp::gps_fix::GpsFix;
+use calamp::lmu_time::LmuTime;
+use calamp::message::Message;
+use calamp::message::acknowledgement::{AcknowledgementMessage, AcknowledgementType};
+use calamp::message::application::ApplicationMessage;
+use calamp::message::event_report::EventReportMessage;
+use calamp::message::id_report::IdReportMessage;
+use calamp::message::locate_report::LocateReportMessage;
+use calamp::message::null::NullMessage;
+use calamp::message::report_header::ReportHeader;
+use calamp::message::user::UserMessage;
+use calamp::message_header::*;
+use calamp::options_header::*;
+use calamp::packet::Packet;
+use calamp::server::Server;
+use calamp::signal::{CarrierId, Rssi};
+
+use criterion::{BenchmarkId, Criterion, Throughput};
+
+/// Options header with every option bit set.
+fn options_header() -> OptionsHeader {
+    let mut extension      = OptionExtension::new();
+    let mut options_header = OptionsHeader::new();
+
+    extension.set_esn(Some("4641143898".to_string()));
+    extension.set_vin(Some("1FTFW1ET5DFC10312".to_string()));
+    extension.set_encryption_service(Some((EncryptionType::Esn, [1, 2, 3, 4])));
+
+    options_header.set_mobile_id(Some(MobileId::Esn("4641143898".to_string())));
+    options_header.set_authentication(Some(vec![0xDE, 0xAD, 0xBE, 0xEF]));
+    options_header.set_routing(Some(vec![0x0A, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07]));
+    options_header.set_forwarding(Some(("10.0.0.1".to_string(), 20500, ForwardingProtocol::Udp,
+                                        ForwardingOperationType::Proxy)));
+    options_header.set_redirection(Some(("10.0.0.2".to_string(), 20501)));
+    options_header.set_extension(Some(extension));
+
+    options_header
+}
+
+fn report_header() -> ReportHeader {
+    let mut report_header = ReportHeader::new(LmuTime::from_secs(1449602011),
+                                              LmuTime::from_secs(1449602010),
+                                              GpsFix::new(331031058, -1172898431, 1200, 1111,
+                                                          90, 9, FixStatus::default(), 9));
+
+    report_header.set_carrier(CarrierId::new(410));
+    report_header.set_comm_state(CommState::AVAILABLE | CommState::CONNECTED);
+    report_header.set_inputs(Inputs::IGNITION);
+    report_header.set_rssi(Rssi::new(-75));
+    report_header.set_unit_status(UnitStatus::GPS_TRACKING);
+
+    report_header
+}
+
+/// One body per decoded message type.
+fn messages() -> Vec<(&'static str, MessageType, Message)> {
+    let accumulators = Accumulators::new((0..16).collect());
+
+    let mut id_report = IdReportMessage::new(33, [1, 2, 3], [4, 5, 6]);
+
+    id_report.set_esn("4641143898");
+    id_report.set_imei("352099001761481");
+    id_report.set_imsi("310150123456789");
+    id_report.set_iccid("89014103211118510720");
+
+    vec![("null", MessageType::Null, Message::Null(NullMessage::new())),
+         ("ack_nak", MessageType::AckNak,
+          Message::AckNak(AcknowledgementMessage::new(MessageType::EventReport,
+                                                      AcknowledgementType::Successful,
+                                                      [1, 2, 3]))),
+         ("event_report", MessageType::EventReport,
+          Message::EventReport(EventReportMessage::new(report_header(), 1, 13,
+                                                       accumulators.clone()))),
+         ("id_report", MessageType::IdReport, Message::IdReport(id_report)),
+         ("user_data", MessageType::UserData,
+          Message::UserData(UserMessage::new(report_header(), 0, 1, vec![0x55; 64]))),
+         ("application_data", MessageType::ApplicationData,
+          Message::ApplicationData(ApplicationMessage::new(report_header(), 130,
+                                                           vec![0x55; 64]))),
+         ("locate_report", MessageType::LocateReport,
+          Message::LocateReport(LocateReportMessage::new(report_header(), 2, 0, accumulators)))]
+}
+
+fn encoded<F: Fn(&mut Vec<u8>)>(encode: F) -> Vec<u8> {
+    let mut buffer = Vec::new();
+
+    encode(&mut buffer);
+
+    buffer
+}
+
+fn bench_options_header(c: &mut Criterion) {
+    let options_header = options_header();
+    let data           = encoded(|buffer| options_header.encode(buffer));
+
+    let mut group = c.benchmark_group("options_header");
+
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.bench_function("parse/all_options", |b| {
+        b.iter(|| OptionsHeader::parse(black_box(&data)).unwrap())
+    });
+    group.bench_function("encode/all_options", |b| {
+        let mut buffer = Vec::with_capacity(data.len());
+
+        b.iter(|| {
+            buffer.clear();
+            black_box(&options_header).encode(&mut buffer);
+        })
+    });
+    group.finish();
+}
+
+fn bench_message_header(c: &mut Criterion) {
+    let message_header = MessageHeader::new(ServiceType::AcknowledgedRequest,
+                                            MessageType::EventReport, 1);
+    let data           = encoded(|buffer| message_header.encode(buffer));
+
+    let mut group = c.benchmark_group("message_header");
+
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.bench_function("parse", |b| {
+        b.iter(|| MessageHeader::parse(black_box(&data)).unwrap())
+    });
+    group.bench_function("encode", |b| {
+        let mut buffer = Vec::with_capacity(data.len());
+
+        b.iter(|| {
+            buffer.clear();
+            black_box(&message_header).encode(&mut buffer);
+        })
+    });
+    group.finish();
+}
+
+fn bench_message(c: &mut Criterion) {
+    let mut group = c.benchmark_group("message");
+
+    for (name, message_type, message) in messages() {
+        let data = encoded(|buffer| message.encode(buffer));
+
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_with_input(BenchmarkId::new("parse", name), &data, |b, data| {
+            b.iter(|| Message::parse(&message_type, black_box(data)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("encode", name), &message, |b, message| {
+            let mut buffer = Vec::with_capacity(data.len());
+
+            b.iter(|| {
+                buffer.clear();
+                black_box(message).encode(&mut buffer);
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_packet(c: &mut Criterion) {
+    let mut group = c.benchmark_group("packet");
+
+    for (name, message_type, message) in messages() {
+        let mut options_header = OptionsHeader::new();
+
+        options_header.set_mobile_id(Some(MobileId::Esn("4641143898".to_string())));
+
+        let packet = Packet::new(options_header,
+                                 MessageHeader::new(ServiceType::AcknowledgedRequest,
+                                                    message_type, 1),
+                                 message);
+        let data   = encoded(|buffer| packet.encode(buffer));
+
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_with_input(BenchmarkId::new("parse", name), &data, |b, data| {
+            b.iter(|| Packet::parse(black_box(data)).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_server(c: &mut Criterion) {
+    let mut server = Server::bind("127.0.0.1:0", |_: &Packet, _: SocketAddr| {
+        AcknowledgementType::Successful
+    }).unwrap();
+
+    let address = server.local_addr().unwrap();
+
+    thread::spawn(move || server.serve());
+
+    let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+    client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    client.connect(address).unwrap();
+
+    let (_, message_type, message) = messages().swap_remove(2);
+
+    let mut options_header = OptionsHeader::new();
+
+    options_header.set_mobile_id(Some(MobileId::Esn("4641143898".to_string())));
+
+    let data = encoded(|buffer| {
+        Packet::new(options_header.clone(),
+                    MessageHeader::new(ServiceType::AcknowledgedRequest, message_type, 1),
+                    message.clone()).encode(buffer)
+    });
+
+    let mut buffer = [0; 512];
+    let mut group  = c.benchmark_group("server");
+
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("round_trip/event_report", |b| {
+        b.iter(|| {
+            client.send(&data).unwrap();
+            client.recv(&mut buffer).unwrap()
+        })
+    });
+
+    // keep a window of requests in flight to measure throughput rather than latency
+    const WINDOW: usize = 32;
+
+    group.throughput(Throughput::Elements(WINDOW as u64));
+    group.bench_function(BenchmarkId::new("pipelined/event_report", WINDOW), |b| {
+        b.iter(|| {
+            for _ in 0..WINDOW {
+                client.send(&data).unwrap();
+            }
+
+            for _ in 0..WINDOW {
+                client.recv(&mut buffer).unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_options_header, bench_message_header, bench_message,
+                 bench_packet, bench_server);
+criterion_main!(benches);
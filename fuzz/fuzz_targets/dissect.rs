@@ -0,0 +1,35 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+//! Fuzz `dissect::dissect`, and render the dissection.
+
+#![no_main]
+
+extern crate calamp;
+#[macro_use]
+extern crate libfuzzer_sys;
+
+use calamp::dissect;
+
+fuzz_target!(|data: &[u8]| {
+    let dissection = dissect::dissect(data);
+
+    assert!(dissection.stop() <= data.len());
+
+    let _ = dissection.to_string();
+});
@@ -0,0 +1,38 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+//! Fuzz `Message::parse`, with the first byte selecting the message type.
+
+#![no_main]
+
+extern crate calamp;
+#[macro_use]
+extern crate libfuzzer_sys;
+
+use calamp::message::Message;
+use calamp::message_header::MessageType;
+
+fuzz_target!(|data: &[u8]| {
+    if let Some((&value, body)) = data.split_first() {
+        if let Ok(message_type) = MessageType::from_u8(value) {
+            if let Ok((_, length)) = Message::parse(&message_type, body) {
+                assert!(length <= body.len());
+            }
+        }
+    }
+});
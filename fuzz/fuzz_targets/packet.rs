@@ -0,0 +1,46 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+//! Fuzz `Packet::parse` and its variants. Packets that parse must encode back to the bytes they
+//! were parsed from.
+
+#![no_main]
+
+extern crate calamp;
+#[macro_use]
+extern crate libfuzzer_sys;
+
+use calamp::ParseOptions;
+use calamp::cipher::Rc4Cipher;
+use calamp::packet::Packet;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Packet::parse_with_options(data, &ParseOptions::lenient());
+    let _ = Packet::parse_with_cipher(data, &Rc4Cipher, &ParseOptions::lenient());
+
+    if let Ok((packet, length)) = Packet::parse(data) {
+        assert!(length <= data.len());
+
+        // packets that parse encode back to the bytes they were parsed from
+        let mut encoded = Vec::new();
+
+        packet.encode(&mut encoded);
+
+        assert_eq!(encoded, &data[..length]);
+    }
+});
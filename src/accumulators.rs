@@ -0,0 +1,423 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+use CalAmpError;
+#[cfg(any(feature = "toml", feature = "json"))]
+use ConfigError;
+#[cfg(any(feature = "toml", feature = "json"))]
+use config;
+use dissect::Trace;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::slice;
+use std::str::FromStr;
+
+#[cfg(any(feature = "toml", feature = "json"))]
+use std::path::Path;
+
+/// Maximum number of values the 6 bit accumulator count can describe.
+pub const MAX_ACCUMULATORS: usize = 63;
+
+/// Accumulator list carried by event and locate reports.
+///
+/// The meaning of each value depends on the PEG script of the unit, and is described by an
+/// `AccumulatorSchema`.
+#[derive(Clone,Debug,Default,Eq,PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct Accumulators {
+    /// Accumulator list type, stored in the upper 2 bits of the accumulator count.
+    accumulator_type: u8,
+
+    /// Spare byte following the accumulator count.
+    spare: u8,
+
+    /// Accumulator values.
+    values: Vec<u32>
+}
+
+impl Accumulators {
+    /// Create a new Accumulators list.
+    ///
+    /// Values beyond `MAX_ACCUMULATORS` are dropped, since the count cannot describe them.
+    pub fn new(mut values: Vec<u32>) -> Accumulators {
+        values.truncate(MAX_ACCUMULATORS);
+
+        Accumulators{
+            accumulator_type: 0,
+            spare:            0,
+            values
+        }
+    }
+
+    /// Parse an accumulator count, spare byte, and accumulator list from a slice.
+    ///
+    /// Returns the Accumulators and parsed byte count.
+    pub fn parse(slice: &[u8]) -> Result<(Accumulators, usize), CalAmpError> {
+        Accumulators::parse_traced(slice, &mut Trace::disabled())
+    }
+
+    /// Parse an accumulator count, spare byte, and accumulator list from a slice, recording field
+    /// spans into `trace`.
+    ///
+    /// Returns the Accumulators and parsed byte count.
+    pub fn parse_traced(slice: &[u8], trace: &mut Trace)
+    -> Result<(Accumulators, usize), CalAmpError> {
+        // slice index
+        let mut index = 0;
+
+        // bits 0-5: accumulator count
+        // bits 6-7: accumulator list type
+        let count = read_u8!(slice, index);
+
+        trace.field("Accumulator count", 0, 1, || {
+            format!("{} (list type {})", count & 0x3F, count >> 6)
+        });
+
+        let spare = trace_field!(trace, "Spare", index, read_u8!(slice, index), "{}");
+
+        let mut values = Vec::with_capacity((count & 0x3F) as usize);
+
+        for n in 0..(count & 0x3F) {
+            values.push(trace_field!(trace, &format!("Accumulator {}", n), index,
+                                     read_u32!(slice, index), "{}"));
+        }
+
+        Ok((Accumulators{
+            accumulator_type: count >> 6,
+            spare,
+            values
+        }, index))
+    }
+
+    /// Encode the accumulator count, spare byte, and accumulator list into a buffer.
+    ///
+    /// At most `MAX_ACCUMULATORS` values are encoded.
+    pub fn encode(&self, buffer: &mut Vec<u8>) {
+        let values = &self.values[..self.values.len().min(MAX_ACCUMULATORS)];
+
+        buffer.push((self.accumulator_type << 6) | values.len() as u8);
+        buffer.push(self.spare);
+
+        for value in values {
+            buffer.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+
+    /// Retrieve the accumulator list type.
+    pub fn accumulator_type(&self) -> u8 {
+        self.accumulator_type
+    }
+
+    /// Retrieve the value at `index`.
+    pub fn get(&self, index: usize) -> Option<u32> {
+        self.values.get(index).cloned()
+    }
+
+    /// Retrieve an iterator over the values.
+    pub fn iter(&self) -> slice::Iter<'_, u32> {
+        self.values.iter()
+    }
+
+    /// Retrieve the value count.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Indicates that the list holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Retrieve the values.
+    pub fn values(&self) -> &[u32] {
+        &self.values
+    }
+
+    /// Resolve the values described by `schema` into labeled values.
+    ///
+    /// Values without a schema entry are skipped.
+    pub fn resolve<'a>(&self, schema: &'a AccumulatorSchema) -> Vec<AccumulatorValue<'a>> {
+        self.values.iter().enumerate().filter_map(|(index, raw)| {
+            schema.get(index).map(|definition| {
+                AccumulatorValue{
+                    definition,
+                    index,
+                    raw: *raw
+                }
+            })
+        }).collect()
+    }
+}
+
+/// Accumulator kind.
+#[derive(Clone,Copy,Debug,Eq,Hash,PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum AccumulatorKind {
+    /// Event counter.
+    Counter,
+
+    /// Distance in meters.
+    Distance,
+
+    /// Uninterpreted value.
+    Raw,
+
+    /// Timer in seconds.
+    Timer
+}
+
+impl FromStr for AccumulatorKind {
+    type Err = String;
+
+    fn from_str(kind: &str) -> Result<AccumulatorKind, String> {
+        match kind.trim().to_lowercase().as_str() {
+            "counter" => Ok(AccumulatorKind::Counter),
+            "distance" => Ok(AccumulatorKind::Distance),
+            "raw" => Ok(AccumulatorKind::Raw),
+            "timer" => Ok(AccumulatorKind::Timer),
+            _ => Err(format!("unknown accumulator kind '{}'", kind.trim()))
+        }
+    }
+}
+
+impl fmt::Display for AccumulatorKind {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AccumulatorKind::Counter => {
+                write!(formatter, "counter")
+            },
+            AccumulatorKind::Distance => {
+                write!(formatter, "distance")
+            },
+            AccumulatorKind::Raw => {
+                write!(formatter, "raw")
+            },
+            AccumulatorKind::Timer => {
+                write!(formatter, "timer")
+            }
+        }
+    }
+}
+
+/// Definition of a single accumulator.
+#[derive(Clone,Debug,PartialEq)]
+pub struct AccumulatorDefinition {
+    /// Kind.
+    kind: AccumulatorKind,
+
+    /// Name.
+    name: String,
+
+    /// Factor that converts the raw value into `unit`.
+    scale: f64,
+
+    /// Unit label.
+    unit: String
+}
+
+impl AccumulatorDefinition {
+    /// Create a new AccumulatorDefinition.
+    pub fn new(name: &str, kind: AccumulatorKind, unit: &str, scale: f64) -> AccumulatorDefinition {
+        AccumulatorDefinition{
+            kind,
+            name: name.to_string(),
+            scale,
+            unit: unit.to_string()
+        }
+    }
+
+    /// Retrieve the kind.
+    pub fn kind(&self) -> AccumulatorKind {
+        self.kind
+    }
+
+    /// Retrieve the name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Retrieve the factor that converts the raw value into the unit.
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    /// Retrieve the unit label.
+    pub fn unit(&self) -> &str {
+        &self.unit
+    }
+}
+
+/// Schema entry within a TOML or JSON file.
+#[cfg(any(feature = "toml", feature = "json"))]
+#[derive(Deserialize)]
+struct Entry {
+    /// Accumulator index.
+    index: usize,
+
+    /// Kind.
+    kind: AccumulatorKind,
+
+    /// Name.
+    name: String,
+
+    /// Factor that converts the raw value into `unit`, or 1 when omitted.
+    scale: Option<f64>,
+
+    /// Unit label.
+    #[serde(default)]
+    unit: String
+}
+
+/// Layout of a TOML or JSON schema file.
+#[cfg(any(feature = "toml", feature = "json"))]
+#[derive(Deserialize)]
+struct File {
+    /// Schema entries.
+    #[serde(default)]
+    accumulator: Vec<Entry>
+}
+
+/// Mapping of accumulator indexes to definitions for a PEG script.
+#[derive(Clone,Debug,Default,PartialEq)]
+pub struct AccumulatorSchema {
+    /// Definitions keyed by accumulator index.
+    definitions: BTreeMap<usize, AccumulatorDefinition>
+}
+
+impl AccumulatorSchema {
+    /// Create a new empty AccumulatorSchema.
+    pub fn new() -> AccumulatorSchema {
+        AccumulatorSchema::default()
+    }
+
+    /// Load a schema file, choosing the format from the `.toml` or `.json` extension.
+    ///
+    /// The file lists one `accumulator` entry per definition. The unit and scale are optional,
+    /// and default to no unit and a scale of 1:
+    ///
+    /// ```toml
+    /// [[accumulator]]
+    /// index = 0
+    /// name  = "odometer"
+    /// kind  = "distance"
+    /// unit  = "km"
+    /// scale = 0.001
+    ///
+    /// [[accumulator]]
+    /// index = 1
+    /// name  = "idle"
+    /// kind  = "timer"
+    /// ```
+    #[cfg(any(feature = "toml", feature = "json"))]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<AccumulatorSchema, ConfigError> {
+        config::load(path.as_ref()).map(AccumulatorSchema::from_file)
+    }
+
+    /// Parse a schema from TOML text.
+    #[cfg(feature = "toml")]
+    pub fn from_toml(text: &str) -> Result<AccumulatorSchema, ConfigError> {
+        config::from_toml(text).map(AccumulatorSchema::from_file)
+    }
+
+    /// Parse a schema from JSON text.
+    #[cfg(feature = "json")]
+    pub fn from_json(text: &str) -> Result<AccumulatorSchema, ConfigError> {
+        config::from_json(text).map(AccumulatorSchema::from_file)
+    }
+
+    /// Build a schema from a parsed file.
+    #[cfg(any(feature = "toml", feature = "json"))]
+    fn from_file(file: File) -> AccumulatorSchema {
+        let mut schema = AccumulatorSchema::new();
+
+        for entry in file.accumulator {
+            schema.insert(entry.index, AccumulatorDefinition::new(&entry.name, entry.kind,
+                                                                  &entry.unit,
+                                                                  entry.scale.unwrap_or(1.0)));
+        }
+
+        schema
+    }
+
+    /// Retrieve the definition at `index`.
+    pub fn get(&self, index: usize) -> Option<&AccumulatorDefinition> {
+        self.definitions.get(&index)
+    }
+
+    /// Set the definition at `index`.
+    pub fn insert(&mut self, index: usize, definition: AccumulatorDefinition) {
+        self.definitions.insert(index, definition);
+    }
+}
+
+/// Accumulator value labeled by its definition.
+#[derive(Clone,Debug,PartialEq)]
+pub struct AccumulatorValue<'a> {
+    /// Definition.
+    definition: &'a AccumulatorDefinition,
+
+    /// Accumulator index.
+    index: usize,
+
+    /// Raw value.
+    raw: u32
+}
+
+impl<'a> AccumulatorValue<'a> {
+    /// Retrieve the definition.
+    pub fn definition(&self) -> &'a AccumulatorDefinition {
+        self.definition
+    }
+
+    /// Retrieve the accumulator index.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Retrieve the name.
+    pub fn name(&self) -> &'a str {
+        &self.definition.name
+    }
+
+    /// Retrieve the raw value.
+    pub fn raw(&self) -> u32 {
+        self.raw
+    }
+
+    /// Retrieve the unit label.
+    pub fn unit(&self) -> &'a str {
+        &self.definition.unit
+    }
+
+    /// Retrieve the value converted into the unit.
+    pub fn value(&self) -> f64 {
+        self.raw as f64 * self.definition.scale
+    }
+}
+
+impl<'a> fmt::Display for AccumulatorValue<'a> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        if self.definition.unit.is_empty() {
+            write!(formatter, "{}: {}", self.definition.name, self.value())
+        } else {
+            write!(formatter, "{}: {} {}", self.definition.name, self.value(), self.definition.unit)
+        }
+    }
+}
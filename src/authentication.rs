@@ -0,0 +1,279 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+#[cfg(any(feature = "toml", feature = "json"))]
+use ConfigError;
+#[cfg(any(feature = "toml", feature = "json"))]
+use config;
+use message::acknowledgement::AcknowledgementType;
+use options_header::{MobileId, OptionsHeader};
+use std::collections::HashMap;
+use std::fmt;
+
+#[cfg(any(feature = "toml", feature = "json"))]
+use util;
+#[cfg(any(feature = "toml", feature = "json"))]
+use std::path::{Path, PathBuf};
+
+/// Authentication failure.
+#[derive(Clone,Debug,Eq,PartialEq)]
+pub enum AuthenticationError {
+    /// Authentication field does not match the credential.
+    Mismatch,
+
+    /// Packet does not supply an authentication field.
+    MissingAuthentication,
+
+    /// Packet does not supply a mobile ID.
+    MissingMobileId,
+
+    /// No credential is stored for the mobile ID.
+    UnknownMobileId(MobileId)
+}
+
+impl AuthenticationError {
+    /// Retrieve the acknowledgement type reported to the unit.
+    pub fn ack(&self) -> AcknowledgementType {
+        AcknowledgementType::FailedAuthentication
+    }
+}
+
+impl fmt::Display for AuthenticationError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AuthenticationError::Mismatch => {
+                write!(formatter, "authentication does not match")
+            },
+            AuthenticationError::MissingAuthentication => {
+                write!(formatter, "authentication is not supplied")
+            },
+            AuthenticationError::MissingMobileId => {
+                write!(formatter, "mobile id is not supplied")
+            },
+            AuthenticationError::UnknownMobileId(ref mobile_id) => {
+                write!(formatter, "no credential for mobile id {}", mobile_id)
+            }
+        }
+    }
+}
+
+/// Per mobile ID credential store.
+pub trait CredentialStore {
+    /// Retrieve the authentication field for `mobile_id`.
+    fn credential(&self, mobile_id: &MobileId) -> Option<Vec<u8>>;
+
+    /// Verify the authentication field of an inbound packet against the credential for its
+    /// mobile ID.
+    fn verify(&self, options_header: &OptionsHeader) -> Result<(), AuthenticationError> {
+        let mobile_id = match *options_header.mobile_id() {
+            Some(ref mobile_id) => mobile_id,
+            None => return Err(AuthenticationError::MissingMobileId)
+        };
+
+        let credential = match self.credential(mobile_id) {
+            Some(credential) => credential,
+            None => return Err(AuthenticationError::UnknownMobileId(mobile_id.clone()))
+        };
+
+        match *options_header.authentication() {
+            Some(ref authentication) if constant_time_eq(authentication, &credential) => Ok(()),
+            Some(_) => Err(AuthenticationError::Mismatch),
+            None => Err(AuthenticationError::MissingAuthentication)
+        }
+    }
+
+    /// Set the authentication field of an outbound packet to the credential for its mobile ID.
+    fn authenticate(&self, options_header: &mut OptionsHeader) -> Result<(), AuthenticationError> {
+        let credential = match *options_header.mobile_id() {
+            Some(ref mobile_id) => {
+                self.credential(mobile_id).ok_or_else(|| {
+                    AuthenticationError::UnknownMobileId(mobile_id.clone())
+                })?
+            },
+            None => return Err(AuthenticationError::MissingMobileId)
+        };
+
+        options_header.set_authentication(Some(credential));
+
+        Ok(())
+    }
+}
+
+/// Credential entry within a TOML or JSON file.
+#[cfg(any(feature = "toml", feature = "json"))]
+#[derive(Deserialize)]
+struct Entry {
+    /// Authentication field as hex.
+    authentication: String,
+
+    /// Mobile ID written as `type:value`.
+    mobile_id: String
+}
+
+/// Layout of a TOML or JSON credential file.
+#[cfg(any(feature = "toml", feature = "json"))]
+#[derive(Deserialize)]
+struct File {
+    /// Credential entries.
+    #[serde(default)]
+    credential: Vec<Entry>
+}
+
+/// In-memory credential store.
+#[derive(Clone,Debug,Default)]
+pub struct MemoryCredentialStore {
+    /// Credentials by mobile ID.
+    credentials: HashMap<MobileId, Vec<u8>>
+}
+
+impl MemoryCredentialStore {
+    /// Create a new MemoryCredentialStore.
+    pub fn new() -> MemoryCredentialStore {
+        MemoryCredentialStore::default()
+    }
+
+    /// Load a credential file, choosing the format from the `.toml` or `.json` extension.
+    ///
+    /// The file lists one `credential` entry per unit, holding a mobile ID written as
+    /// `type:value` and the hex authentication field:
+    ///
+    /// ```toml
+    /// [[credential]]
+    /// mobile_id      = "esn:4641143898"
+    /// authentication = "0a1b2c3d"
+    ///
+    /// [[credential]]
+    /// mobile_id      = "imei:352099001761481"
+    /// authentication = "deadbeef"
+    /// ```
+    #[cfg(any(feature = "toml", feature = "json"))]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<MemoryCredentialStore, ConfigError> {
+        config::load(path.as_ref()).and_then(MemoryCredentialStore::from_file)
+    }
+
+    /// Parse credentials from TOML text.
+    #[cfg(feature = "toml")]
+    pub fn from_toml(text: &str) -> Result<MemoryCredentialStore, ConfigError> {
+        config::from_toml(text).and_then(MemoryCredentialStore::from_file)
+    }
+
+    /// Parse credentials from JSON text.
+    #[cfg(feature = "json")]
+    pub fn from_json(text: &str) -> Result<MemoryCredentialStore, ConfigError> {
+        config::from_json(text).and_then(MemoryCredentialStore::from_file)
+    }
+
+    /// Build a store from a parsed file.
+    #[cfg(any(feature = "toml", feature = "json"))]
+    fn from_file(file: File) -> Result<MemoryCredentialStore, ConfigError> {
+        let mut store = MemoryCredentialStore::new();
+
+        for entry in file.credential {
+            let mobile_id = entry.mobile_id.parse::<MobileId>().map_err(ConfigError::Invalid)?;
+            let credential = util::decode_hex(&entry.authentication).ok_or_else(|| {
+                ConfigError::Invalid(format!("invalid authentication '{}' for {}",
+                                             entry.authentication, entry.mobile_id))
+            })?;
+
+            store.insert(mobile_id, credential);
+        }
+
+        Ok(store)
+    }
+
+    /// Set the credential for `mobile_id`.
+    pub fn insert(&mut self, mobile_id: MobileId, credential: Vec<u8>) {
+        self.credentials.insert(mobile_id, credential);
+    }
+
+    /// Indicates no credentials are stored.
+    pub fn is_empty(&self) -> bool {
+        self.credentials.is_empty()
+    }
+
+    /// Retrieve the count of stored credentials.
+    pub fn len(&self) -> usize {
+        self.credentials.len()
+    }
+
+    /// Remove the credential for `mobile_id`.
+    pub fn remove(&mut self, mobile_id: &MobileId) -> Option<Vec<u8>> {
+        self.credentials.remove(mobile_id)
+    }
+}
+
+impl CredentialStore for MemoryCredentialStore {
+    fn credential(&self, mobile_id: &MobileId) -> Option<Vec<u8>> {
+        self.credentials.get(mobile_id).cloned()
+    }
+}
+
+/// Credential store backed by a TOML or JSON file, in the format read by
+/// `MemoryCredentialStore::load`.
+///
+/// Credentials are read when the store is opened, and again on `reload`, so units can be added or
+/// revoked without restarting the listener.
+#[cfg(any(feature = "toml", feature = "json"))]
+#[derive(Clone,Debug)]
+pub struct FileCredentialStore {
+    /// Credential file path.
+    path: PathBuf,
+
+    /// Credentials read from the file.
+    store: MemoryCredentialStore
+}
+
+#[cfg(any(feature = "toml", feature = "json"))]
+impl FileCredentialStore {
+    /// Open a credential file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<FileCredentialStore, ConfigError> {
+        Ok(FileCredentialStore{
+            path: path.as_ref().to_path_buf(),
+            store: MemoryCredentialStore::load(path)?
+        })
+    }
+
+    /// Retrieve the credential file path.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Read the credential file again. The current credentials are kept when it fails to load.
+    pub fn reload(&mut self) -> Result<(), ConfigError> {
+        self.store = MemoryCredentialStore::load(&self.path)?;
+
+        Ok(())
+    }
+
+    /// Retrieve the credentials read from the file.
+    pub fn store(&self) -> &MemoryCredentialStore {
+        &self.store
+    }
+}
+
+#[cfg(any(feature = "toml", feature = "json"))]
+impl CredentialStore for FileCredentialStore {
+    fn credential(&self, mobile_id: &MobileId) -> Option<Vec<u8>> {
+        self.store.credential(mobile_id)
+    }
+}
+
+/// Compare two byte strings in time that depends only on their lengths.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |difference, (x, y)| difference | (x ^ y)) == 0
+}
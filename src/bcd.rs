@@ -0,0 +1,67 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+/// Decode packed BCD bytes into a string of decimal digits. Filler nibbles (0xF) are skipped.
+pub fn decode(bytes: &[u8]) -> String {
+    let mut digits = String::with_capacity(bytes.len() * 2);
+
+    for n in bytes {
+        for nibble in &[n >> 4, n & 0xF] {
+            if *nibble != 0xF {
+                digits.push((0x30 + nibble) as char);
+            }
+        }
+    }
+
+    digits
+}
+
+/// Decode packed BCD bytes like `decode()`, also returning the bytes when `encode()` would not
+/// give them back from the digits, such as when they hold filler nibbles that are not trailing.
+pub fn decode_exact(bytes: &[u8]) -> (String, Option<Vec<u8>>) {
+    let digits      = decode(bytes);
+    let mut encoded = Vec::new();
+
+    encode(&digits, &mut encoded);
+
+    if encoded == bytes {
+        (digits, None)
+    } else {
+        (digits, Some(bytes.to_vec()))
+    }
+}
+
+/// Encode a string of decimal digits as packed BCD, padding an odd digit count with 0xF.
+pub fn encode(digits: &str, buffer: &mut Vec<u8>) {
+    for pair in digits.as_bytes().chunks(2) {
+        let high = pair[0].wrapping_sub(0x30) & 0xF;
+        let low  = if pair.len() > 1 { pair[1].wrapping_sub(0x30) & 0xF } else { 0xF };
+
+        buffer.push((high << 4) | low);
+    }
+}
+
+/// Encode a string of decimal digits as `length` bytes of packed BCD, filling unused bytes with
+/// 0xFF.
+pub fn encode_fixed(digits: &str, length: usize, buffer: &mut Vec<u8>) {
+    let start = buffer.len();
+
+    encode(digits, buffer);
+
+    buffer.resize(start + length, 0xFF);
+}
@@ -0,0 +1,363 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+extern crate calamp;
+
+#[cfg(feature = "json")]
+extern crate serde_json;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Read};
+use std::process;
+
+#[cfg(feature = "pcap")]
+use calamp::capture::CaptureReader;
+use calamp::ParseOptions;
+use calamp::dissect;
+use calamp::packet::Packet;
+use calamp::util;
+
+const USAGE: &str = "\
+Usage: calamp-decode [OPTIONS] [INPUT...]
+
+Decode CalAmp LMU packets and print the options header, message header and message body.
+
+Each INPUT is a file path, or - for standard input. Standard input is read when no INPUT or
+literal packet is given.
+
+Options:
+    -f, --format FORMAT  Encoding of INPUT data: binary (default), hex, base64 or pcap. Hex and
+                         base64 inputs hold one packet per line. The pcap format reads pcap and
+                         pcapng captures, and requires the pcap feature.
+    -p, --port PORT      Capture port carrying LMU traffic, repeatable (default 20500)
+    -x, --hex PACKET     Decode a literal hex packet
+    -b, --base64 PACKET  Decode a literal base64 packet
+    -d, --dissect        Print an annotated byte-by-byte breakdown, including packets that fail
+                         to decode
+    -l, --lenient        Decode packets with anomalies such as a VIN that is not 17 bytes,
+                         printing the anomalies as warnings
+    -j, --json           Print JSON rather than text
+    -h, --help           Print this message
+
+Exit status is 0 when every packet decodes, 1 when a packet fails to decode, and 2 on usage or
+I/O errors.";
+
+/// Input encoding.
+#[derive(Clone,Copy,PartialEq)]
+enum Format {
+    /// Raw bytes.
+    Binary,
+
+    /// Base64 text.
+    Base64,
+
+    /// Hex text.
+    Hex,
+
+    /// pcap or pcapng capture.
+    Pcap
+}
+
+/// Packet bytes, or the reason they could not be decoded, with a note appended to the label.
+type InputPacket = (String, Result<Vec<u8>, String>);
+
+/// Packet source.
+enum Source {
+    /// File path, or - for standard input.
+    Path(String),
+
+    /// Literal packet text.
+    Literal(Format, String)
+}
+
+fn main() {
+    let mut dissect = false;
+    let mut format  = Format::Binary;
+    let mut json    = false;
+    let mut options = ParseOptions::strict();
+    let mut ports   = Vec::new();
+    let mut sources = Vec::new();
+
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-f" | "--format" => {
+                format = match args.next().as_deref() {
+                    Some("binary") => Format::Binary,
+                    Some("base64") => Format::Base64,
+                    Some("hex") => Format::Hex,
+                    Some("pcap") => Format::Pcap,
+                    _ => usage_error("--format expects binary, hex, base64 or pcap")
+                };
+            },
+            "-p" | "--port" => {
+                match args.next().and_then(|port| port.parse().ok()) {
+                    Some(port) => ports.push(port),
+                    None => usage_error("--port expects a port number")
+                }
+            },
+            "-x" | "--hex" => {
+                match args.next() {
+                    Some(text) => sources.push(Source::Literal(Format::Hex, text)),
+                    None => usage_error("--hex expects a packet")
+                }
+            },
+            "-b" | "--base64" => {
+                match args.next() {
+                    Some(text) => sources.push(Source::Literal(Format::Base64, text)),
+                    None => usage_error("--base64 expects a packet")
+                }
+            },
+            "-d" | "--dissect" => {
+                dissect = true;
+            },
+            "-l" | "--lenient" => {
+                options = ParseOptions::lenient();
+            },
+            "-j" | "--json" => {
+                json = true;
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            },
+            _ if arg.starts_with('-') && arg != "-" => {
+                usage_error(&format!("unknown option: {}", arg))
+            },
+            _ => {
+                sources.push(Source::Path(arg));
+            }
+        }
+    }
+
+    if json && cfg!(not(feature = "json")) {
+        usage_error("--json requires calamp-decode to be built with the json feature");
+    }
+
+    if format == Format::Pcap && cfg!(not(feature = "pcap")) {
+        usage_error("--format pcap requires calamp-decode to be built with the pcap feature");
+    }
+
+    if dissect && json {
+        usage_error("--dissect and --json are mutually exclusive");
+    }
+
+    if sources.is_empty() {
+        sources.push(Source::Path("-".to_string()));
+    }
+
+    let mut failed = false;
+
+    for source in sources {
+        let (name, packets) = match source {
+            Source::Path(path) => {
+                let data = read_input(&path).unwrap_or_else(|error| {
+                    eprintln!("calamp-decode: {}: {}", path, error);
+                    process::exit(2);
+                });
+
+                let packets = split_packets(format, &data, &ports).unwrap_or_else(|error| {
+                    eprintln!("calamp-decode: {}: {}", path, error);
+                    process::exit(2);
+                });
+
+                (path, packets)
+            },
+            Source::Literal(format, text) => {
+                (text.clone(), vec![(String::new(), decode_text(format, &text))])
+            }
+        };
+
+        for (n, (note, packet)) in packets.into_iter().enumerate() {
+            let label = format!("{}#{}{}", name, n + 1, note);
+
+            if dissect {
+                match packet {
+                    Ok(data) => {
+                        let dissection = dissect::dissect(&data);
+
+                        println!("Packet {}", label);
+                        println!("{}", dissection);
+
+                        if let Some(error) = dissection.error() {
+                            eprintln!("calamp-decode: {}: {}", label, error);
+                            failed = true;
+                        }
+                    },
+                    Err(error) => {
+                        eprintln!("calamp-decode: {}: {}", label, error);
+                        failed = true;
+                    }
+                }
+
+                continue;
+            }
+
+            match packet.map_err(|error| error.to_string()).and_then(|data| {
+                Packet::parse_with_options(&data, &options).map_err(|error| error.to_string())
+            }) {
+                Ok((packet, _)) => {
+                    for warning in packet.warnings() {
+                        eprintln!("calamp-decode: {}: warning: {}", label, warning);
+                    }
+
+                    if json {
+                        print_json(&packet);
+                    } else {
+                        print_text(&label, &packet);
+                    }
+                },
+                Err(error) => {
+                    eprintln!("calamp-decode: {}: {}", label, error);
+                    failed = true;
+                }
+            }
+        }
+    }
+
+    process::exit(if failed { 1 } else { 0 });
+}
+
+/// Print a usage error and exit.
+fn usage_error(message: &str) -> ! {
+    eprintln!("calamp-decode: {}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+/// Read a file, or standard input when `path` is -.
+fn read_input(path: &str) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+
+    if path == "-" {
+        io::stdin().read_to_end(&mut data)?;
+    } else {
+        File::open(path)?.read_to_end(&mut data)?;
+    }
+
+    Ok(data)
+}
+
+/// Split input data into packets, each with a note appended to its label. Binary data is a single
+/// packet, text data holds one packet per non-empty line, and captures hold a packet per LMU
+/// datagram or reassembled TCP message.
+fn split_packets(format: Format, data: &[u8], ports: &[u16]) -> Result<Vec<InputPacket>, String> {
+    match format {
+        Format::Binary => {
+            Ok(vec![(String::new(), Ok(data.to_vec()))])
+        },
+        Format::Pcap => {
+            split_capture(data, ports)
+        },
+        _ => {
+            Ok(String::from_utf8_lossy(data).lines()
+                                            .filter(|line| !line.trim().is_empty())
+                                            .map(|line| (String::new(), decode_text(format, line)))
+                                            .collect())
+        }
+    }
+}
+
+/// Split a capture into packets, noting the transport and addresses of each.
+#[cfg(feature = "pcap")]
+fn split_capture(data: &[u8], ports: &[u16]) -> Result<Vec<InputPacket>, String> {
+    let reader = if ports.is_empty() {
+        CaptureReader::new()
+    } else {
+        CaptureReader::with_ports(ports.to_vec())
+    };
+
+    let packets = reader.read(data).map_err(|error| error.to_string())?;
+
+    Ok(packets.into_iter().map(|packet| {
+        (format!(" {:?} {} -> {}", packet.transport(), packet.source(), packet.destination()),
+         Ok(packet.payload().to_vec()))
+    }).collect())
+}
+
+/// Split a capture into packets.
+#[cfg(not(feature = "pcap"))]
+fn split_capture(_data: &[u8], _ports: &[u16]) -> Result<Vec<InputPacket>, String> {
+    unreachable!("--format pcap is rejected without the pcap feature")
+}
+
+/// Decode hex or base64 packet text.
+fn decode_text(format: Format, text: &str) -> Result<Vec<u8>, String> {
+    let text: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+
+    match format {
+        Format::Base64 => decode_base64(&text),
+        Format::Hex => {
+            // hex text is optionally prefixed by 0x
+            util::decode_hex(text.trim_start_matches("0x")).ok_or_else(|| {
+                "invalid hex packet".to_string()
+            })
+        },
+        Format::Binary | Format::Pcap => Ok(text.into_bytes())
+    }
+}
+
+/// Decode standard or URL-safe base64 text, with or without padding.
+fn decode_base64(text: &str) -> Result<Vec<u8>, String> {
+    let mut data  = Vec::with_capacity(text.len() * 3 / 4);
+    let mut bits  = 0u32;
+    let mut count = 0;
+
+    for c in text.trim_end_matches('=').chars() {
+        let value = match c {
+            'A'..='Z' => c as u32 - 'A' as u32,
+            'a'..='z' => c as u32 - 'a' as u32 + 26,
+            '0'..='9' => c as u32 - '0' as u32 + 52,
+            '+' | '-' => 62,
+            '/' | '_' => 63,
+            _ => return Err(format!("invalid base64 character: {}", c))
+        };
+
+        bits   = (bits << 6) | value;
+        count += 6;
+
+        if count >= 8 {
+            count -= 8;
+            data.push((bits >> count) as u8);
+        }
+    }
+
+    Ok(data)
+}
+
+/// Print a packet as text.
+fn print_text(label: &str, packet: &Packet) {
+    println!("Packet {}", label);
+    println!("Options header: {:#?}", packet.options_header());
+    println!("Message header: {:#?}", packet.message_header());
+    println!("Message: {:#?}", packet.message());
+    println!();
+}
+
+/// Print a packet as JSON.
+#[cfg(feature = "json")]
+fn print_json(packet: &Packet) {
+    println!("{}", serde_json::to_string_pretty(packet).expect("packets serialize to JSON"));
+}
+
+/// Print a packet as JSON.
+#[cfg(not(feature = "json"))]
+fn print_json(_packet: &Packet) {
+    unreachable!("--json is rejected without the json feature")
+}
@@ -0,0 +1,380 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+extern crate calamp;
+
+use std::collections::HashMap;
+use std::env;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use calamp::accumulators::Accumulators;
+use calamp::gps_fix::GpsFix;
+use calamp::lmu_time::LmuTime;
+use calamp::message::Message;
+use calamp::message::acknowledgement::AcknowledgementType;
+use calamp::message::event_report::EventReportMessage;
+use calamp::message::id_report::IdReportMessage;
+use calamp::message::null::NullMessage;
+use calamp::message::report_header::ReportHeader;
+use calamp::message::user::UserMessage;
+use calamp::message_header::{MessageHeader, MessageType, ServiceType};
+use calamp::options_header::{MobileId, OptionsHeader};
+use calamp::packet::Packet;
+use calamp::server::MAX_DATAGRAM_SIZE;
+
+const USAGE: &str = "\
+Usage: calamp-load [OPTIONS] TARGET
+
+Send acknowledged requests to the LMU server at TARGET at a fixed rate, and report the ACK
+latency percentiles once sending stops.
+
+Options:
+    -r, --rate PACKETS      Packets sent per second across all units (default 1000)
+    -d, --duration SECONDS  Time spent sending (default 10)
+    -n, --units COUNT       Number of units, each with its own socket and ESN (default 1)
+    -e, --esn ESN           ESN of the first unit, incremented per unit (default 4000000000)
+    -m, --message TYPE      Message type sent: event_report (default), id_report, null or
+                            user_data
+    -w, --wait SECONDS      Time to wait for outstanding ACKs once sending stops (default 1)
+    -h, --help              Print this message
+
+Requests without an ACK by the end of the wait are counted as lost. Exit status is 0 on success,
+and 2 on usage or I/O errors.";
+
+/// Reported latency percentiles.
+const PERCENTILES: [f64; 5] = [50.0, 90.0, 99.0, 99.9, 100.0];
+
+/// Simulated unit sending pre-encoded requests.
+struct Unit {
+    /// Encoded request, with the sequence number patched in before each send.
+    data: Vec<u8>,
+
+    /// Send times of requests awaiting an ACK, by sequence number.
+    outstanding: HashMap<u16, Instant>,
+
+    /// Last assigned sequence number.
+    sequence_number: u16,
+
+    /// Offset of the sequence number within `data`.
+    sequence_offset: usize,
+
+    /// Connected socket.
+    socket: UdpSocket
+}
+
+/// Request and ACK counts.
+#[derive(Default)]
+struct Totals {
+    /// Requests answered with a successful ACK.
+    acknowledged: u64,
+
+    /// ACK round trip times.
+    latencies: Vec<Duration>,
+
+    /// Requests answered with a NAK.
+    rejected: u64,
+
+    /// Requests sent.
+    sent: u64
+}
+
+fn main() {
+    let mut duration     = Duration::from_secs(10);
+    let mut esn          = 4_000_000_000u64;
+    let mut message_type = MessageType::EventReport;
+    let mut rate         = 1000.0;
+    let mut target       = None;
+    let mut units        = 1usize;
+    let mut wait         = Duration::from_secs(1);
+
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-r" | "--rate" => {
+                rate = number(args.next(), "--rate expects packets per second");
+
+                if rate <= 0.0 {
+                    usage_error("--rate expects packets per second");
+                }
+            },
+            "-d" | "--duration" => {
+                duration = Duration::from_secs_f64(number(args.next(),
+                                                          "--duration expects seconds"));
+            },
+            "-n" | "--units" => {
+                units = number(args.next(), "--units expects a unit count");
+
+                if units == 0 {
+                    usage_error("--units expects a unit count");
+                }
+            },
+            "-e" | "--esn" => {
+                esn = number(args.next(), "--esn expects a numeric ESN");
+            },
+            "-m" | "--message" => {
+                message_type = match args.next().as_deref() {
+                    Some("event_report") => MessageType::EventReport,
+                    Some("id_report") => MessageType::IdReport,
+                    Some("null") => MessageType::Null,
+                    Some("user_data") => MessageType::UserData,
+                    _ => usage_error("--message expects event_report, id_report, null or \
+                                      user_data")
+                };
+            },
+            "-w" | "--wait" => {
+                wait = Duration::from_secs_f64(number(args.next(), "--wait expects seconds"));
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            },
+            _ if arg.starts_with('-') => {
+                usage_error(&format!("unknown option: {}", arg))
+            },
+            _ => {
+                target = Some(arg);
+            }
+        }
+    }
+
+    let target: SocketAddr = match target {
+        Some(target) => {
+            match target.to_socket_addrs().ok().and_then(|mut addrs| addrs.next()) {
+                Some(target) => target,
+                None => usage_error(&format!("invalid target address: {}", target))
+            }
+        },
+        None => usage_error("missing target address")
+    };
+
+    let result = (0..units).map(|n| {
+        unit(MobileId::Esn((esn + n as u64).to_string()), message_type, target)
+    }).collect::<io::Result<Vec<Unit>>>().and_then(|mut units| {
+        run(&mut units, rate, duration, wait)
+    });
+
+    match result {
+        Ok(totals) => print_totals(&totals, duration),
+        Err(error) => {
+            eprintln!("calamp-load: {}", error);
+            process::exit(2);
+        }
+    }
+}
+
+/// Print a usage error and exit.
+fn usage_error(message: &str) -> ! {
+    eprintln!("calamp-load: {}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+/// Parse a numeric option value.
+fn number<T: std::str::FromStr>(value: Option<String>, message: &str) -> T {
+    value.and_then(|value| value.parse().ok()).unwrap_or_else(|| usage_error(message))
+}
+
+/// Create a unit connected to `target`, with its request encoded.
+fn unit(mobile_id: MobileId, message_type: MessageType, target: SocketAddr) -> io::Result<Unit> {
+    let now = LmuTime::from_system_time(SystemTime::now()).unwrap_or(LmuTime::from_secs(0));
+
+    let report_header = || {
+        ReportHeader::new(now, LmuTime::from_secs(now.as_secs()),
+                          GpsFix::new(331031058, -1172898431, 1200, 1111, 90, 9,
+                                      Default::default(), 9))
+    };
+
+    let message = match message_type {
+        MessageType::IdReport => {
+            let mut report = IdReportMessage::new(0, [0; 3], [0; 3]);
+
+            report.set_esn(&mobile_id.to_string());
+            report.set_mobile_id_type(mobile_id.type_value());
+
+            Message::IdReport(report)
+        },
+        MessageType::Null => {
+            Message::Null(NullMessage::new())
+        },
+        MessageType::UserData => {
+            Message::UserData(UserMessage::new(report_header(), 0, 0, vec![0x55; 64]))
+        },
+        _ => {
+            Message::EventReport(EventReportMessage::new(report_header(), 0, 0,
+                                                         Accumulators::new(vec![0; 8])))
+        }
+    };
+
+    let mut options_header = OptionsHeader::new();
+
+    options_header.set_mobile_id(Some(mobile_id));
+
+    let mut sequence_offset = Vec::new();
+
+    options_header.encode(&mut sequence_offset);
+
+    let mut data = Vec::new();
+
+    Packet::new(options_header,
+                MessageHeader::new(ServiceType::AcknowledgedRequest, message_type, 0),
+                message).encode(&mut data);
+
+    let socket = if target.is_ipv4() {
+        UdpSocket::bind("0.0.0.0:0")?
+    } else {
+        UdpSocket::bind("[::]:0")?
+    };
+
+    socket.connect(target)?;
+    socket.set_nonblocking(true)?;
+
+    Ok(Unit{
+        data,
+        outstanding:     HashMap::new(),
+        sequence_number: 0,
+        // the sequence number follows the service type and message type
+        sequence_offset: sequence_offset.len() + 2,
+        socket
+    })
+}
+
+/// Send requests round robin across `units` at `rate` per second for `duration`, then wait for
+/// outstanding ACKs for up to `wait`.
+fn run(units: &mut [Unit], rate: f64, duration: Duration, wait: Duration) -> io::Result<Totals> {
+    let mut buffer = vec![0; MAX_DATAGRAM_SIZE];
+    let mut next   = 0;
+    let mut totals = Totals::default();
+
+    let start = Instant::now();
+    let end   = start + duration;
+    let total = (duration.as_secs_f64() * rate).round() as u64;
+
+    loop {
+        let now  = Instant::now();
+        let done = totals.sent == total;
+
+        if done && (now >= end + wait || units.iter().all(|unit| unit.outstanding.is_empty())) {
+            return Ok(totals);
+        }
+
+        // request n is due n / rate seconds after the start
+        let due = if now < end {
+            total.min((now.duration_since(start).as_secs_f64() * rate) as u64 + 1)
+        } else {
+            total
+        };
+
+        while totals.sent < due {
+            let unit = &mut units[next % units.len()];
+
+            unit.sequence_number = unit.sequence_number.wrapping_add(1);
+
+            let offset = unit.sequence_offset;
+
+            unit.data[offset..offset + 2].copy_from_slice(&unit.sequence_number.to_be_bytes());
+
+            match unit.socket.send(&unit.data) {
+                Ok(_) => {},
+                // the socket buffer is full, so try again on the next pass
+                Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => {
+                    unit.sequence_number = unit.sequence_number.wrapping_sub(1);
+
+                    break;
+                },
+                Err(error) => return Err(error)
+            }
+
+            unit.outstanding.insert(unit.sequence_number, Instant::now());
+
+            next        += 1;
+            totals.sent += 1;
+        }
+
+        let mut received = false;
+
+        for unit in units.iter_mut() {
+            loop {
+                let length = match unit.socket.recv(&mut buffer) {
+                    Ok(length) => length,
+                    Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                    // ICMP port unreachable surfaces as a refused connection
+                    Err(ref error) if error.kind() == io::ErrorKind::ConnectionRefused => break,
+                    Err(error) => return Err(error)
+                };
+
+                received = true;
+
+                let packet = match Packet::parse(&buffer[..length]) {
+                    Ok((packet, _)) => packet,
+                    Err(_) => continue
+                };
+
+                let ack = match *packet.message() {
+                    Message::AckNak(ref ack) => *ack.ack(),
+                    _ => continue
+                };
+
+                if let Some(sent) = unit.outstanding.remove(&packet.message_header()
+                                                                   .sequence_number()) {
+                    totals.latencies.push(sent.elapsed());
+
+                    if ack == AcknowledgementType::Successful {
+                        totals.acknowledged += 1;
+                    } else {
+                        totals.rejected += 1;
+                    }
+                }
+            }
+        }
+
+        if !received {
+            thread::sleep(Duration::from_micros(100));
+        }
+    }
+}
+
+/// Print request counts and latency percentiles.
+fn print_totals(totals: &Totals, duration: Duration) {
+    let answered = totals.acknowledged + totals.rejected;
+
+    println!("sent {} ({:.0}/s)  acknowledged {}  rejected {}  lost {}",
+             totals.sent, totals.sent as f64 / duration.as_secs_f64().max(1e-9),
+             totals.acknowledged, totals.rejected, totals.sent - answered);
+
+    if totals.latencies.is_empty() {
+        return;
+    }
+
+    let mut latencies = totals.latencies.clone();
+
+    latencies.sort();
+
+    let columns: Vec<String> = PERCENTILES.iter().map(|percentile| {
+        // nearest rank percentile
+        let rank = (percentile / 100.0 * latencies.len() as f64).ceil() as usize;
+
+        format!("p{} {:.3}ms", percentile,
+                latencies[rank.clamp(1, latencies.len()) - 1].as_secs_f64() * 1000.0)
+    }).collect();
+
+    println!("latency  {}", columns.join("  ").replace("p100 ", "max "));
+}
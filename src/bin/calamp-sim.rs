@@ -0,0 +1,177 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+extern crate calamp;
+
+use std::env;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::process;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use calamp::sim::{Profile, Route, Simulator, UnitStats, Waypoint};
+
+const USAGE: &str = "\
+Usage: calamp-sim [OPTIONS] [SERVER]
+
+Impersonate one or many CalAmp LMUs sending to SERVER (default 127.0.0.1:20500). Each unit sends
+an ID report on boot, then periodic event reports along a route, user messages and null
+keep-alives, retrying reports until they are acknowledged.
+
+Options:
+    -n, --units COUNT            Number of units (default 1)
+    -e, --esn ESN                ESN of the first unit, incremented per unit (default 4000000000)
+    -r, --route FILE             Route file, in CSV, GPX, TOML or JSON by its extension
+                                 (default a fixed position)
+    -i, --interval SECONDS       Interval between event reports (default 60)
+    -k, --keep-alive SECONDS     Idle time before a null keep-alive, 0 to disable (default 300)
+    -u, --user-interval SECONDS  Interval between user messages, 0 to disable (default 0)
+    -s, --speed KMH              Speed between waypoints that do not supply one (default 50)
+    -d, --duration SECONDS       Stop after the given time rather than running until interrupted
+    -h, --help                   Print this message
+
+Message counts are printed every 10 seconds and on exit.";
+
+/// Interval between printed message counts.
+const STATS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Interval between unit polls.
+const TICK: Duration = Duration::from_millis(10);
+
+fn main() {
+    let mut duration = None;
+    let mut esn      = 4_000_000_000u64;
+    let mut profile  = Profile::new();
+    let mut route    = None;
+    let mut server   = "127.0.0.1:20500".to_string();
+    let mut units    = 1;
+
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-n" | "--units" => {
+                units = number(args.next(), "--units expects a unit count");
+            },
+            "-e" | "--esn" => {
+                esn = number(args.next(), "--esn expects a numeric ESN");
+            },
+            "-r" | "--route" => {
+                match args.next() {
+                    Some(path) => route = Some(path),
+                    None => usage_error("--route expects a file")
+                }
+            },
+            "-i" | "--interval" => {
+                profile.set_report_interval(seconds(args.next(), "--interval")
+                                                .unwrap_or_else(|| {
+                    usage_error("--interval expects a non-zero number of seconds")
+                }));
+            },
+            "-k" | "--keep-alive" => {
+                profile.set_keep_alive_interval(seconds(args.next(), "--keep-alive"));
+            },
+            "-u" | "--user-interval" => {
+                profile.set_user_interval(seconds(args.next(), "--user-interval"));
+            },
+            "-s" | "--speed" => {
+                profile.set_speed(number(args.next(), "--speed expects a speed in km/h"));
+            },
+            "-d" | "--duration" => {
+                duration = seconds(args.next(), "--duration");
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            },
+            _ if arg.starts_with('-') => {
+                usage_error(&format!("unknown option: {}", arg))
+            },
+            _ => {
+                server = arg;
+            }
+        }
+    }
+
+    let resolved = server.to_socket_addrs().ok().and_then(|mut addrs| addrs.next());
+
+    let server: SocketAddr = match resolved {
+        Some(server) => server,
+        None => usage_error(&format!("invalid server address: {}", server))
+    };
+
+    let route = match route {
+        Some(path) => Route::load(&path).unwrap_or_else(|error| {
+            eprintln!("calamp-sim: {}: {}", path, error);
+            process::exit(2);
+        }),
+        None => Route::new(vec![Waypoint::new(33.1031058, -117.2898431)])
+    };
+
+    let mut simulator = Simulator::spawn(units, esn, server, Arc::new(route), profile)
+                                  .unwrap_or_else(|error| {
+        eprintln!("calamp-sim: {}", error);
+        process::exit(2);
+    });
+
+    let start = Instant::now();
+
+    loop {
+        let step = match duration {
+            Some(duration) if start.elapsed() >= duration => break,
+            Some(duration) => STATS_INTERVAL.min(duration - start.elapsed()),
+            None => STATS_INTERVAL
+        };
+
+        if let Err(error) = simulator.run(step, TICK) {
+            eprintln!("calamp-sim: {}", error);
+            process::exit(2);
+        }
+
+        print_stats(start.elapsed(), &simulator.stats());
+    }
+}
+
+/// Print a usage error and exit.
+fn usage_error(message: &str) -> ! {
+    eprintln!("calamp-sim: {}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+/// Parse a numeric option value.
+fn number<T: std::str::FromStr>(value: Option<String>, message: &str) -> T {
+    value.and_then(|value| value.parse().ok()).unwrap_or_else(|| usage_error(message))
+}
+
+/// Parse a seconds option value, where 0 disables the option.
+fn seconds(value: Option<String>, option: &str) -> Option<Duration> {
+    let seconds: f64 = number(value, &format!("{} expects a number of seconds", option));
+
+    if seconds > 0.0 {
+        Some(Duration::from_secs_f64(seconds))
+    } else {
+        None
+    }
+}
+
+/// Print message counts.
+fn print_stats(elapsed: Duration, stats: &UnitStats) {
+    println!("{:>6}s  sent {}  retries {}  acknowledged {}  rejected {}  expired {}  received {}",
+             elapsed.as_secs(), stats.sent(), stats.retries(), stats.acknowledged(),
+             stats.rejected(), stats.expired(), stats.received());
+}
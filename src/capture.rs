@@ -0,0 +1,712 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+use CalAmpError;
+use packet::Packet;
+use server::DEFAULT_PORT;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// pcapng section header block type.
+const BLOCK_SECTION_HEADER: u32 = 0x0A0D_0D0A;
+
+/// pcapng interface description block type.
+const BLOCK_INTERFACE: u32 = 1;
+
+/// pcapng simple packet block type.
+const BLOCK_SIMPLE_PACKET: u32 = 3;
+
+/// pcapng enhanced packet block type.
+const BLOCK_ENHANCED_PACKET: u32 = 6;
+
+/// BSD loopback link type.
+const LINK_NULL: u32 = 0;
+
+/// Ethernet link type.
+const LINK_ETHERNET: u32 = 1;
+
+/// Raw IP link types.
+const LINK_RAW: [u32; 3] = [12, 14, 101];
+
+/// Linux cooked capture link type.
+const LINK_SLL: u32 = 113;
+
+/// Linux cooked capture v2 link type.
+const LINK_SLL2: u32 = 276;
+
+#[derive(Debug)]
+pub enum CaptureError {
+    /// Data is not a pcap or pcapng capture, or is malformed.
+    Format(String),
+
+    /// Failed to read a capture file.
+    Io(io::Error)
+}
+
+impl From<io::Error> for CaptureError {
+    fn from(error: io::Error) -> CaptureError {
+        CaptureError::Io(error)
+    }
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CaptureError::Format(ref message) => {
+                write!(formatter, "{}", message)
+            },
+            CaptureError::Io(ref error) => {
+                write!(formatter, "{}", error)
+            }
+        }
+    }
+}
+
+/// Transport a packet was captured on.
+#[derive(Clone,Copy,Debug,Eq,Hash,PartialEq)]
+pub enum Transport {
+    /// Reassembled TCP stream.
+    Tcp,
+
+    /// UDP datagram.
+    Udp
+}
+
+/// LMU packet extracted from a capture.
+#[derive(Debug)]
+pub struct CapturedPacket {
+    /// Destination address.
+    destination: SocketAddr,
+
+    /// Packet bytes.
+    payload: Vec<u8>,
+
+    /// Parse result.
+    result: Result<Packet, CalAmpError>,
+
+    /// Source address.
+    source: SocketAddr,
+
+    /// Capture timestamp. For TCP this is the timestamp of the segment completing the packet.
+    timestamp: SystemTime,
+
+    /// Transport.
+    transport: Transport
+}
+
+impl CapturedPacket {
+    /// Retrieve the destination address.
+    pub fn destination(&self) -> SocketAddr {
+        self.destination
+    }
+
+    /// Retrieve the parse error, if the payload failed to parse.
+    pub fn error(&self) -> Option<&CalAmpError> {
+        self.result.as_ref().err()
+    }
+
+    /// Retrieve the packet, if the payload parsed.
+    pub fn packet(&self) -> Option<&Packet> {
+        self.result.as_ref().ok()
+    }
+
+    /// Retrieve the packet bytes.
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    /// Retrieve the parse result.
+    pub fn result(&self) -> &Result<Packet, CalAmpError> {
+        &self.result
+    }
+
+    /// Retrieve the source address.
+    pub fn source(&self) -> SocketAddr {
+        self.source
+    }
+
+    /// Retrieve the capture timestamp.
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+
+    /// Retrieve the transport.
+    pub fn transport(&self) -> Transport {
+        self.transport
+    }
+}
+
+/// pcap and pcapng capture reader.
+///
+/// UDP datagrams to or from the configured ports are parsed as individual packets. TCP streams to
+/// or from the configured ports are reassembled in sequence order, and packets are parsed back to
+/// back from each stream. Messages whose length is implied by the end of a datagram, such as ID
+/// report extension strings and undecoded message types, consume the remainder of the data
+/// reassembled so far.
+///
+/// Supported link types are Ethernet (including VLAN tags), raw IP, BSD loopback, and Linux cooked
+/// captures. Fragmented IP packets are skipped.
+#[derive(Clone,Debug)]
+pub struct CaptureReader {
+    /// LMU ports.
+    ports: Vec<u16>
+}
+
+impl CaptureReader {
+    /// Create a new CaptureReader that extracts traffic on the default LMU port.
+    pub fn new() -> CaptureReader {
+        CaptureReader::with_ports(vec![DEFAULT_PORT])
+    }
+
+    /// Create a new CaptureReader that extracts traffic on `ports`.
+    pub fn with_ports(ports: Vec<u16>) -> CaptureReader {
+        CaptureReader{
+            ports
+        }
+    }
+
+    /// Retrieve the LMU ports.
+    pub fn ports(&self) -> &[u16] {
+        &self.ports
+    }
+
+    /// Read a pcap or pcapng capture file.
+    pub fn load<P: AsRef<Path>>(&self, path: P) -> Result<Vec<CapturedPacket>, CaptureError> {
+        self.read(&fs::read(path)?)
+    }
+
+    /// Read pcap or pcapng capture data.
+    ///
+    /// Returns the extracted packets in capture order, followed by an `Eos` error for each TCP
+    /// stream that ends with an incomplete packet. A truncated final capture record is ignored.
+    pub fn read(&self, data: &[u8]) -> Result<Vec<CapturedPacket>, CaptureError> {
+        let frames = if read_u32(data, 0, false) == Some(BLOCK_SECTION_HEADER) {
+            read_pcapng(data)?
+        } else {
+            read_pcap(data)?
+        };
+
+        let mut packets = Vec::new();
+        let mut streams = HashMap::new();
+
+        for frame in frames {
+            self.frame(&frame, &mut packets, &mut streams);
+        }
+
+        let mut streams: Vec<((SocketAddr, SocketAddr), Stream)> = streams.into_iter().collect();
+
+        streams.sort_by_key(|(_, stream)| stream.timestamp);
+
+        for ((source, destination), stream) in streams {
+            if !stream.buffer.is_empty() {
+                packets.push(CapturedPacket{
+                    destination,
+                    payload: stream.buffer,
+                    result: Err(CalAmpError::Eos),
+                    source,
+                    timestamp: stream.timestamp,
+                    transport: Transport::Tcp
+                });
+            }
+        }
+
+        Ok(packets)
+    }
+
+    /// Extract packets from a captured frame.
+    fn frame(&self, frame: &Frame, packets: &mut Vec<CapturedPacket>,
+             streams: &mut HashMap<(SocketAddr, SocketAddr), Stream>) {
+        let segment = match network_layer(frame.link_type, frame.data).and_then(transport_layer) {
+            Some(segment) => segment,
+            None => return
+        };
+
+        if !self.ports.contains(&segment.source.port())
+        && !self.ports.contains(&segment.destination.port()) {
+            return;
+        }
+
+        match segment.tcp {
+            None => {
+                packets.push(CapturedPacket{
+                    destination: segment.destination,
+                    payload: segment.payload.to_vec(),
+                    result: Packet::parse(segment.payload).map(|(packet, _)| packet),
+                    source: segment.source,
+                    timestamp: frame.timestamp,
+                    transport: Transport::Udp
+                });
+            },
+            Some((sequence, syn)) => {
+                let stream = streams.entry((segment.source, segment.destination))
+                                    .or_insert_with(Stream::new);
+
+                stream.timestamp = frame.timestamp;
+
+                stream.segment(sequence, syn, segment.payload);
+
+                while !stream.buffer.is_empty() {
+                    let result = match Packet::parse(&stream.buffer) {
+                        Err(CalAmpError::Eos) => break,
+                        result => result
+                    };
+
+                    let length = match result {
+                        Ok((_, length)) => length,
+                        Err(_) => stream.buffer.len()
+                    };
+
+                    packets.push(CapturedPacket{
+                        destination: segment.destination,
+                        payload: stream.buffer.drain(..length).collect(),
+                        result: result.map(|(packet, _)| packet),
+                        source: segment.source,
+                        timestamp: frame.timestamp,
+                        transport: Transport::Tcp
+                    });
+
+                    if length == 0 {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Default for CaptureReader {
+    fn default() -> CaptureReader {
+        CaptureReader::new()
+    }
+}
+
+/// Captured link-layer frame.
+struct Frame<'a> {
+    /// Frame bytes.
+    data: &'a [u8],
+
+    /// Link type.
+    link_type: u32,
+
+    /// Capture timestamp.
+    timestamp: SystemTime
+}
+
+/// Transport-layer segment.
+struct Segment<'a> {
+    /// Destination address.
+    destination: SocketAddr,
+
+    /// Payload bytes.
+    payload: &'a [u8],
+
+    /// Source address.
+    source: SocketAddr,
+
+    /// TCP sequence number and SYN flag, or `None` for UDP.
+    tcp: Option<(u32, bool)>
+}
+
+/// TCP stream being reassembled in one direction.
+struct Stream {
+    /// Reassembled data not yet parsed.
+    buffer: Vec<u8>,
+
+    /// Next expected sequence number, once known.
+    next: Option<u32>,
+
+    /// Segments received ahead of the next expected sequence number.
+    pending: Vec<(u32, Vec<u8>)>,
+
+    /// Timestamp of the latest segment.
+    timestamp: SystemTime
+}
+
+impl Stream {
+    /// Create a new Stream.
+    fn new() -> Stream {
+        Stream{
+            buffer: Vec::new(),
+            next: None,
+            pending: Vec::new(),
+            timestamp: UNIX_EPOCH
+        }
+    }
+
+    /// Add a segment, appending any data that is now in sequence to the buffer.
+    ///
+    /// Streams captured without their SYN begin at the first segment seen.
+    fn segment(&mut self, mut sequence: u32, syn: bool, payload: &[u8]) {
+        if syn {
+            // the SYN occupies one sequence number
+            sequence  = sequence.wrapping_add(1);
+            self.next = Some(sequence);
+        }
+
+        if payload.is_empty() {
+            return;
+        }
+
+        let next = *self.next.get_or_insert(sequence);
+
+        if (sequence.wrapping_sub(next) as i32) > 0 {
+            self.pending.push((sequence, payload.to_vec()));
+
+            return;
+        }
+
+        self.append(sequence, payload);
+
+        // drain segments that are now in sequence
+        while let Some(position) = self.pending.iter().position(|&(sequence, _)| {
+            (sequence.wrapping_sub(self.next.unwrap_or(sequence)) as i32) <= 0
+        }) {
+            let (sequence, payload) = self.pending.swap_remove(position);
+
+            self.append(sequence, &payload);
+        }
+    }
+
+    /// Append the part of a segment beyond the next expected sequence number.
+    fn append(&mut self, sequence: u32, payload: &[u8]) {
+        let next = self.next.unwrap_or(sequence);
+        let skip = next.wrapping_sub(sequence) as usize;
+
+        if skip < payload.len() {
+            self.buffer.extend_from_slice(&payload[skip..]);
+            self.next = Some(next.wrapping_add((payload.len() - skip) as u32));
+        }
+    }
+}
+
+/// Read frames from a pcap capture.
+fn read_pcap(data: &[u8]) -> Result<Vec<Frame<'_>>, CaptureError> {
+    let (big_endian, nanoseconds) = match read_u32(data, 0, false) {
+        Some(0xA1B2_C3D4) => (false, false),
+        Some(0xA1B2_3C4D) => (false, true),
+        Some(0xD4C3_B2A1) => (true, false),
+        Some(0x4D3C_B2A1) => (true, true),
+        _ => {
+            return Err(CaptureError::Format("unrecognized capture format".to_string()));
+        }
+    };
+
+    // the upper bits of the link type field hold FCS details
+    let link_type = match read_u32(data, 20, big_endian) {
+        Some(link_type) => link_type & 0x0FFF_FFFF,
+        None => return Err(CaptureError::Format("truncated pcap header".to_string()))
+    };
+
+    let mut frames = Vec::new();
+    let mut offset = 24;
+
+    while let (Some(seconds), Some(fraction), Some(length)) = (read_u32(data, offset, big_endian),
+                                                               read_u32(data, offset + 4,
+                                                                        big_endian),
+                                                               read_u32(data, offset + 8,
+                                                                        big_endian)) {
+        let start = offset + 16;
+        let end   = start + length as usize;
+
+        if end > data.len() {
+            break;
+        }
+
+        let nanos = if nanoseconds { fraction } else { fraction.saturating_mul(1_000) };
+
+        frames.push(Frame{
+            data: &data[start..end],
+            link_type,
+            timestamp: UNIX_EPOCH + Duration::new(u64::from(seconds), 0)
+                                  + Duration::from_nanos(u64::from(nanos))
+        });
+
+        offset = end;
+    }
+
+    Ok(frames)
+}
+
+/// Read frames from a pcapng capture.
+fn read_pcapng(data: &[u8]) -> Result<Vec<Frame<'_>>, CaptureError> {
+    let mut big_endian = false;
+    let mut frames     = Vec::new();
+    let mut interfaces = Vec::new();
+    let mut offset     = 0;
+
+    while offset + 12 <= data.len() {
+        // the section header block type reads the same in either byte order
+        if read_u32(data, offset, false) == Some(BLOCK_SECTION_HEADER) {
+            big_endian = match read_u32(data, offset + 8, false) {
+                Some(0x1A2B_3C4D) => false,
+                Some(0x4D3C_2B1A) => true,
+                _ => {
+                    return Err(CaptureError::Format("invalid pcapng byte order".to_string()));
+                }
+            };
+
+            interfaces.clear();
+        }
+
+        let block_type = read_u32(data, offset, big_endian).unwrap_or(0);
+        let length     = read_u32(data, offset + 4, big_endian).unwrap_or(0) as usize;
+
+        if length < 12 || length % 4 != 0 {
+            return Err(CaptureError::Format(format!("invalid pcapng block length at {}",
+                                                    offset)));
+        }
+
+        if offset + length > data.len() {
+            break;
+        }
+
+        let body = &data[offset + 8..offset + length - 4];
+
+        match block_type {
+            BLOCK_INTERFACE => {
+                let link_type = read_u16(body, 0, big_endian).unwrap_or(0);
+
+                interfaces.push((u32::from(link_type), interface_resolution(body, big_endian)));
+            },
+            BLOCK_ENHANCED_PACKET => {
+                let interface = read_u32(body, 0, big_endian).unwrap_or(0) as usize;
+                let high      = read_u32(body, 4, big_endian).unwrap_or(0);
+                let low       = read_u32(body, 8, big_endian).unwrap_or(0);
+                let captured  = read_u32(body, 12, big_endian).unwrap_or(0) as usize;
+
+                let &(link_type, resolution) = interfaces.get(interface).ok_or_else(|| {
+                    CaptureError::Format(format!("undefined pcapng interface {}", interface))
+                })?;
+
+                if 20 + captured <= body.len() {
+                    frames.push(Frame{
+                        data: &body[20..20 + captured],
+                        link_type,
+                        timestamp: pcapng_timestamp((u64::from(high) << 32) | u64::from(low),
+                                                    resolution)
+                    });
+                }
+            },
+            BLOCK_SIMPLE_PACKET => {
+                let original = read_u32(body, 0, big_endian).unwrap_or(0) as usize;
+
+                if let Some(&(link_type, _)) = interfaces.first() {
+                    frames.push(Frame{
+                        data: &body[4..body.len().min(4 + original)],
+                        link_type,
+                        timestamp: UNIX_EPOCH
+                    });
+                }
+            },
+            _ => {
+                // other blocks carry nothing needed here
+            }
+        }
+
+        offset += length;
+    }
+
+    Ok(frames)
+}
+
+/// Retrieve the timestamp resolution option from an interface description block body, defaulting
+/// to microseconds.
+fn interface_resolution(body: &[u8], big_endian: bool) -> u8 {
+    let mut offset = 8;
+
+    while let (Some(code), Some(length)) = (read_u16(body, offset, big_endian),
+                                            read_u16(body, offset + 2, big_endian)) {
+        if code == 0 {
+            break;
+        }
+
+        if code == 9 && length == 1 {
+            if let Some(&resolution) = body.get(offset + 4) {
+                return resolution;
+            }
+        }
+
+        // option values are padded to 32 bits
+        offset += 4 + ((length as usize + 3) & !3);
+    }
+
+    6
+}
+
+/// Convert a pcapng timestamp in units of `resolution` to a SystemTime.
+///
+/// A resolution with the high bit set is a negative power of 2, and otherwise a negative power of
+/// 10.
+fn pcapng_timestamp(timestamp: u64, resolution: u8) -> SystemTime {
+    let units = if resolution & 0x80 == 0 {
+        10u128.checked_pow(u32::from(resolution))
+    } else {
+        2u128.checked_pow(u32::from(resolution & 0x7F))
+    };
+
+    let units = match units {
+        Some(units) if units > 0 => units,
+        _ => return UNIX_EPOCH
+    };
+
+    let timestamp = u128::from(timestamp);
+    let nanos     = (timestamp % units) * 1_000_000_000 / units;
+
+    UNIX_EPOCH + Duration::new((timestamp / units) as u64, nanos as u32)
+}
+
+/// Retrieve the IP packet carried by a link-layer frame.
+fn network_layer(link_type: u32, data: &[u8]) -> Option<&[u8]> {
+    let (protocol, offset) = match link_type {
+        LINK_NULL => {
+            // the address family is in host byte order, so rely on the IP version instead
+            return data.get(4..);
+        },
+        LINK_ETHERNET => {
+            let mut offset   = 14;
+            let mut protocol = read_u16(data, 12, true)?;
+
+            // skip 802.1Q and 802.1ad tags
+            while protocol == 0x8100 || protocol == 0x88A8 {
+                protocol  = read_u16(data, offset + 2, true)?;
+                offset   += 4;
+            }
+
+            (protocol, offset)
+        },
+        LINK_SLL => {
+            (read_u16(data, 14, true)?, 16)
+        },
+        LINK_SLL2 => {
+            (read_u16(data, 0, true)?, 20)
+        },
+        _ if LINK_RAW.contains(&link_type) => {
+            return Some(data);
+        },
+        _ => {
+            return None;
+        }
+    };
+
+    if protocol == 0x0800 || protocol == 0x86DD {
+        data.get(offset..)
+    } else {
+        None
+    }
+}
+
+/// Retrieve the UDP or TCP segment carried by an IP packet.
+fn transport_layer(data: &[u8]) -> Option<Segment<'_>> {
+    let (source, destination, protocol, payload) = match data.first()? >> 4 {
+        4 => {
+            let header_length = ((data[0] & 0x0F) as usize) * 4;
+            let total_length  = read_u16(data, 2, true)? as usize;
+            let fragment      = read_u16(data, 6, true)?;
+
+            if fragment & 0x3FFF != 0 || header_length < 20 || total_length > data.len()
+            || header_length > total_length {
+                // fragmented or malformed
+                return None;
+            }
+
+            let source      = Ipv4Addr::new(data[12], data[13], data[14], data[15]);
+            let destination = Ipv4Addr::new(data[16], data[17], data[18], data[19]);
+
+            (IpAddr::V4(source), IpAddr::V4(destination), data[9],
+             &data[header_length..total_length])
+        },
+        6 => {
+            let payload_length  = read_u16(data, 4, true)? as usize;
+            let mut next_header = *data.get(6)?;
+            let mut offset      = 40;
+
+            if offset + payload_length > data.len() {
+                return None;
+            }
+
+            let mut source      = [0; 16];
+            let mut destination = [0; 16];
+
+            source.copy_from_slice(&data[8..24]);
+            destination.copy_from_slice(&data[24..40]);
+
+            // skip hop-by-hop, routing and destination options headers
+            while next_header == 0 || next_header == 43 || next_header == 60 {
+                next_header  = *data.get(offset)?;
+                offset      += (*data.get(offset + 1)? as usize + 1) * 8;
+            }
+
+            if offset > 40 + payload_length {
+                return None;
+            }
+
+            (IpAddr::V6(Ipv6Addr::from(source)), IpAddr::V6(Ipv6Addr::from(destination)),
+             next_header, &data[offset..40 + payload_length])
+        },
+        _ => {
+            return None;
+        }
+    };
+
+    let source_port      = read_u16(payload, 0, true)?;
+    let destination_port = read_u16(payload, 2, true)?;
+
+    let (payload, tcp) = match protocol {
+        6 => {
+            let header_length = ((*payload.get(12)? >> 4) as usize) * 4;
+            let syn           = payload.get(13)? & 0x02 != 0;
+
+            (payload.get(header_length..)?, Some((read_u32(payload, 4, true)?, syn)))
+        },
+        17 => {
+            let length = read_u16(payload, 4, true)? as usize;
+
+            (payload.get(8..length.min(payload.len()).max(8))?, None)
+        },
+        _ => {
+            return None;
+        }
+    };
+
+    Some(Segment{
+        destination: SocketAddr::new(destination, destination_port),
+        payload,
+        source: SocketAddr::new(source, source_port),
+        tcp
+    })
+}
+
+/// Read a u16 at `offset`, if available.
+fn read_u16(data: &[u8], offset: usize, big_endian: bool) -> Option<u16> {
+    let bytes = [*data.get(offset)?, *data.get(offset + 1)?];
+
+    Some(if big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+}
+
+/// Read a u32 at `offset`, if available.
+fn read_u32(data: &[u8], offset: usize, big_endian: bool) -> Option<u32> {
+    let bytes = [*data.get(offset)?, *data.get(offset + 1)?, *data.get(offset + 2)?,
+                 *data.get(offset + 3)?];
+
+    Some(if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+}
@@ -0,0 +1,171 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+use CalAmpError;
+use bcd;
+use options_header::{EncryptionType, MobileId, OptionsHeader};
+
+/// Message body cipher.
+///
+/// Packets parsed with `Packet::parse_with_cipher` are decrypted after the message header and
+/// before the message body is dispatched on its type, and packets encoded with
+/// `Packet::encode_with_cipher` are encrypted after the message header. Implementations derive
+/// their working key from the `CipherKey`, which carries the identifier selected by the encryption
+/// type and the random key supplied in the options extension.
+pub trait Cipher {
+    /// Decrypt a message body in place.
+    fn decrypt(&self, key: &CipherKey, data: &mut [u8]);
+
+    /// Encrypt a message body in place.
+    fn encrypt(&self, key: &CipherKey, data: &mut [u8]);
+}
+
+/// RC4 message body cipher.
+///
+/// The body is XORed with an RC4 keystream, keyed by the identifier selected by the encryption
+/// type followed by the 4 byte random key. Encryption and decryption are the same operation.
+///
+/// This scheme has not been checked against traffic from a unit with encryption enabled.
+#[derive(Clone,Copy,Debug,Default)]
+pub struct Rc4Cipher;
+
+impl Rc4Cipher {
+    /// Create a new Rc4Cipher.
+    pub fn new() -> Rc4Cipher {
+        Rc4Cipher
+    }
+
+    /// XOR `data` with the keystream for `key`.
+    fn apply(key: &CipherKey, data: &mut [u8]) {
+        let mut seed = key.identifier.clone();
+
+        seed.extend_from_slice(&key.random_key);
+
+        // RC4 key scheduling
+        let mut state: Vec<u8> = (0..=255).collect();
+        let mut j: u8          = 0;
+
+        for i in 0..256 {
+            j = j.wrapping_add(state[i]).wrapping_add(seed[i % seed.len()]);
+            state.swap(i, j as usize);
+        }
+
+        // RC4 keystream generation
+        let mut i: u8 = 0;
+
+        j = 0;
+
+        for byte in data.iter_mut() {
+            i = i.wrapping_add(1);
+            j = j.wrapping_add(state[i as usize]);
+            state.swap(i as usize, j as usize);
+
+            *byte ^= state[state[i as usize].wrapping_add(state[j as usize]) as usize];
+        }
+    }
+}
+
+impl Cipher for Rc4Cipher {
+    fn decrypt(&self, key: &CipherKey, data: &mut [u8]) {
+        Rc4Cipher::apply(key, data);
+    }
+
+    fn encrypt(&self, key: &CipherKey, data: &mut [u8]) {
+        Rc4Cipher::apply(key, data);
+    }
+}
+
+/// Key material for an encrypted packet.
+#[derive(Clone,Debug,Eq,PartialEq)]
+pub struct CipherKey {
+    /// Encryption type.
+    encryption_type: EncryptionType,
+
+    /// Identifier selected by the encryption type, as encoded on the wire.
+    identifier: Vec<u8>,
+
+    /// Random key.
+    random_key: [u8; 4]
+}
+
+impl CipherKey {
+    /// Create a new CipherKey.
+    pub fn new(encryption_type: EncryptionType, identifier: Vec<u8>, random_key: [u8; 4])
+    -> CipherKey {
+        CipherKey{
+            encryption_type,
+            identifier,
+            random_key
+        }
+    }
+
+    /// Select the key material for a packet from its options header.
+    ///
+    /// The identifier is the BCD encoded ESN for ESN encryption, taken from the options extension
+    /// or else an ESN mobile ID; the BCD encoded IMEI or MEID mobile ID for IMEI/MEID encryption;
+    /// and the encoded mobile ID of any type for mobile ID encryption.
+    ///
+    /// Returns `None` when the packet is not encrypted, and `CalAmpError::EncryptionIdentifier`
+    /// when the selected identifier is not present.
+    pub fn from_options_header(options_header: &OptionsHeader)
+    -> Result<Option<CipherKey>, CalAmpError> {
+        let extension = match *options_header.extension() {
+            Some(ref extension) => extension,
+            None => return Ok(None)
+        };
+
+        let (encryption_type, random_key) = match (extension.encryption_type(),
+                                                   *extension.encryption_service()) {
+            (Some(EncryptionType::Unencrypted), _) | (None, _) | (_, None) => return Ok(None),
+            (Some(encryption_type), Some(random_key)) => (encryption_type, random_key)
+        };
+
+        let mut identifier = Vec::new();
+
+        match (encryption_type, extension.esn(), options_header.mobile_id()) {
+            (EncryptionType::Esn, Some(esn), _) => {
+                bcd::encode(esn, &mut identifier);
+            },
+            (EncryptionType::Esn, None, Some(mobile_id @ MobileId::Esn(_))) |
+            (EncryptionType::ImeiMeid, _, Some(mobile_id @ MobileId::ImeiEid(_))) |
+            (EncryptionType::MobileId, _, Some(mobile_id)) => {
+                mobile_id.encode(&mut identifier);
+            },
+            _ => {
+                return Err(CalAmpError::EncryptionIdentifier(encryption_type));
+            }
+        }
+
+        Ok(Some(CipherKey::new(encryption_type, identifier, random_key)))
+    }
+
+    /// Retrieve the encryption type.
+    pub fn encryption_type(&self) -> EncryptionType {
+        self.encryption_type
+    }
+
+    /// Retrieve the identifier selected by the encryption type, as encoded on the wire.
+    pub fn identifier(&self) -> &[u8] {
+        &self.identifier
+    }
+
+    /// Retrieve the random key.
+    pub fn random_key(&self) -> [u8; 4] {
+        self.random_key
+    }
+}
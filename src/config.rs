@@ -0,0 +1,55 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+use ConfigError;
+
+use serde::de::DeserializeOwned;
+
+use std::fs;
+use std::path::Path;
+
+/// Load a configuration file, choosing the format from the `.toml` or `.json` extension.
+pub fn load<T: DeserializeOwned>(path: &Path) -> Result<T, ConfigError> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        #[cfg(feature = "toml")]
+        Some("toml") => from_toml(&fs::read_to_string(path)?),
+        #[cfg(feature = "json")]
+        Some("json") => from_json(&fs::read_to_string(path)?),
+        _ => Err(ConfigError::Format(path.to_path_buf()))
+    }
+}
+
+/// Parse a configuration from TOML text.
+#[cfg(feature = "toml")]
+pub fn from_toml<T: DeserializeOwned>(text: &str) -> Result<T, ConfigError> {
+    toml::from_str(text).map_err(|error| {
+        match error.span() {
+            Some(span) => {
+                ConfigError::Line(text[..span.start].matches('\n').count() + 1,
+                                  error.message().to_string())
+            },
+            None => ConfigError::Invalid(error.message().to_string())
+        }
+    })
+}
+
+/// Parse a configuration from JSON text.
+#[cfg(feature = "json")]
+pub fn from_json<T: DeserializeOwned>(text: &str) -> Result<T, ConfigError> {
+    serde_json::from_str(text).map_err(|error| ConfigError::Line(error.line(), error.to_string()))
+}
@@ -0,0 +1,296 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+use message_header::MessageType;
+use options_header::MobileId;
+use packet::Packet;
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+use std::time::{Duration, Instant};
+
+/// Default maximum number of units tracked at once.
+pub const DEFAULT_CAPACITY: usize = 65536;
+
+/// Deduplication verdict.
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum Verdict {
+    /// The message has already been received within the window. It should be acknowledged again,
+    /// but not processed.
+    Duplicate,
+
+    /// The message has not been received before.
+    New,
+
+    /// The sequence number is too far behind the window to tell. The window restarts from this
+    /// message.
+    OutOfWindow
+}
+
+/// Sequence state of a single unit.
+#[derive(Clone,Debug)]
+struct Window {
+    /// Highest sequence number received, in 16-bit serial number order.
+    highest: u16,
+
+    /// Time the last message was received.
+    last_seen: Instant,
+
+    /// Recently received sequence numbers, message types and body fingerprints, oldest first.
+    received: VecDeque<(u16, MessageType, u64)>,
+
+    /// Update serial, ordering windows last seen at the same time.
+    serial: u64
+}
+
+/// Per-unit duplicate and retransmission detection.
+///
+/// Messages are identified by the sequence number and message type of their message header.
+/// Sequence numbers are compared using serial number arithmetic, so the window carries across the
+/// 16-bit wraparound.
+///
+/// A retransmission repeats the message body as well, so a known sequence number carrying a
+/// different body means the unit rebooted and restarted its sequence numbers, as does falling back
+/// to a sequence number below the window size after sending higher numbers. The window restarts in
+/// both cases.
+///
+/// Windows idle for longer than the idle timeout are removed by `expire()`, and restart when their
+/// unit is heard from again. At most `capacity()` units are tracked, and the least recently seen
+/// unit is forgotten to make room for a new one.
+#[derive(Clone,Debug)]
+pub struct Deduplicator {
+    /// Maximum number of units tracked.
+    capacity: usize,
+
+    /// Idle timeout.
+    idle_timeout: Duration,
+
+    /// Mobile IDs ordered by last seen time and update serial.
+    order: BTreeMap<(Instant, u64), MobileId>,
+
+    /// Next update serial.
+    serial: u64,
+
+    /// Window size.
+    size: u16,
+
+    /// Windows keyed by mobile ID.
+    windows: HashMap<MobileId, Window>
+}
+
+impl Deduplicator {
+    /// Create a new Deduplicator that remembers the last `size` messages of each unit, and forgets
+    /// units idle for longer than `idle_timeout`.
+    pub fn new(size: u16, idle_timeout: Duration) -> Deduplicator {
+        Deduplicator{
+            capacity:     DEFAULT_CAPACITY,
+            idle_timeout,
+            order:        BTreeMap::new(),
+            serial:       0,
+            size:         size.max(1),
+            windows:      HashMap::new()
+        }
+    }
+
+    /// Check `packet` received at time `now`, and record it as received.
+    ///
+    /// Packets without a mobile ID cannot be told apart, and are always new.
+    pub fn check(&mut self, packet: &Packet, now: Instant) -> Verdict {
+        let mobile_id = match *packet.options_header().mobile_id() {
+            Some(ref mobile_id) => mobile_id,
+            None => return Verdict::New
+        };
+
+        let sequence_number = packet.message_header().sequence_number();
+        let message_type    = *packet.message_header().message_type();
+        let fingerprint     = fingerprint(packet);
+        let idle_timeout    = self.idle_timeout;
+        let size            = self.size;
+        let entry           = (sequence_number, message_type, fingerprint);
+        let serial          = self.serial;
+
+        match self.windows.get(mobile_id) {
+            Some(window) => {
+                self.order.remove(&(window.last_seen, window.serial));
+            },
+            None => {
+                while self.windows.len() >= self.capacity && self.evict() {}
+            }
+        }
+
+        self.serial += 1;
+        self.order.insert((now, serial), mobile_id.clone());
+
+        let window = match self.windows.get_mut(mobile_id) {
+            Some(window) if now.saturating_duration_since(window.last_seen) <= idle_timeout => {
+                window
+            },
+            _ => {
+                self.windows.insert(mobile_id.clone(), Window::new(entry, now, serial));
+
+                return Verdict::New;
+            }
+        };
+
+        window.last_seen = now;
+        window.serial    = serial;
+
+        let known = window.received.iter().find(|received| {
+            received.0 == sequence_number && received.1 == message_type
+        });
+
+        match known.map(|received| received.2) {
+            Some(received) if received == fingerprint => {
+                return Verdict::Duplicate;
+            },
+            Some(_) => {
+                // the unit rebooted and reused a sequence number for a different message
+                *window = Window::new(entry, now, serial);
+
+                return Verdict::New;
+            },
+            None => {
+            }
+        }
+
+        // serial number distance from the highest sequence number
+        let distance = sequence_number.wrapping_sub(window.highest) as i16;
+
+        if distance > 0 {
+            window.highest = sequence_number;
+        } else if -(distance as i32) >= size as i32 {
+            let verdict = if sequence_number < size {
+                // the unit rebooted and restarted its sequence numbers
+                Verdict::New
+            } else {
+                Verdict::OutOfWindow
+            };
+
+            *window = Window::new(entry, now, serial);
+
+            return verdict;
+        }
+
+        window.received.push_back(entry);
+
+        while window.received.len() > size as usize {
+            window.received.pop_front();
+        }
+
+        Verdict::New
+    }
+
+    /// Remove windows that have been idle for longer than the idle timeout at time `now`.
+    ///
+    /// Returns the mobile IDs of the removed windows.
+    pub fn expire(&mut self, now: Instant) -> Vec<MobileId> {
+        let mut expired = Vec::new();
+
+        while let Some(entry) = self.order.first_entry() {
+            if now.saturating_duration_since(entry.key().0) <= self.idle_timeout {
+                break;
+            }
+
+            let mobile_id = entry.remove();
+
+            self.windows.remove(&mobile_id);
+            expired.push(mobile_id);
+        }
+
+        expired
+    }
+
+    /// Forget the least recently seen window.
+    ///
+    /// Returns `false` when no units are tracked.
+    fn evict(&mut self) -> bool {
+        match self.order.pop_first() {
+            Some((_, mobile_id)) => {
+                self.windows.remove(&mobile_id);
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Forget the window of `mobile_id`.
+    pub fn remove(&mut self, mobile_id: &MobileId) {
+        if let Some(window) = self.windows.remove(mobile_id) {
+            self.order.remove(&(window.last_seen, window.serial));
+        }
+    }
+
+    /// Retrieve the maximum number of units tracked.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Set the maximum number of units tracked, forgetting the least recently seen units beyond it.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+
+        while self.windows.len() > self.capacity && self.evict() {}
+    }
+
+    /// Retrieve the idle timeout.
+    pub fn idle_timeout(&self) -> Duration {
+        self.idle_timeout
+    }
+
+    /// Retrieve the number of units tracked.
+    pub fn len(&self) -> usize {
+        self.windows.len()
+    }
+
+    /// Indicates that no units are tracked.
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+
+    /// Retrieve the window size.
+    pub fn size(&self) -> u16 {
+        self.size
+    }
+}
+
+impl Window {
+    /// Create a new Window starting at `entry`, received at time `now` with update serial `serial`.
+    fn new(entry: (u16, MessageType, u64), now: Instant, serial: u64) -> Window {
+        let mut received = VecDeque::new();
+
+        received.push_back(entry);
+
+        Window{
+            highest:   entry.0,
+            last_seen: now,
+            received,
+            serial
+        }
+    }
+}
+
+/// Fingerprint the message body of `packet`.
+fn fingerprint(packet: &Packet) -> u64 {
+    let mut body   = Vec::new();
+    let mut hasher = DefaultHasher::new();
+
+    packet.message().encode(&mut body);
+    hasher.write(&body);
+    hasher.finish()
+}
@@ -0,0 +1,353 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+use CalAmpError;
+use packet::Packet;
+use std::cmp;
+use std::fmt;
+use std::ops::Range;
+
+/// Maximum bytes of hex shown on a rendered line.
+const HEX_BYTES: usize = 8;
+
+/// Decoded field and the bytes it occupies.
+#[derive(Clone,Debug,PartialEq)]
+pub struct Span {
+    /// Nested spans, for groups such as headers.
+    children: Vec<Span>,
+
+    /// Field name.
+    name: String,
+
+    /// Byte range within the traced data.
+    range: Range<usize>,
+
+    /// Interpreted value, empty for groups.
+    value: String
+}
+
+impl Span {
+    /// Retrieve the nested spans.
+    pub fn children(&self) -> &[Span] {
+        &self.children
+    }
+
+    /// Retrieve the field name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Retrieve the byte range within the traced data.
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    /// Retrieve the interpreted value.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Find the first span named `name`, searching this span and its descendants depth-first.
+    pub fn find(&self, name: &str) -> Option<&Span> {
+        if self.name == name {
+            return Some(self);
+        }
+
+        self.children.iter().filter_map(|span| span.find(name)).next()
+    }
+}
+
+/// Span recorder passed to the `parse_traced` parsers.
+///
+/// Each parser has a `parse_traced` counterpart that records the byte span and interpreted value
+/// of every field it reads. Parsers record offsets relative to the slice they are given, and
+/// groups opened with `begin` translate them into offsets within the traced data.
+#[derive(Debug)]
+pub struct Trace {
+    /// Offset of the innermost open group.
+    base: usize,
+
+    /// Indicates spans are recorded.
+    enabled: bool,
+
+    /// Furthest offset reached by a recorded span.
+    furthest: usize,
+
+    /// Open groups, each with the base offset of its parent.
+    open: Vec<(Span, usize)>,
+
+    /// Completed top-level spans.
+    spans: Vec<Span>
+}
+
+impl Trace {
+    /// Create a new Trace that records spans.
+    pub fn new() -> Trace {
+        Trace{
+            base: 0,
+            enabled: true,
+            furthest: 0,
+            open: Vec::new(),
+            spans: Vec::new()
+        }
+    }
+
+    /// Create a new Trace that records nothing, used by the untraced parsers.
+    pub fn disabled() -> Trace {
+        Trace{
+            enabled: false,
+            ..Trace::new()
+        }
+    }
+
+    /// Retrieve the furthest offset reached by a recorded span.
+    ///
+    /// After a failed parse this is where decoding stopped.
+    pub fn furthest(&self) -> usize {
+        self.furthest
+    }
+
+    /// Close any open groups and retrieve the recorded spans.
+    ///
+    /// Groups left open by a failed parse end at the furthest offset reached.
+    pub fn into_spans(mut self) -> Vec<Span> {
+        while let Some((mut span, parent)) = self.open.pop() {
+            span.range.end = cmp::max(span.range.start, self.furthest);
+            self.base      = parent;
+
+            self.push(span);
+        }
+
+        self.spans
+    }
+
+    /// Record a field spanning `start..end` of the current slice, described by `value`.
+    ///
+    /// `value` is only evaluated when spans are recorded.
+    pub(crate) fn field<F: FnOnce() -> String>(&mut self, name: &str, start: usize, end: usize,
+                                               value: F) {
+        if !self.enabled {
+            return;
+        }
+
+        let span = Span{
+            children: Vec::new(),
+            name: name.to_string(),
+            range: self.base + start..self.base + end,
+            value: value()
+        };
+
+        self.furthest = cmp::max(self.furthest, span.range.end);
+
+        self.push(span);
+    }
+
+    /// Open a group starting at `start` of the current slice. Spans recorded until the matching
+    /// `end` nest within the group, with offsets relative to `start`.
+    pub(crate) fn begin(&mut self, name: &str, start: usize) {
+        if !self.enabled {
+            return;
+        }
+
+        let start = self.base + start;
+
+        self.open.push((Span{
+            children: Vec::new(),
+            name: name.to_string(),
+            range: start..start,
+            value: String::new()
+        }, self.base));
+
+        self.base = start;
+    }
+
+    /// Close the innermost group after `length` bytes.
+    pub(crate) fn end(&mut self, length: usize) {
+        if !self.enabled {
+            return;
+        }
+
+        if let Some((mut span, parent)) = self.open.pop() {
+            span.range.end = span.range.start + length;
+            self.base      = parent;
+            self.furthest  = cmp::max(self.furthest, span.range.end);
+
+            self.push(span);
+        }
+    }
+
+    /// Insert a completed span into the innermost open group, ordered by offset.
+    ///
+    /// Parsers may record a field after the fields that follow it, such as a mobile id that can
+    /// only be interpreted once its type has been read.
+    fn push(&mut self, span: Span) {
+        let spans = match self.open.last_mut() {
+            Some(&mut (ref mut group, _)) => &mut group.children,
+            None => &mut self.spans
+        };
+
+        let position = spans.iter()
+                            .rposition(|other| other.range.start <= span.range.start)
+                            .map_or(0, |position| position + 1);
+
+        spans.insert(position, span);
+    }
+}
+
+impl Default for Trace {
+    fn default() -> Trace {
+        Trace::new()
+    }
+}
+
+/// Traced packet, rendered as an indented tree alongside the hex by `Display`.
+#[derive(Debug)]
+pub struct Dissection {
+    /// Traced data.
+    data: Vec<u8>,
+
+    /// Parse result.
+    result: Result<Packet, CalAmpError>,
+
+    /// Recorded spans.
+    spans: Vec<Span>,
+
+    /// Offset at which decoding stopped.
+    stop: usize
+}
+
+impl Dissection {
+    /// Retrieve the traced data.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Retrieve the parse error, if the packet failed to parse.
+    pub fn error(&self) -> Option<&CalAmpError> {
+        self.result.as_ref().err()
+    }
+
+    /// Retrieve the packet, if it parsed.
+    pub fn packet(&self) -> Option<&Packet> {
+        self.result.as_ref().ok()
+    }
+
+    /// Retrieve the recorded spans.
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+
+    /// Find the first span named `name`, searching depth-first.
+    pub fn find(&self, name: &str) -> Option<&Span> {
+        self.spans.iter().filter_map(|span| span.find(name)).next()
+    }
+
+    /// Offset at which decoding stopped: the packet length after a successful parse, or the
+    /// furthest offset reached before an error.
+    pub fn stop(&self) -> usize {
+        self.stop
+    }
+
+    /// Write a span and its children at `depth`.
+    fn write_span(&self, formatter: &mut fmt::Formatter, span: &Span, depth: usize)
+    -> fmt::Result {
+        let hex = if span.children.is_empty() {
+            let bytes = &self.data[span.range.start..cmp::min(span.range.end, self.data.len())];
+
+            let mut hex: Vec<String> = bytes.iter()
+                                            .take(HEX_BYTES)
+                                            .map(|b| format!("{:02x}", b))
+                                            .collect();
+
+            if bytes.len() > HEX_BYTES {
+                hex.pop();
+                hex.push("..".to_string());
+            }
+
+            hex.join(" ")
+        } else {
+            String::new()
+        };
+
+        write!(formatter, "{:04x}..{:04x}  {:<width$}  {:indent$}{}", span.range.start,
+                                                                   span.range.end,
+                                                                   hex,
+                                                                   "",
+                                                                   span.name,
+                                                                   width = HEX_BYTES * 3 - 1,
+                                                                   indent = depth * 2)?;
+
+        if span.value.is_empty() {
+            writeln!(formatter)?;
+        } else {
+            writeln!(formatter, ": {}", span.value)?;
+        }
+
+        for child in &span.children {
+            self.write_span(formatter, child, depth + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Dissection {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        for span in &self.spans {
+            self.write_span(formatter, span, 0)?;
+        }
+
+        if let Err(ref error) = self.result {
+            writeln!(formatter, "error at {:04x}: {}", self.stop, error)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Trace a packet.
+///
+/// Decoding stops at the first error, and the spans read until then are kept. Bytes following a
+/// complete packet are recorded as a trailing data span.
+pub fn dissect(slice: &[u8]) -> Dissection {
+    let mut trace = Trace::new();
+
+    let (result, stop) = match Packet::parse_traced(slice, &mut trace) {
+        Ok((packet, index)) => {
+            if index < slice.len() {
+                trace.field("Trailing data", index, slice.len(), || {
+                    format!("{} bytes", slice.len() - index)
+                });
+            }
+
+            (Ok(packet), index)
+        },
+        Err(error) => {
+            let stop = trace.furthest();
+
+            (Err(error), stop)
+        }
+    };
+
+    Dissection{
+        data: slice.to_vec(),
+        result,
+        spans: trace.into_spans(),
+        stop
+    }
+}
@@ -0,0 +1,428 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+use message::Message;
+use message::acknowledgement::AcknowledgementType;
+use message_header::{MessageHeader, MessageType, ServiceType};
+use options_header::{MobileId, OptionsHeader};
+use packet::Packet;
+use session::SessionTable;
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+/// Outbound command identifier.
+#[derive(Clone,Copy,Debug,Eq,Hash,Ord,PartialEq,PartialOrd)]
+pub struct CommandId(u64);
+
+/// Final outcome of an outbound command.
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum Outcome {
+    /// The unit replied with an ACK/NAK message.
+    Acknowledged(AcknowledgementType),
+
+    /// The retry schedule ran out without a reply.
+    Expired
+}
+
+/// Delivery status of an outbound command.
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum Status {
+    /// Completed with the given outcome.
+    Completed(Outcome),
+
+    /// Held until the unit checks in, or until the expiry time passes.
+    Held(Instant),
+
+    /// Sent the given number of times, and awaiting acknowledgement.
+    Pending(usize)
+}
+
+/// Default time that completed commands remain queryable.
+pub const DEFAULT_RETENTION: Duration = Duration::from_secs(3600);
+
+/// Outbound command held for store-and-forward delivery.
+#[derive(Clone,Debug)]
+struct HeldCommand {
+    /// Expiry time.
+    expires: Instant,
+
+    /// Message body.
+    message: Message,
+
+    /// Message type.
+    message_type: MessageType,
+
+    /// Mobile ID.
+    mobile_id: MobileId
+}
+
+/// Outbound command awaiting acknowledgement.
+#[derive(Clone,Debug)]
+struct Command {
+    /// Number of times the command has been sent.
+    attempts: usize,
+
+    /// Encoded packet.
+    data: Vec<u8>,
+
+    /// Message type.
+    message_type: MessageType,
+
+    /// Mobile ID.
+    mobile_id: MobileId,
+
+    /// Time of the next retry.
+    next_attempt: Instant,
+
+    /// Destination address.
+    peer: SocketAddr,
+
+    /// Sequence number.
+    sequence_number: u16
+}
+
+/// Outbound command queue.
+///
+/// Commands are assigned sequence numbers per mobile ID and sent as acknowledged requests to the
+/// unit's last known address in a `SessionTable`. They remain pending until an ACK/NAK message
+/// with the same sequence number and message type arrives, or until the retry schedule runs out.
+///
+/// Units behind carrier NAT cannot receive unsolicited messages, so commands can also be held
+/// with `hold()` and sent by `check_in()` right after the unit's next inbound packet.
+///
+/// The outcome of a completed command remains available from `status()` for the retention period.
+#[derive(Clone,Debug)]
+pub struct Downlink {
+    /// Pending commands.
+    commands: HashMap<CommandId, Command>,
+
+    /// Outcomes and completion times of completed commands.
+    completed: HashMap<CommandId, (Outcome, Instant)>,
+
+    /// Held commands.
+    held: HashMap<CommandId, HeldCommand>,
+
+    /// Next command identifier.
+    next_id: u64,
+
+    /// Time that completed commands remain queryable.
+    retention: Duration,
+
+    /// Delay after each attempt before the command is sent again, or expires after the last one.
+    retry_schedule: Vec<Duration>,
+
+    /// Last assigned sequence number per mobile ID.
+    sequence_numbers: HashMap<MobileId, u16>
+}
+
+impl Downlink {
+    /// Create a new Downlink with a default retry schedule of 5, 10 and 20 seconds.
+    pub fn new() -> Downlink {
+        Downlink::with_retry_schedule(vec![Duration::from_secs(5),
+                                           Duration::from_secs(10),
+                                           Duration::from_secs(20)])
+    }
+
+    /// Create a new Downlink with a custom retry schedule.
+    ///
+    /// Each entry is the delay after an attempt before the command is sent again. The command
+    /// expires once the delay after the last attempt passes, so the schedule length is also the
+    /// attempt count.
+    pub fn with_retry_schedule(retry_schedule: Vec<Duration>) -> Downlink {
+        Downlink{
+            commands:         HashMap::new(),
+            completed:        HashMap::new(),
+            held:             HashMap::new(),
+            next_id:          0,
+            retention:        DEFAULT_RETENTION,
+            retry_schedule,
+            sequence_numbers: HashMap::new()
+        }
+    }
+
+    /// Send a message over `socket` to `mobile_id` at its last known address in `sessions`.
+    ///
+    /// Returns the identifier of the pending command, or an `io::ErrorKind::NotFound` error when
+    /// `sessions` holds no session for the unit.
+    pub fn send(&mut self, socket: &UdpSocket, sessions: &SessionTable, mobile_id: &MobileId,
+                message_type: MessageType, message: Message, now: Instant)
+    -> io::Result<CommandId> {
+        let peer = sessions.peer(mobile_id).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("no known address for {}", mobile_id))
+        })?;
+
+        let id = self.next_id();
+
+        self.transmit(id, socket, mobile_id, peer, message_type, message, now)?;
+
+        Ok(id)
+    }
+
+    /// Hold a message for `mobile_id` until the unit checks in, or until `expires` passes.
+    ///
+    /// Returns the identifier of the held command.
+    pub fn hold(&mut self, mobile_id: &MobileId, message_type: MessageType, message: Message,
+                expires: Instant) -> CommandId {
+        let id = self.next_id();
+
+        self.held.insert(id, HeldCommand{
+            expires,
+            message,
+            message_type,
+            mobile_id: mobile_id.clone()
+        });
+
+        id
+    }
+
+    /// Send the messages held for the unit that sent `packet` from `peer`.
+    ///
+    /// Any inbound packet counts as a check-in, including null keep-alive messages. Returns the
+    /// identifiers of the commands that were sent. A command that fails to send remains held, along
+    /// with the commands after it.
+    pub fn check_in(&mut self, socket: &UdpSocket, packet: &Packet, peer: SocketAddr,
+                    now: Instant) -> io::Result<Vec<CommandId>> {
+        let mobile_id = match *packet.options_header().mobile_id() {
+            Some(ref mobile_id) => mobile_id,
+            None => return Ok(Vec::new())
+        };
+
+        let mut ids: Vec<CommandId> = self.held.iter()
+                                               .filter(|&(_, held)| held.mobile_id == *mobile_id &&
+                                                                    held.expires > now)
+                                               .map(|(id, _)| *id)
+                                               .collect();
+
+        ids.sort();
+
+        for id in &ids {
+            let held = self.held[id].clone();
+
+            self.transmit(*id, socket, mobile_id, peer, held.message_type, held.message, now)?;
+            self.held.remove(id);
+        }
+
+        Ok(ids)
+    }
+
+    /// Build and send a command, and mark it as pending.
+    #[allow(clippy::too_many_arguments)]
+    fn transmit(&mut self, id: CommandId, socket: &UdpSocket, mobile_id: &MobileId,
+                peer: SocketAddr, message_type: MessageType, message: Message, now: Instant)
+    -> io::Result<()> {
+        let sequence_number = self.next_sequence_number(mobile_id);
+
+        let mut options_header = OptionsHeader::new();
+
+        options_header.set_mobile_id(Some(mobile_id.clone()));
+
+        let mut data = Vec::new();
+
+        Packet::new(options_header,
+                    MessageHeader::new(ServiceType::AcknowledgedRequest, message_type,
+                                       sequence_number),
+                    message).encode(&mut data);
+
+        socket.send_to(&data, peer)?;
+
+        self.commands.insert(id, Command{
+            attempts:     1,
+            data,
+            message_type,
+            mobile_id:    mobile_id.clone(),
+            next_attempt: now + self.delay(0),
+            peer,
+            sequence_number
+        });
+
+        Ok(())
+    }
+
+    /// Match an incoming packet received from `peer` at time `now` against the pending commands.
+    ///
+    /// Returns the completed command when the packet is the ACK/NAK message it was waiting for.
+    /// The mobile ID of the ACK/NAK message must match the command, or when it carries none, `peer`
+    /// must be the address the command was sent to.
+    pub fn acknowledge(&mut self, packet: &Packet, peer: SocketAddr, now: Instant)
+    -> Option<(CommandId, Outcome)> {
+        let message = match *packet.message() {
+            Message::AckNak(ref message) => message,
+            _ => return None
+        };
+
+        let sequence_number = packet.message_header().sequence_number();
+        let mobile_id       = packet.options_header().mobile_id();
+
+        let id = self.commands.iter().filter(|&(_, command)| {
+            command.sequence_number == sequence_number &&
+            command.message_type == *message.message_type() &&
+            match *mobile_id {
+                Some(ref mobile_id) => *mobile_id == command.mobile_id,
+                None => peer == command.peer
+            }
+        }).map(|(id, _)| *id).min()?;
+
+        let outcome = Outcome::Acknowledged(*message.ack());
+
+        self.commands.remove(&id);
+        self.completed.insert(id, (outcome, now));
+
+        Some((id, outcome))
+    }
+
+    /// Resend commands whose retry delay has passed at time `now`, expire commands that ran out of
+    /// attempts or were held past their expiry time, and forget completed commands older than the
+    /// retention period.
+    ///
+    /// Returns the expired commands. A failed resend counts as an attempt, and does not stop the
+    /// other commands from being resent. The first failure is returned once every command has been
+    /// processed, and the expired commands then remain available from `status()`.
+    pub fn poll(&mut self, socket: &UdpSocket, now: Instant)
+    -> io::Result<Vec<(CommandId, Outcome)>> {
+        let mut expired: Vec<(CommandId, Outcome)> = self.held.iter().filter(|&(_, held)| {
+            held.expires <= now
+        }).map(|(id, _)| (*id, Outcome::Expired)).collect();
+
+        for &(id, _) in &expired {
+            self.held.remove(&id);
+        }
+
+        let mut due: Vec<CommandId> = self.commands.iter().filter(|&(_, command)| {
+            command.next_attempt <= now
+        }).map(|(id, _)| *id).collect();
+
+        due.sort();
+
+        let mut error = None;
+
+        for id in due {
+            if self.commands[&id].attempts >= self.retry_schedule.len() {
+                self.commands.remove(&id);
+
+                expired.push((id, Outcome::Expired));
+
+                continue;
+            }
+
+            let delay   = self.delay(self.commands[&id].attempts);
+            let command = self.commands.get_mut(&id).expect("command is pending");
+
+            if let Err(send_error) = socket.send_to(&command.data, command.peer) {
+                error = error.or(Some(send_error));
+            }
+
+            command.attempts     += 1;
+            command.next_attempt  = now + delay;
+        }
+
+        expired.sort_by_key(|&(id, _)| id);
+
+        for &(id, outcome) in &expired {
+            self.completed.insert(id, (outcome, now));
+        }
+
+        let retention = self.retention;
+
+        self.completed.retain(|_, &mut (_, completed)| {
+            now.saturating_duration_since(completed) <= retention
+        });
+
+        match error {
+            Some(error) => Err(error),
+            None => Ok(expired)
+        }
+    }
+
+    /// Cancel a held or pending command.
+    ///
+    /// Returns `true` when the command was held or pending.
+    pub fn cancel(&mut self, id: CommandId) -> bool {
+        self.held.remove(&id).is_some() || self.commands.remove(&id).is_some()
+    }
+
+    /// Indicates that a command is awaiting acknowledgement.
+    pub fn is_pending(&self, id: CommandId) -> bool {
+        self.commands.contains_key(&id)
+    }
+
+    /// Retrieve the delivery status of a command.
+    ///
+    /// Returns `None` once the command has been cancelled, or once the retention period has
+    /// passed after it completed.
+    pub fn status(&self, id: CommandId) -> Option<Status> {
+        if let Some(held) = self.held.get(&id) {
+            return Some(Status::Held(held.expires));
+        }
+
+        if let Some(command) = self.commands.get(&id) {
+            return Some(Status::Pending(command.attempts));
+        }
+
+        self.completed.get(&id).map(|&(outcome, _)| Status::Completed(outcome))
+    }
+
+    /// Retrieve the time that completed commands remain queryable.
+    pub fn retention(&self) -> Duration {
+        self.retention
+    }
+
+    /// Set the time that completed commands remain queryable.
+    pub fn set_retention(&mut self, retention: Duration) {
+        self.retention = retention;
+    }
+
+    /// Retrieve the held and pending command count.
+    pub fn len(&self) -> usize {
+        self.held.len() + self.commands.len()
+    }
+
+    /// Indicates that no commands are held or pending.
+    pub fn is_empty(&self) -> bool {
+        self.held.is_empty() && self.commands.is_empty()
+    }
+
+    /// Retrieve the retry delay following attempt number `attempt`, counting from zero.
+    fn delay(&self, attempt: usize) -> Duration {
+        self.retry_schedule.get(attempt).cloned().unwrap_or_default()
+    }
+
+    /// Assign the next command identifier.
+    fn next_id(&mut self) -> CommandId {
+        self.next_id += 1;
+
+        CommandId(self.next_id)
+    }
+
+    /// Assign the next sequence number for `mobile_id`.
+    fn next_sequence_number(&mut self, mobile_id: &MobileId) -> u16 {
+        let sequence_number = self.sequence_numbers.entry(mobile_id.clone()).or_insert(0);
+
+        *sequence_number = sequence_number.wrapping_add(1);
+
+        *sequence_number
+    }
+}
+
+impl Default for Downlink {
+    fn default() -> Downlink {
+        Downlink::new()
+    }
+}
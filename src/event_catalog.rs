@@ -0,0 +1,191 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+#[cfg(any(feature = "toml", feature = "json"))]
+use ConfigError;
+#[cfg(any(feature = "toml", feature = "json"))]
+use config;
+use message::Message;
+use packet::Packet;
+use session::SessionTable;
+
+use std::collections::HashMap;
+
+#[cfg(any(feature = "toml", feature = "json"))]
+use std::path::Path;
+
+/// Description of a PEG event code.
+#[derive(Clone,Debug,Eq,PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct EventDefinition {
+    /// Category, such as `ignition` or `driving`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    category: String,
+
+    /// Descriptive name.
+    name: String
+}
+
+impl EventDefinition {
+    /// Create a new EventDefinition.
+    pub fn new(name: &str, category: &str) -> EventDefinition {
+        EventDefinition{
+            category: category.to_string(),
+            name:     name.to_string()
+        }
+    }
+
+    /// Retrieve the category.
+    pub fn category(&self) -> &str {
+        &self.category
+    }
+
+    /// Retrieve the descriptive name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Catalog entry within a TOML or JSON file.
+#[cfg(any(feature = "toml", feature = "json"))]
+#[derive(Deserialize)]
+struct Entry {
+    /// Event code.
+    code: u8,
+
+    /// Definition.
+    #[serde(flatten)]
+    definition: EventDefinition,
+
+    /// Script version the entry applies to, or all scripts when omitted.
+    script: Option<u8>
+}
+
+/// Layout of a TOML or JSON catalog file.
+#[cfg(any(feature = "toml", feature = "json"))]
+#[derive(Deserialize)]
+struct File {
+    /// Catalog entries.
+    #[serde(default)]
+    event: Vec<Entry>
+}
+
+/// Catalog of PEG event codes.
+///
+/// Event codes only mean something within the PEG script of the unit, so definitions can be
+/// given per script version. Definitions without a script version apply to every script that
+/// does not override them.
+#[derive(Clone,Debug,Default)]
+pub struct EventCatalog {
+    /// Definitions that apply to all scripts, keyed by event code.
+    defaults: HashMap<u8, EventDefinition>,
+
+    /// Definitions keyed by script version and event code.
+    scripts: HashMap<(u8, u8), EventDefinition>
+}
+
+impl EventCatalog {
+    /// Create a new empty EventCatalog.
+    pub fn new() -> EventCatalog {
+        EventCatalog::default()
+    }
+
+    /// Load a catalog file, choosing the format from the `.toml` or `.json` extension.
+    ///
+    /// The file lists one `event` entry per definition:
+    ///
+    /// ```toml
+    /// [[event]]
+    /// code     = 13
+    /// name     = "Ignition on"
+    /// category = "ignition"
+    ///
+    /// [[event]]
+    /// script   = 33
+    /// code     = 13
+    /// name     = "Harsh brake"
+    /// category = "driving"
+    /// ```
+    #[cfg(any(feature = "toml", feature = "json"))]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<EventCatalog, ConfigError> {
+        config::load(path.as_ref()).map(EventCatalog::from_file)
+    }
+
+    /// Parse a catalog from TOML text.
+    #[cfg(feature = "toml")]
+    pub fn from_toml(text: &str) -> Result<EventCatalog, ConfigError> {
+        config::from_toml(text).map(EventCatalog::from_file)
+    }
+
+    /// Parse a catalog from JSON text.
+    #[cfg(feature = "json")]
+    pub fn from_json(text: &str) -> Result<EventCatalog, ConfigError> {
+        config::from_json(text).map(EventCatalog::from_file)
+    }
+
+    /// Build a catalog from a parsed file.
+    #[cfg(any(feature = "toml", feature = "json"))]
+    fn from_file(file: File) -> EventCatalog {
+        let mut catalog = EventCatalog::new();
+
+        for entry in file.event {
+            catalog.insert(entry.script, entry.code, entry.definition);
+        }
+
+        catalog
+    }
+
+    /// Set the definition of `code` for `script`, or for all scripts when `script` is `None`.
+    pub fn insert(&mut self, script: Option<u8>, code: u8, definition: EventDefinition) {
+        match script {
+            Some(script) => self.scripts.insert((script, code), definition),
+            None => self.defaults.insert(code, definition)
+        };
+    }
+
+    /// Retrieve the definition of `code` for `script`, falling back to the definition for all
+    /// scripts.
+    pub fn get(&self, script: Option<u8>, code: u8) -> Option<&EventDefinition> {
+        script.and_then(|script| self.scripts.get(&(script, code)))
+              .or_else(|| self.defaults.get(&code))
+    }
+
+    /// Retrieve the definition of the event code within an event report, as sent by a unit
+    /// running `script`.
+    pub fn label(&self, script: Option<u8>, message: &Message) -> Option<&EventDefinition> {
+        match *message {
+            Message::EventReport(ref report) => self.get(script, report.event_code()),
+            _ => None
+        }
+    }
+
+    /// Retrieve the definition of the event code within an event report packet.
+    ///
+    /// The script version is taken from the last ID report of the unit within `sessions`.
+    pub fn label_packet(&self, packet: &Packet, sessions: &SessionTable)
+    -> Option<&EventDefinition> {
+        let script = packet.options_header()
+                           .mobile_id()
+                           .as_ref()
+                           .and_then(|mobile_id| sessions.get(mobile_id))
+                           .and_then(|session| session.id_report().as_ref())
+                           .map(|id_report| id_report.script_version());
+
+        self.label(script, packet.message())
+    }
+}
@@ -0,0 +1,198 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+use std::fmt;
+use std::ops;
+
+/// Define a single byte flag set `$name` with one constant and accessor per flag. Bits without a
+/// named flag are kept, so `from_bits(x).bits() == x` for every byte.
+macro_rules! flags {
+    ($(#[$meta:meta])* $name:ident {
+        $($(#[$flag_meta:meta])* $flag:ident, $accessor:ident, $label:expr => $bit:expr;)*
+    }) => {
+        $(#[$meta])*
+        #[derive(Clone,Copy,Default,Eq,Hash,PartialEq)]
+        #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+        pub struct $name(u8);
+
+        impl $name {
+            $(
+                $(#[$flag_meta])*
+                pub const $flag: $name = $name(1 << $bit);
+            )*
+
+            /// Create a new flag set from its wire value.
+            pub fn from_bits(bits: u8) -> $name {
+                $name(bits)
+            }
+
+            /// Retrieve the wire value.
+            pub fn bits(&self) -> u8 {
+                self.0
+            }
+
+            /// Indicates that all flags within `other` are set.
+            pub fn contains(&self, other: $name) -> bool {
+                self.0 & other.0 == other.0
+            }
+
+            /// Indicates that no flags are set.
+            pub fn is_empty(&self) -> bool {
+                self.0 == 0
+            }
+
+            $(
+                $(#[$flag_meta])*
+                pub fn $accessor(&self) -> bool {
+                    self.contains($name::$flag)
+                }
+            )*
+        }
+
+        impl ops::BitOr for $name {
+            type Output = $name;
+
+            fn bitor(self, other: $name) -> $name {
+                $name(self.0 | other.0)
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                let mut names = Vec::new();
+                let mut known = 0;
+
+                $(
+                    known |= 1 << $bit;
+
+                    if self.$accessor() {
+                        names.push($label.to_string());
+                    }
+                )*
+
+                if self.0 & !known != 0 {
+                    names.push(format!("{:#04x}", self.0 & !known));
+                }
+
+                write!(formatter, "{}({})", stringify!($name), names.join(" | "))
+            }
+        }
+    }
+}
+
+flags! {
+    /// GPS fix status.
+    FixStatus {
+        /// The fix is predicted rather than measured.
+        PREDICTED, predicted, "Predicted" => 0;
+
+        /// The fix is differentially corrected.
+        DIFFERENTIAL, differential, "Differential" => 1;
+
+        /// The fix is the last known fix, because no current fix is available.
+        LAST_KNOWN, last_known, "LastKnown" => 2;
+
+        /// The fix is invalid.
+        INVALID_FIX, invalid_fix, "InvalidFix" => 3;
+
+        /// The fix is 2D, with fewer than 4 satellites.
+        TWO_D, two_d, "TwoD" => 4;
+
+        /// The message was logged and is being sent after the fact.
+        HISTORIC, historic, "Historic" => 5;
+
+        /// The update time is invalid.
+        INVALID_TIME, invalid_time, "InvalidTime" => 6;
+    }
+}
+
+flags! {
+    /// Wireless network communication state.
+    CommState {
+        /// Network is available.
+        AVAILABLE, available, "Available" => 0;
+
+        /// Network service is available.
+        NETWORK_SERVICE, network_service, "NetworkService" => 1;
+
+        /// Data service is available.
+        DATA_SERVICE, data_service, "DataService" => 2;
+
+        /// Connected to the data network.
+        CONNECTED, connected, "Connected" => 3;
+
+        /// A voice call is in progress.
+        VOICE_CALL, voice_call, "VoiceCall" => 4;
+
+        /// Roaming on another carrier's network.
+        ROAMING, roaming, "Roaming" => 5;
+    }
+}
+
+flags! {
+    /// Unit status.
+    UnitStatus {
+        /// The last over-the-air update failed.
+        OTA_UPDATE_ERROR, ota_update_error, "OtaUpdateError" => 0;
+
+        /// The GPS antenna is OK.
+        GPS_ANTENNA_OK, gps_antenna_ok, "GpsAntennaOk" => 1;
+
+        /// The GPS receiver passed its self-test.
+        GPS_RECEIVER_OK, gps_receiver_ok, "GpsReceiverOk" => 2;
+
+        /// The GPS receiver is tracking satellites.
+        GPS_TRACKING, gps_tracking, "GpsTracking" => 3;
+    }
+}
+
+flags! {
+    /// Input states.
+    Inputs {
+        /// Input 0, which is wired to the ignition.
+        IGNITION, ignition, "Ignition" => 0;
+
+        /// Input 1.
+        INPUT_1, input_1, "Input1" => 1;
+
+        /// Input 2.
+        INPUT_2, input_2, "Input2" => 2;
+
+        /// Input 3.
+        INPUT_3, input_3, "Input3" => 3;
+
+        /// Input 4.
+        INPUT_4, input_4, "Input4" => 4;
+
+        /// Input 5.
+        INPUT_5, input_5, "Input5" => 5;
+
+        /// Input 6.
+        INPUT_6, input_6, "Input6" => 6;
+
+        /// Input 7.
+        INPUT_7, input_7, "Input7" => 7;
+    }
+}
+
+impl Inputs {
+    /// Indicates that input `n` is set.
+    pub fn input(&self, n: u8) -> bool {
+        n < 8 && self.0 & (1 << n) != 0
+    }
+}
@@ -0,0 +1,168 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+use flags::FixStatus;
+
+/// GPS fix shared by all position-carrying messages.
+///
+/// Values are kept in their wire units, and converted on retrieval. They are serialized in wire
+/// units too, under names that carry the unit.
+#[derive(Clone,Copy,Debug,Eq,Hash,PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct GpsFix {
+    /// Altitude in centimeters.
+    #[cfg_attr(feature = "serde", serde(rename = "altitude_cm"))]
+    altitude: i32,
+
+    /// Fix status.
+    fix_status: FixStatus,
+
+    /// Horizontal dilution of precision in tenths.
+    #[cfg_attr(feature = "serde", serde(rename = "hdop_tenths"))]
+    hdop: u8,
+
+    /// Heading in degrees.
+    heading: u16,
+
+    /// Latitude in 1e-7 degrees.
+    #[cfg_attr(feature = "serde", serde(rename = "latitude_e7"))]
+    latitude: i32,
+
+    /// Longitude in 1e-7 degrees.
+    #[cfg_attr(feature = "serde", serde(rename = "longitude_e7"))]
+    longitude: i32,
+
+    /// Satellite count.
+    satellites: u8,
+
+    /// Speed in centimeters per second.
+    #[cfg_attr(feature = "serde", serde(rename = "speed_cm_s"))]
+    speed: u32
+}
+
+impl GpsFix {
+    /// Create a new GpsFix from wire values.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(latitude: i32, longitude: i32, altitude: i32, speed: u32, heading: u16,
+               satellites: u8, fix_status: FixStatus, hdop: u8) -> GpsFix {
+        GpsFix{
+            altitude,
+            fix_status,
+            hdop,
+            heading,
+            latitude,
+            longitude,
+            satellites,
+            speed
+        }
+    }
+
+    /// Retrieve the altitude in meters.
+    pub fn altitude(&self) -> f64 {
+        self.altitude as f64 / 100.0
+    }
+
+    /// Retrieve the altitude in centimeters.
+    pub fn altitude_cm(&self) -> i32 {
+        self.altitude
+    }
+
+    /// Retrieve the fix status.
+    pub fn fix_status(&self) -> FixStatus {
+        self.fix_status
+    }
+
+    /// Retrieve the horizontal dilution of precision.
+    pub fn hdop(&self) -> f64 {
+        self.hdop as f64 / 10.0
+    }
+
+    /// Retrieve the horizontal dilution of precision in tenths.
+    pub fn hdop_raw(&self) -> u8 {
+        self.hdop
+    }
+
+    /// Retrieve the heading in degrees.
+    pub fn heading(&self) -> u16 {
+        self.heading
+    }
+
+    /// Retrieve the latitude in degrees.
+    pub fn latitude(&self) -> f64 {
+        self.latitude as f64 / 1e7
+    }
+
+    /// Retrieve the latitude in 1e-7 degrees.
+    pub fn latitude_raw(&self) -> i32 {
+        self.latitude
+    }
+
+    /// Retrieve the longitude in degrees.
+    pub fn longitude(&self) -> f64 {
+        self.longitude as f64 / 1e7
+    }
+
+    /// Retrieve the longitude in 1e-7 degrees.
+    pub fn longitude_raw(&self) -> i32 {
+        self.longitude
+    }
+
+    /// Retrieve the satellite count.
+    pub fn satellites(&self) -> u8 {
+        self.satellites
+    }
+
+    /// Retrieve the speed in centimeters per second.
+    pub fn speed_cm_s(&self) -> u32 {
+        self.speed
+    }
+
+    /// Retrieve the speed in kilometers per hour.
+    pub fn speed_kmh(&self) -> f64 {
+        self.speed as f64 * 0.036
+    }
+
+    /// Retrieve the speed in miles per hour.
+    pub fn speed_mph(&self) -> f64 {
+        self.speed as f64 * 0.036 / 1.609344
+    }
+
+    /// Indicates that the coordinates are within -90..90 degrees latitude and -180..180 degrees
+    /// longitude.
+    pub fn is_in_range(&self) -> bool {
+        self.latitude.unsigned_abs() <= 900_000_000 &&
+        self.longitude.unsigned_abs() <= 1_800_000_000
+    }
+
+    /// Indicates that the fix status marks the fix as invalid.
+    pub fn is_invalid_fix(&self) -> bool {
+        self.fix_status.invalid_fix()
+    }
+
+    /// Indicates that both coordinates are zero, which units report when they have never had a
+    /// fix.
+    pub fn is_zero_position(&self) -> bool {
+        self.latitude == 0 && self.longitude == 0
+    }
+
+    /// Indicates that the fix can be plotted: it is not marked invalid, is in range, and is not the
+    /// zero position.
+    pub fn is_valid(&self) -> bool {
+        !self.is_invalid_fix() && self.is_in_range() && !self.is_zero_position()
+    }
+}
@@ -16,17 +16,66 @@
 // | Author: Sean Kerr <sean@metatomic.io>                                                         |
 // +-----------------------------------------------------------------------------------------------+
 
+#[cfg(feature = "chrono")]
+extern crate chrono;
+
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+
+#[cfg(feature = "json")]
+extern crate serde_json;
+
+#[cfg(feature = "time")]
+extern crate time;
+
+#[cfg(feature = "toml")]
+extern crate toml;
+
 #[macro_use]
 mod macros;
 
+mod bcd;
+#[cfg(any(feature = "toml", feature = "json"))]
+mod config;
+
+pub mod accumulators;
+pub mod authentication;
+#[cfg(feature = "pcap")]
+pub mod capture;
+pub mod cipher;
+pub mod deduplication;
+pub mod dissect;
+pub mod downlink;
+pub mod event_catalog;
+pub mod flags;
+pub mod gps_fix;
+pub mod lmu_time;
 pub mod message;
 pub mod message_header;
 pub mod options_header;
+pub mod packet;
+pub mod relay;
+pub mod router;
+pub mod server;
+pub mod session;
+pub mod signal;
+pub mod sim;
+#[doc(hidden)]
+pub mod util;
 
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug)]
 pub enum CalAmpError {
     /// Unsupported acknowledgement type.
     AcknowledgementType(u8),
 
+    /// Identifier selected by the encryption type is not present in the options header.
+    EncryptionIdentifier(options_header::EncryptionType),
+
     /// Unsupported encryption type.
     EncryptionType(u8),
 
@@ -37,6 +86,7 @@ pub enum CalAmpError {
     MessageType(u8),
 
     /// Option extension bit length.
+    #[deprecated(note = "option extension bitmaps of any length are parsed")]
     OptionExtensionBitLength(u8),
 
     /// Invalid service type.
@@ -45,3 +95,129 @@ pub enum CalAmpError {
     /// Invalid vehicle identification number length.
     VinLength
 }
+
+impl fmt::Display for CalAmpError {
+    #[allow(deprecated)]
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CalAmpError::AcknowledgementType(x) => {
+                write!(formatter, "unsupported acknowledgement type: {}", x)
+            },
+            CalAmpError::EncryptionIdentifier(x) => {
+                write!(formatter, "identifier for {} encryption is not present", x)
+            },
+            CalAmpError::EncryptionType(x) => {
+                write!(formatter, "unsupported encryption type: {}", x)
+            },
+            CalAmpError::Eos => {
+                write!(formatter, "premature end of stream")
+            },
+            CalAmpError::MessageType(x) => {
+                write!(formatter, "invalid message type: {}", x)
+            },
+            CalAmpError::OptionExtensionBitLength(x) => {
+                write!(formatter, "unsupported option extension bit length: {}", x)
+            },
+            CalAmpError::ServiceType(x) => {
+                write!(formatter, "invalid service type: {}", x)
+            },
+            CalAmpError::VinLength => {
+                write!(formatter, "invalid vehicle identification number length")
+            }
+        }
+    }
+}
+
+/// Parser behavior for anomalies that do not prevent decoding the rest of a packet.
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub struct ParseOptions {
+    /// Fail on anomalies rather than recording them as warnings.
+    pub strict: bool
+}
+
+impl ParseOptions {
+    /// Create new ParseOptions that fail on anomalies.
+    pub fn strict() -> ParseOptions {
+        ParseOptions{
+            strict: true
+        }
+    }
+
+    /// Create new ParseOptions that record anomalies as warnings.
+    pub fn lenient() -> ParseOptions {
+        ParseOptions{
+            strict: false
+        }
+    }
+}
+
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        ParseOptions::strict()
+    }
+}
+
+/// Anomaly recorded by a lenient parse.
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ParseWarning {
+    /// Unsupported encryption type. The message body is kept encrypted.
+    EncryptionType(u8),
+
+    /// Vehicle identification number that is not 17 bytes. It is kept as is.
+    VinLength(usize)
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseWarning::EncryptionType(x) => {
+                write!(formatter, "unsupported encryption type: {}", x)
+            },
+            ParseWarning::VinLength(x) => {
+                write!(formatter, "vehicle identification number length is {}, not 17", x)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    /// Unsupported configuration file format.
+    Format(PathBuf),
+
+    /// Invalid configuration value that is not tied to a line.
+    Invalid(String),
+
+    /// Failed to read a configuration file.
+    Io(io::Error),
+
+    /// Invalid configuration at a line number, counting from 1.
+    Line(usize, String)
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(error: io::Error) -> ConfigError {
+        ConfigError::Io(error)
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::Format(ref path) => {
+                write!(formatter, "unsupported configuration format: {}", path.display())
+            },
+            ConfigError::Invalid(ref message) => {
+                write!(formatter, "{}", message)
+            },
+            ConfigError::Io(ref error) => {
+                write!(formatter, "{}", error)
+            },
+            ConfigError::Line(line, ref message) => {
+                write!(formatter, "line {}: {}", line, message)
+            }
+        }
+    }
+}
@@ -0,0 +1,258 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::marker::PhantomData;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "chrono")]
+use chrono;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[cfg(feature = "time")]
+use time;
+
+/// GPS epoch, 1980-01-06T00:00:00Z, in seconds since the Unix epoch.
+pub const GPS_EPOCH: u32 = 315_964_800;
+
+/// Seconds in a GPS week, and the range of the shortened time form.
+pub const SECONDS_PER_WEEK: u32 = 604_800;
+
+/// Earliest time a unit with a valid clock reports, 2000-01-01T00:00:00Z. Units without a clock
+/// fix report times counted from the Unix epoch or the GPS epoch instead.
+const EARLIEST_VALID: u32 = 946_684_800;
+
+/// Marker for update times, when a message was generated.
+#[derive(Clone,Copy,Debug,Eq,Hash,Ord,PartialEq,PartialOrd)]
+pub enum Update {}
+
+/// Marker for fix times, when the GPS fix was taken.
+#[derive(Clone,Copy,Debug,Eq,Hash,Ord,PartialEq,PartialOrd)]
+pub enum Fix {}
+
+/// LMU timestamp in seconds since the Unix epoch.
+///
+/// The marker `K` keeps update times and fix times apart, so one cannot be passed or compared
+/// where the other is expected.
+///
+/// Mini messages carry a shortened time form holding only the seconds into the GPS week, which
+/// `from_short()` places in the week nearest to a reference time.
+#[derive(Clone,Copy,Eq,Hash,Ord,PartialEq,PartialOrd)]
+pub struct LmuTime<K> {
+    /// Seconds since the Unix epoch.
+    seconds: u32,
+
+    /// Time kind.
+    kind: PhantomData<K>
+}
+
+/// Time a message was generated.
+pub type UpdateTime = LmuTime<Update>;
+
+/// Time the GPS fix was taken.
+pub type FixTime = LmuTime<Fix>;
+
+impl<K> LmuTime<K> {
+    /// Create a new LmuTime from seconds since the Unix epoch.
+    pub fn from_secs(seconds: u32) -> LmuTime<K> {
+        LmuTime{
+            seconds,
+            kind: PhantomData
+        }
+    }
+
+    /// Create a new LmuTime from a SystemTime.
+    ///
+    /// Returns `None` when the time is before the Unix epoch or past the 32-bit range.
+    pub fn from_system_time(time: SystemTime) -> Option<LmuTime<K>> {
+        let seconds = time.duration_since(UNIX_EPOCH).ok()?.as_secs();
+
+        if seconds > u32::MAX as u64 {
+            None
+        } else {
+            Some(LmuTime::from_secs(seconds as u32))
+        }
+    }
+
+    /// Create a new LmuTime from seconds since the GPS epoch.
+    ///
+    /// GPS time does not count leap seconds, so only the epoch is converted. Returns `None` past
+    /// the 32-bit range.
+    pub fn from_gps_secs(seconds: u32) -> Option<LmuTime<K>> {
+        seconds.checked_add(GPS_EPOCH).map(LmuTime::from_secs)
+    }
+
+    /// Create a new LmuTime from a GPS week number and the seconds into that week.
+    ///
+    /// Returns `None` when `seconds` is not within the week, or past the 32-bit range.
+    pub fn from_gps_week(week: u32, seconds: u32) -> Option<LmuTime<K>> {
+        if seconds >= SECONDS_PER_WEEK {
+            return None;
+        }
+
+        week.checked_mul(SECONDS_PER_WEEK)
+            .and_then(|start| start.checked_add(seconds))
+            .and_then(LmuTime::from_gps_secs)
+    }
+
+    /// Create a new LmuTime from the shortened time form of mini messages, the seconds into the
+    /// GPS week. The week is chosen so that the time lies nearest to `reference`, such as the
+    /// time the message was received.
+    ///
+    /// Returns `None` when `short` is not within a week, or the time is outside the 32-bit range.
+    pub fn from_short<R>(short: u32, reference: LmuTime<R>) -> Option<LmuTime<K>> {
+        if short >= SECONDS_PER_WEEK {
+            return None;
+        }
+
+        let week      = SECONDS_PER_WEEK as i64;
+        let reference = reference.as_secs() as i64 - GPS_EPOCH as i64;
+        let mut time  = reference - reference.rem_euclid(week) + short as i64;
+
+        if time - reference > week / 2 {
+            time -= week;
+        } else if reference - time > week / 2 {
+            time += week;
+        }
+
+        u32::try_from(time + GPS_EPOCH as i64).ok().map(LmuTime::from_secs)
+    }
+
+    /// Retrieve the seconds since the Unix epoch.
+    pub fn as_secs(&self) -> u32 {
+        self.seconds
+    }
+
+    /// Retrieve the seconds since the GPS epoch, or `None` when the time predates it.
+    pub fn as_gps_secs(&self) -> Option<u32> {
+        self.seconds.checked_sub(GPS_EPOCH)
+    }
+
+    /// Retrieve the GPS week number, or `None` when the time predates the GPS epoch.
+    pub fn gps_week(&self) -> Option<u32> {
+        self.as_gps_secs().map(|seconds| seconds / SECONDS_PER_WEEK)
+    }
+
+    /// Retrieve the shortened time form of mini messages, the seconds into the GPS week.
+    pub fn to_short(&self) -> u32 {
+        (self.seconds as i64 - GPS_EPOCH as i64).rem_euclid(SECONDS_PER_WEEK as i64) as u32
+    }
+
+    /// Create a new LmuTime from a chrono UTC date and time, dropping fractional seconds.
+    ///
+    /// Returns `None` when the time is before the Unix epoch or past the 32-bit range.
+    #[cfg(feature = "chrono")]
+    pub fn from_chrono(time: &chrono::DateTime<chrono::Utc>) -> Option<LmuTime<K>> {
+        LmuTime::from_timestamp(time.timestamp())
+    }
+
+    /// Create a new LmuTime from a time OffsetDateTime, dropping fractional seconds.
+    ///
+    /// Returns `None` when the time is before the Unix epoch or past the 32-bit range.
+    #[cfg(feature = "time")]
+    pub fn from_offset_date_time(time: &time::OffsetDateTime) -> Option<LmuTime<K>> {
+        LmuTime::from_timestamp(time.unix_timestamp())
+    }
+
+    /// Create a new LmuTime from a signed Unix timestamp.
+    #[cfg(any(feature = "chrono", feature = "time"))]
+    fn from_timestamp(seconds: i64) -> Option<LmuTime<K>> {
+        u32::try_from(seconds).ok().map(LmuTime::from_secs)
+    }
+
+    /// Convert to a SystemTime.
+    pub fn to_system_time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.seconds as u64)
+    }
+
+    /// Indicates that the time predates 2000, which happens when a unit has no valid clock and
+    /// reports 1970 or GPS epoch based values.
+    pub fn is_invalid(&self) -> bool {
+        self.seconds < EARLIEST_VALID
+    }
+
+    /// Format as an RFC 3339 UTC timestamp, such as `2015-12-08T19:13:31Z`.
+    pub fn to_rfc3339(&self) -> String {
+        let days    = self.seconds / 86_400;
+        let seconds = self.seconds % 86_400;
+
+        // civil date from days since the Unix epoch
+        let z     = days as i64 + 719_468;
+        let era   = z / 146_097;
+        let doe   = z - era * 146_097;
+        let yoe   = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy   = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp    = (5 * doy + 2) / 153;
+        let day   = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year  = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, seconds / 3_600,
+                seconds / 60 % 60, seconds % 60)
+    }
+}
+
+impl<K> fmt::Debug for LmuTime<K> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "LmuTime({})", self.to_rfc3339())
+    }
+}
+
+impl<K> fmt::Display for LmuTime<K> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{}", self.to_rfc3339())
+    }
+}
+
+impl<K> From<LmuTime<K>> for SystemTime {
+    fn from(time: LmuTime<K>) -> SystemTime {
+        time.to_system_time()
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<K> From<LmuTime<K>> for chrono::DateTime<chrono::Utc> {
+    fn from(time: LmuTime<K>) -> chrono::DateTime<chrono::Utc> {
+        chrono::DateTime::from_timestamp(time.seconds as i64, 0).expect("u32 seconds are in range")
+    }
+}
+
+#[cfg(feature = "time")]
+impl<K> From<LmuTime<K>> for time::OffsetDateTime {
+    fn from(time: LmuTime<K>) -> time::OffsetDateTime {
+        time::OffsetDateTime::from_unix_timestamp(time.seconds as i64)
+                             .expect("u32 seconds are in range")
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<K> Serialize for LmuTime<K> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(self.seconds)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, K> Deserialize<'de> for LmuTime<K> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<LmuTime<K>, D::Error> {
+        u32::deserialize(deserializer).map(LmuTime::from_secs)
+    }
+}
@@ -64,17 +64,26 @@ macro_rules! read_u16 {
     });
 }
 
+/// Read a u32 from `$slice`, and then advance `$index` by 4 bytes. Upon locating end-of-stream,
+/// return prematurely with `CalAmpError::Eos`.
+macro_rules! read_u32 {
+    ($slice:expr, $index:expr) => ({
+        verify_bytes!($slice, $index, 4);
+
+        $index += 4;
+
+        (($slice[$index - 4] as u32) << 24) + (($slice[$index - 3] as u32) << 16) +
+        (($slice[$index - 2] as u32) << 8) + $slice[$index - 1] as u32
+    });
+}
+
 /// Read `$length` bytes from `$slice` as a vector, and then advance `$index` by `$length` bytes.
 /// Upon locating end-of-stream, return prematurely with `CalAmpError::Eos`.
 macro_rules! read_vector {
     ($slice:expr, $index:expr, $length:expr) => ({
-        verify_bytes!($slice, $index, $length);
-
         let mut v = Vec::with_capacity($length);
 
-        v.extend_from_slice(&$slice[$index..$index+$length]);
-
-        $index += $length;
+        read_into_vector!($slice, $index, $length, v);
 
         v
     });
@@ -84,8 +93,21 @@ macro_rules! read_vector {
 /// prematurely with `CalAmpError::Eos`.
 macro_rules! verify_bytes {
     ($slice:expr, $index:expr, $length:expr) => ({
-        if $index + $length >= $slice.len() {
+        if $index + $length > $slice.len() {
             return Err(CalAmpError::Eos);
         }
     });
 }
+
+/// Evaluate `$read`, which reads from a slice and advances `$index`, and record the bytes it
+/// consumed in `$trace` as the span `$name`, described by formatting the value with `$format`.
+macro_rules! trace_field {
+    ($trace:expr, $name:expr, $index:expr, $read:expr, $format:tt) => ({
+        let start = $index;
+        let value = $read;
+
+        $trace.field($name, start, $index, || format!($format, value));
+
+        value
+    });
+}
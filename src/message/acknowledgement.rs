@@ -17,10 +17,13 @@
 // +-----------------------------------------------------------------------------------------------+
 
 use CalAmpError;
+use dissect::Trace;
 use message_header::MessageType;
 
 /// Acknowledgement message.
-struct AcknowledgementMessage {
+#[derive(Clone,Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct AcknowledgementMessage {
     /// Acknowledgement type.
     ack: AcknowledgementType,
 
@@ -28,67 +31,93 @@ struct AcknowledgementMessage {
     application_version: [u8; 3],
 
     /// Message type.
-    message_type: MessageType
+    message_type: MessageType,
+
+    /// Spare byte following the acknowledgement type.
+    spare: u8
 }
 
 impl AcknowledgementMessage {
+    /// Create a new AcknowledgementMessage.
+    pub fn new(message_type: MessageType, ack: AcknowledgementType, application_version: [u8; 3])
+    -> AcknowledgementMessage {
+        AcknowledgementMessage{
+            ack,
+            application_version,
+            message_type,
+            spare: 0
+        }
+    }
+
     /// Parse acknowledgement data from a slice.
     ///
     /// Returns the AcknowledgementMessage and parsed byte count.
     pub fn parse(slice: &[u8]) -> Result<(AcknowledgementMessage, usize), CalAmpError> {
+        AcknowledgementMessage::parse_traced(slice, &mut Trace::disabled())
+    }
+
+    /// Parse acknowledgement data from a slice, recording field spans into `trace`.
+    ///
+    /// Returns the AcknowledgementMessage and parsed byte count.
+    #[allow(clippy::redundant_field_names)]
+    pub fn parse_traced(slice: &[u8], trace: &mut Trace)
+    -> Result<(AcknowledgementMessage, usize), CalAmpError> {
         // slice index
         let mut index = 0;
 
         // message type
-        let message_type = match read_u8!(slice, index) {
-            0 => MessageType::Null,
-            1 => MessageType::AckNak,
-            2 => MessageType::EventReport,
-            3 => MessageType::IdReport,
-            4 => MessageType::UserData,
-            5 => MessageType::ApplicationData,
-            6 => MessageType::ConfigurationParameter,
-            7 => MessageType::UnitRequest,
-            8 => MessageType::LocateReport,
-            9 => MessageType::UserDataAccumulators,
-            10 => MessageType::MiniEventReport,
-            11 => MessageType::MiniUser,
-            x @ _ => {
-                return Err(CalAmpError::MessageType(x));
-            }
-        };
+        let message_type = trace_field!(trace, "Message type", index,
+                                        MessageType::from_u8(read_u8!(slice, index))?, "{}");
 
         // ack
-        let ack = match read_u8!(slice, index) {
-            0 => AcknowledgementType::Successful,
-            1 => AcknowledgementType::FailedNoReason,
-            2 => AcknowledgementType::FailedMessageType,
-            3 => AcknowledgementType::FailedOperation,
-            4 => AcknowledgementType::FailedSerialPort,
-            5 => AcknowledgementType::FailedAuthentication,
-            6 => AcknowledgementType::FailedMobileId,
-            7 => AcknowledgementType::FailedSequenceNumber,
-            x @ _ => {
-                return Err(CalAmpError::AcknowledgementType(x));
-            }
-        };
+        let ack = trace_field!(trace, "Ack", index,
+                               AcknowledgementType::from_u8(read_u8!(slice, index))?, "{:?}");
 
         // spare byte
-        read_u8!(slice, index);
+        let spare = trace_field!(trace, "Spare", index, read_u8!(slice, index), "{}");
 
         // application version
-        let application_version = [read_u8!(slice, index),
-                                   read_u8!(slice, index),
-                                   read_u8!(slice, index)];
+        let application_version = trace_field!(trace, "Application version", index,
+                                               [read_u8!(slice, index),
+                                                read_u8!(slice, index),
+                                                read_u8!(slice, index)], "{:?}");
         Ok((AcknowledgementMessage{
             ack: ack,
             application_version: application_version,
-            message_type: message_type
+            message_type: message_type,
+            spare: spare
         }, index))
     }
+
+    /// Encode acknowledgement data into a buffer.
+    pub fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.push(self.message_type.as_u8());
+        buffer.push(self.ack.as_u8());
+
+        buffer.push(self.spare);
+
+        buffer.extend_from_slice(&self.application_version);
+    }
+
+    /// Retrieve the acknowledgement type.
+    pub fn ack(&self) -> &AcknowledgementType {
+        &self.ack
+    }
+
+    /// Retrieve the application version.
+    pub fn application_version(&self) -> &[u8; 3] {
+        &self.application_version
+    }
+
+    /// Retrieve the acknowledged message type.
+    pub fn message_type(&self) -> &MessageType {
+        &self.message_type
+    }
 }
 
-#[derive(Clone,Debug)]
+#[derive(Clone,Copy,Debug,Eq,Hash,PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum AcknowledgementType {
     /// Failed ACK -- authentication failure.
     FailedAuthentication,
@@ -114,3 +143,34 @@ pub enum AcknowledgementType {
     /// Successful ACK.
     Successful,
 }
+
+impl AcknowledgementType {
+    /// Convert a wire value into an AcknowledgementType.
+    pub fn from_u8(value: u8) -> Result<AcknowledgementType, CalAmpError> {
+        match value {
+            0 => Ok(AcknowledgementType::Successful),
+            1 => Ok(AcknowledgementType::FailedNoReason),
+            2 => Ok(AcknowledgementType::FailedMessageType),
+            3 => Ok(AcknowledgementType::FailedOperation),
+            4 => Ok(AcknowledgementType::FailedSerialPort),
+            5 => Ok(AcknowledgementType::FailedAuthentication),
+            6 => Ok(AcknowledgementType::FailedMobileId),
+            7 => Ok(AcknowledgementType::FailedSequenceNumber),
+            x => Err(CalAmpError::AcknowledgementType(x))
+        }
+    }
+
+    /// Retrieve the wire value.
+    pub fn as_u8(&self) -> u8 {
+        match *self {
+            AcknowledgementType::Successful => 0,
+            AcknowledgementType::FailedNoReason => 1,
+            AcknowledgementType::FailedMessageType => 2,
+            AcknowledgementType::FailedOperation => 3,
+            AcknowledgementType::FailedSerialPort => 4,
+            AcknowledgementType::FailedAuthentication => 5,
+            AcknowledgementType::FailedMobileId => 6,
+            AcknowledgementType::FailedSequenceNumber => 7
+        }
+    }
+}
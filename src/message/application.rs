@@ -0,0 +1,105 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+use CalAmpError;
+use dissect::Trace;
+use message::report_header::ReportHeader;
+
+/// Application data message.
+#[derive(Clone,Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct ApplicationMessage {
+    /// Application message data.
+    data: Vec<u8>,
+
+    /// Application message type.
+    message_type: u16,
+
+    /// Report header.
+    report_header: ReportHeader
+}
+
+impl ApplicationMessage {
+    /// Create a new ApplicationMessage.
+    pub fn new(report_header: ReportHeader, message_type: u16, data: Vec<u8>) -> ApplicationMessage {
+        ApplicationMessage{
+            data,
+            message_type,
+            report_header
+        }
+    }
+
+    /// Parse application data from a slice.
+    ///
+    /// Returns the ApplicationMessage and parsed byte count.
+    pub fn parse(slice: &[u8]) -> Result<(ApplicationMessage, usize), CalAmpError> {
+        ApplicationMessage::parse_traced(slice, &mut Trace::disabled())
+    }
+
+    /// Parse application data from a slice, recording field spans into `trace`.
+    ///
+    /// Returns the ApplicationMessage and parsed byte count.
+    pub fn parse_traced(slice: &[u8], trace: &mut Trace)
+    -> Result<(ApplicationMessage, usize), CalAmpError> {
+        trace.begin("Report header", 0);
+
+        let (report_header, mut index) = ReportHeader::parse_traced(slice, trace)?;
+
+        trace.end(index);
+
+        let message_type = trace_field!(trace, "Application message type", index,
+                                        read_u16!(slice, index), "{}");
+        let length       = trace_field!(trace, "Application message length", index,
+                                        read_u16!(slice, index) as usize, "{}");
+        let data         = read_vector!(slice, index, length);
+
+        trace.field("Application message data", index - length, index, || {
+            format!("{} bytes", length)
+        });
+
+        Ok((ApplicationMessage{
+            data,
+            message_type,
+            report_header
+        }, index))
+    }
+
+    /// Encode application data into a buffer.
+    pub fn encode(&self, buffer: &mut Vec<u8>) {
+        self.report_header.encode(buffer);
+
+        buffer.extend_from_slice(&self.message_type.to_be_bytes());
+        buffer.extend_from_slice(&(self.data.len() as u16).to_be_bytes());
+        buffer.extend_from_slice(&self.data);
+    }
+
+    /// Retrieve the application message data.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Retrieve the application message type.
+    pub fn message_type(&self) -> u16 {
+        self.message_type
+    }
+
+    /// Retrieve the report header.
+    pub fn report_header(&self) -> &ReportHeader {
+        &self.report_header
+    }
+}
@@ -0,0 +1,119 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+use CalAmpError;
+use accumulators::Accumulators;
+use dissect::Trace;
+use message::report_header::ReportHeader;
+
+/// Event report message.
+#[derive(Clone,Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct EventReportMessage {
+    /// Accumulators.
+    accumulators: Accumulators,
+
+    /// Event code.
+    event_code: u8,
+
+    /// Event index.
+    event_index: u8,
+
+    /// Report header.
+    report_header: ReportHeader
+}
+
+impl EventReportMessage {
+    /// Create a new EventReportMessage.
+    pub fn new(report_header: ReportHeader, event_index: u8, event_code: u8,
+               accumulators: Accumulators) -> EventReportMessage {
+        EventReportMessage{
+            accumulators,
+            event_code,
+            event_index,
+            report_header
+        }
+    }
+
+    /// Parse event report data from a slice.
+    ///
+    /// Returns the EventReportMessage and parsed byte count.
+    pub fn parse(slice: &[u8]) -> Result<(EventReportMessage, usize), CalAmpError> {
+        EventReportMessage::parse_traced(slice, &mut Trace::disabled())
+    }
+
+    /// Parse event report data from a slice, recording field spans into `trace`.
+    ///
+    /// Returns the EventReportMessage and parsed byte count.
+    pub fn parse_traced(slice: &[u8], trace: &mut Trace)
+    -> Result<(EventReportMessage, usize), CalAmpError> {
+        trace.begin("Report header", 0);
+
+        let (report_header, mut index) = ReportHeader::parse_traced(slice, trace)?;
+
+        trace.end(index);
+
+        let event_index = trace_field!(trace, "Event index", index, read_u8!(slice, index), "{}");
+        let event_code  = trace_field!(trace, "Event code", index, read_u8!(slice, index), "{}");
+
+        trace.begin("Accumulators", index);
+
+        let (accumulators, byte_count) = Accumulators::parse_traced(&slice[index..], trace)?;
+
+        trace.end(byte_count);
+
+        index += byte_count;
+
+        Ok((EventReportMessage{
+            accumulators,
+            event_code,
+            event_index,
+            report_header
+        }, index))
+    }
+
+    /// Encode event report data into a buffer.
+    pub fn encode(&self, buffer: &mut Vec<u8>) {
+        self.report_header.encode(buffer);
+
+        buffer.push(self.event_index);
+        buffer.push(self.event_code);
+
+        self.accumulators.encode(buffer);
+    }
+
+    /// Retrieve the accumulators.
+    pub fn accumulators(&self) -> &Accumulators {
+        &self.accumulators
+    }
+
+    /// Retrieve the event code.
+    pub fn event_code(&self) -> u8 {
+        self.event_code
+    }
+
+    /// Retrieve the event index.
+    pub fn event_index(&self) -> u8 {
+        self.event_index
+    }
+
+    /// Retrieve the report header.
+    pub fn report_header(&self) -> &ReportHeader {
+        &self.report_header
+    }
+}
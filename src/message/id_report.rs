@@ -0,0 +1,347 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+use CalAmpError;
+use bcd;
+use dissect::Trace;
+use flags::UnitStatus;
+
+/// Lengths of the ESN, IMEI, IMSI, MIN and ICC-ID in bytes.
+const IDENTIFIER_LENGTHS: [usize; 5] = [8, 8, 8, 8, 10];
+
+/// ID report message.
+#[derive(Clone,Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct IdReportMessage {
+    /// Application ID.
+    application_id: u8,
+
+    /// Application version.
+    application_version: [u8; 3],
+
+    /// Configuration version.
+    config_version: [u8; 3],
+
+    /// Electronic serial number.
+    esn: String,
+
+    /// Extension strings.
+    extension: Vec<u8>,
+
+    /// Integrated circuit card ID of the SIM card.
+    iccid: String,
+
+    /// International mobile equipment ID.
+    imei: String,
+
+    /// International mobile subscriber ID.
+    imsi: String,
+
+    /// ESN, IMEI, IMSI, MIN and ICC-ID bytes as received, kept when the digits would not encode
+    /// back the same. The bytes of each identifier are written back while it is unchanged.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    identifier_bytes: Option<Vec<u8>>,
+
+    /// Mobile identification number.
+    min: String,
+
+    /// Mobile ID type.
+    mobile_id_type: u8,
+
+    /// Modem selection.
+    modem_selection: u8,
+
+    /// Query ID.
+    query_id: u32,
+
+    /// Script version.
+    script_version: u8,
+
+    /// Unit status.
+    unit_status: UnitStatus,
+
+    /// Vehicle class.
+    vehicle_class: u8
+}
+
+impl IdReportMessage {
+    /// Create a new IdReportMessage with empty identifiers.
+    pub fn new(script_version: u8, config_version: [u8; 3], application_version: [u8; 3])
+    -> IdReportMessage {
+        IdReportMessage{
+            application_id:   0,
+            application_version,
+            config_version,
+            esn:              String::new(),
+            extension:        Vec::new(),
+            iccid:            String::new(),
+            identifier_bytes: None,
+            imei:             String::new(),
+            imsi:             String::new(),
+            min:              String::new(),
+            mobile_id_type:   0,
+            modem_selection:  0,
+            query_id:         0,
+            script_version,
+            unit_status:      UnitStatus::default(),
+            vehicle_class:    0
+        }
+    }
+
+    /// Parse ID report data from a slice.
+    ///
+    /// Returns the IdReportMessage and parsed byte count.
+    pub fn parse(slice: &[u8]) -> Result<(IdReportMessage, usize), CalAmpError> {
+        IdReportMessage::parse_traced(slice, &mut Trace::disabled())
+    }
+
+    /// Parse ID report data from a slice, recording field spans into `trace`.
+    ///
+    /// Returns the IdReportMessage and parsed byte count.
+    pub fn parse_traced(slice: &[u8], trace: &mut Trace)
+    -> Result<(IdReportMessage, usize), CalAmpError> {
+        // slice index
+        let mut index = 0;
+
+        let script_version = trace_field!(trace, "Script version", index,
+                                          read_u8!(slice, index), "{}");
+
+        let mut config_version = [0; 3];
+
+        read_into_array!(slice, index, config_version);
+
+        trace.field("Config version", index - 3, index, || format!("{:?}", config_version));
+
+        let mut application_version = [0; 3];
+
+        read_into_array!(slice, index, application_version);
+
+        trace.field("Application version", index - 3, index, || {
+            format!("{:?}", application_version)
+        });
+
+        let vehicle_class   = trace_field!(trace, "Vehicle class", index,
+                                           read_u8!(slice, index), "{}");
+        let unit_status     = trace_field!(trace, "Unit status", index,
+                                           UnitStatus::from_bits(read_u8!(slice, index)), "{:?}");
+        let modem_selection = trace_field!(trace, "Modem selection", index,
+                                           read_u8!(slice, index), "{}");
+        let application_id  = trace_field!(trace, "Application id", index,
+                                           read_u8!(slice, index), "{}");
+        let mobile_id_type  = trace_field!(trace, "Mobile id type", index,
+                                           read_u8!(slice, index), "{}");
+        let query_id        = trace_field!(trace, "Query id", index,
+                                           read_u32!(slice, index), "{}");
+        let identifiers     = index;
+        let esn             = trace_field!(trace, "ESN", index,
+                                           bcd::decode(&read_vector!(slice, index, 8)), "{}");
+        let imei            = trace_field!(trace, "IMEI", index,
+                                           bcd::decode(&read_vector!(slice, index, 8)), "{}");
+        let imsi            = trace_field!(trace, "IMSI", index,
+                                           bcd::decode(&read_vector!(slice, index, 8)), "{}");
+        let min             = trace_field!(trace, "MIN", index,
+                                           bcd::decode(&read_vector!(slice, index, 8)), "{}");
+        let iccid           = trace_field!(trace, "ICCID", index,
+                                           bcd::decode(&read_vector!(slice, index, 10)), "{}");
+
+        let mut encoded = Vec::new();
+
+        for (identifier, &length) in [&esn, &imei, &imsi, &min, &iccid].iter()
+                                                                      .zip(&IDENTIFIER_LENGTHS) {
+            bcd::encode_fixed(identifier, length, &mut encoded);
+        }
+
+        let identifier_bytes = if slice[identifiers..index] == encoded[..] {
+            None
+        } else {
+            Some(slice[identifiers..index].to_vec())
+        };
+
+        // extension strings run to the end of the message
+        let extension = slice[index..].to_vec();
+
+        if !extension.is_empty() {
+            trace.field("Extension", index, slice.len(), || {
+                format!("{:?}", String::from_utf8_lossy(&extension))
+            });
+        }
+
+        index = slice.len();
+
+        Ok((IdReportMessage{
+            application_id,
+            application_version,
+            config_version,
+            esn,
+            extension,
+            iccid,
+            identifier_bytes,
+            imei,
+            imsi,
+            min,
+            mobile_id_type,
+            modem_selection,
+            query_id,
+            script_version,
+            unit_status,
+            vehicle_class
+        }, index))
+    }
+
+    /// Encode ID report data into a buffer.
+    pub fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.push(self.script_version);
+        buffer.extend_from_slice(&self.config_version);
+        buffer.extend_from_slice(&self.application_version);
+        buffer.push(self.vehicle_class);
+        buffer.push(self.unit_status.bits());
+        buffer.push(self.modem_selection);
+        buffer.push(self.application_id);
+        buffer.push(self.mobile_id_type);
+        buffer.push((self.query_id >> 24) as u8);
+        buffer.push((self.query_id >> 16) as u8);
+        buffer.push((self.query_id >> 8) as u8);
+        buffer.push(self.query_id as u8);
+
+        let mut offset = 0;
+
+        for (identifier, &length) in [&self.esn, &self.imei, &self.imsi, &self.min, &self.iccid]
+                                         .iter()
+                                         .zip(&IDENTIFIER_LENGTHS) {
+            let received = self.identifier_bytes
+                               .as_ref()
+                               .and_then(|bytes| bytes.get(offset..offset + length));
+
+            match received {
+                Some(bytes) if bcd::decode(bytes) == **identifier => {
+                    buffer.extend_from_slice(bytes)
+                },
+                _ => bcd::encode_fixed(identifier, length, buffer)
+            }
+
+            offset += length;
+        }
+
+        buffer.extend_from_slice(&self.extension);
+    }
+
+    /// Retrieve the application ID.
+    pub fn application_id(&self) -> u8 {
+        self.application_id
+    }
+
+    /// Retrieve the application version.
+    pub fn application_version(&self) -> &[u8; 3] {
+        &self.application_version
+    }
+
+    /// Retrieve the configuration version.
+    pub fn config_version(&self) -> &[u8; 3] {
+        &self.config_version
+    }
+
+    /// Retrieve the ESN.
+    pub fn esn(&self) -> &str {
+        &self.esn
+    }
+
+    /// Retrieve the extension strings.
+    pub fn extension(&self) -> &[u8] {
+        &self.extension
+    }
+
+    /// Retrieve the ICC-ID.
+    pub fn iccid(&self) -> &str {
+        &self.iccid
+    }
+
+    /// Retrieve the IMEI.
+    pub fn imei(&self) -> &str {
+        &self.imei
+    }
+
+    /// Retrieve the IMSI.
+    pub fn imsi(&self) -> &str {
+        &self.imsi
+    }
+
+    /// Retrieve the MIN.
+    pub fn min(&self) -> &str {
+        &self.min
+    }
+
+    /// Retrieve the mobile ID type.
+    pub fn mobile_id_type(&self) -> u8 {
+        self.mobile_id_type
+    }
+
+    /// Retrieve the modem selection.
+    pub fn modem_selection(&self) -> u8 {
+        self.modem_selection
+    }
+
+    /// Retrieve the query ID.
+    pub fn query_id(&self) -> u32 {
+        self.query_id
+    }
+
+    /// Retrieve the script version.
+    pub fn script_version(&self) -> u8 {
+        self.script_version
+    }
+
+    /// Retrieve the unit status.
+    pub fn unit_status(&self) -> UnitStatus {
+        self.unit_status
+    }
+
+    /// Retrieve the vehicle class.
+    pub fn vehicle_class(&self) -> u8 {
+        self.vehicle_class
+    }
+
+    /// Set the ESN.
+    pub fn set_esn(&mut self, esn: &str) {
+        self.esn = esn.to_string();
+    }
+
+    /// Set the ICC-ID.
+    pub fn set_iccid(&mut self, iccid: &str) {
+        self.iccid = iccid.to_string();
+    }
+
+    /// Set the IMEI.
+    pub fn set_imei(&mut self, imei: &str) {
+        self.imei = imei.to_string();
+    }
+
+    /// Set the IMSI.
+    pub fn set_imsi(&mut self, imsi: &str) {
+        self.imsi = imsi.to_string();
+    }
+
+    /// Set the mobile ID type.
+    pub fn set_mobile_id_type(&mut self, mobile_id_type: u8) {
+        self.mobile_id_type = mobile_id_type;
+    }
+
+    /// Set the unit status.
+    pub fn set_unit_status(&mut self, unit_status: UnitStatus) {
+        self.unit_status = unit_status;
+    }
+}
@@ -0,0 +1,183 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+use CalAmpError;
+use dissect::Trace;
+use flags::{CommState, FixStatus, Inputs};
+use gps_fix::GpsFix;
+use lmu_time::{LmuTime, UpdateTime};
+
+/// Common fields that lead mini event report and mini user messages.
+///
+/// Mini messages carry a reduced position block: the time in its shortened form, speed in whole
+/// kilometers per hour, the satellite count and the first four fix status flags sharing a byte,
+/// and no altitude or HDOP.
+#[derive(Clone,Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct MiniReportHeader {
+    /// Communication state.
+    comm_state: CommState,
+
+    /// GPS fix.
+    gps_fix: GpsFix,
+
+    /// Input states.
+    inputs: Inputs,
+
+    /// Update time in the shortened form, the seconds into the GPS week.
+    time: u32
+}
+
+impl MiniReportHeader {
+    /// Create a new MiniReportHeader with no input or communication state details.
+    ///
+    /// The fix is reduced to what mini messages carry: speed is rounded to whole kilometers per
+    /// hour, the satellite count is capped at 15, only the first four fix status flags are kept,
+    /// and altitude and HDOP are dropped.
+    pub fn new(update_time: UpdateTime, gps_fix: GpsFix) -> MiniReportHeader {
+        let speed = (gps_fix.speed_kmh().round() as u64).min(u8::MAX as u64) as u8;
+
+        MiniReportHeader{
+            comm_state: CommState::default(),
+            gps_fix:    mini_fix(gps_fix.latitude_raw(), gps_fix.longitude_raw(),
+                                 gps_fix.heading(), speed,
+                                 (gps_fix.fix_status().bits() << 4) |
+                                 gps_fix.satellites().min(15)),
+            inputs:     Inputs::default(),
+            time:       update_time.to_short()
+        }
+    }
+
+    /// Parse mini report header data from a slice.
+    ///
+    /// Returns the MiniReportHeader and parsed byte count.
+    pub fn parse(slice: &[u8]) -> Result<(MiniReportHeader, usize), CalAmpError> {
+        MiniReportHeader::parse_traced(slice, &mut Trace::disabled())
+    }
+
+    /// Parse mini report header data from a slice, recording field spans into `trace`.
+    ///
+    /// Returns the MiniReportHeader and parsed byte count.
+    pub fn parse_traced(slice: &[u8], trace: &mut Trace)
+    -> Result<(MiniReportHeader, usize), CalAmpError> {
+        // slice index
+        let mut index = 0;
+
+        let time = trace_field!(trace, "Time (seconds into GPS week)", index,
+                                ((read_u8!(slice, index) as u32) << 16) |
+                                read_u16!(slice, index) as u32, "{}");
+        let latitude = read_u32!(slice, index) as i32;
+
+        trace.field("Latitude", index - 4, index, || {
+            format!("{} ({})", f64::from(latitude) / 10_000_000.0, latitude)
+        });
+
+        let longitude = read_u32!(slice, index) as i32;
+
+        trace.field("Longitude", index - 4, index, || {
+            format!("{} ({})", f64::from(longitude) / 10_000_000.0, longitude)
+        });
+
+        let heading    = trace_field!(trace, "Heading", index, read_u16!(slice, index), "{}");
+        let speed      = trace_field!(trace, "Speed (km/h)", index, read_u8!(slice, index), "{}");
+        let fix        = read_u8!(slice, index);
+
+        trace.field("Fix status / satellites", index - 1, index, || {
+            format!("{:?}, {} satellites", FixStatus::from_bits(fix >> 4), fix & 0x0F)
+        });
+
+        let comm_state = trace_field!(trace, "Comm state", index,
+                                      CommState::from_bits(read_u8!(slice, index)), "{:?}");
+        let inputs     = trace_field!(trace, "Inputs", index,
+                                      Inputs::from_bits(read_u8!(slice, index)), "{:?}");
+
+        Ok((MiniReportHeader{
+            comm_state,
+            gps_fix: mini_fix(latitude, longitude, heading, speed, fix),
+            inputs,
+            time
+        }, index))
+    }
+
+    /// Encode mini report header data into a buffer.
+    pub fn encode(&self, buffer: &mut Vec<u8>) {
+        let fix = &self.gps_fix;
+
+        buffer.extend_from_slice(&self.time.to_be_bytes()[1..]);
+        buffer.extend_from_slice(&fix.latitude_raw().to_be_bytes());
+        buffer.extend_from_slice(&fix.longitude_raw().to_be_bytes());
+        buffer.extend_from_slice(&fix.heading().to_be_bytes());
+        buffer.push(self.speed());
+        buffer.push((fix.fix_status().bits() << 4) | fix.satellites());
+        buffer.push(self.comm_state.bits());
+        buffer.push(self.inputs.bits());
+    }
+
+    /// Retrieve the communication state.
+    pub fn comm_state(&self) -> CommState {
+        self.comm_state
+    }
+
+    /// Retrieve the GPS fix.
+    pub fn gps_fix(&self) -> &GpsFix {
+        &self.gps_fix
+    }
+
+    /// Retrieve the input states.
+    pub fn inputs(&self) -> Inputs {
+        self.inputs
+    }
+
+    /// Retrieve the speed in kilometers per hour, as sent.
+    pub fn speed(&self) -> u8 {
+        ((self.gps_fix.speed_cm_s() * 36 + 500) / 1_000) as u8
+    }
+
+    /// Retrieve the update time in the shortened form, the seconds into the GPS week.
+    pub fn time_short(&self) -> u32 {
+        self.time
+    }
+
+    /// Retrieve the update time, placed in the GPS week nearest to `reference`, such as the time
+    /// the message was received.
+    ///
+    /// Returns `None` when the shortened time is not within a week, or the time is outside the
+    /// 32-bit range.
+    pub fn update_time<K>(&self, reference: LmuTime<K>) -> Option<UpdateTime> {
+        UpdateTime::from_short(self.time, reference)
+    }
+
+    /// Set the communication state.
+    pub fn set_comm_state(&mut self, comm_state: CommState) {
+        self.comm_state = comm_state;
+    }
+
+    /// Set the input states.
+    pub fn set_inputs(&mut self, inputs: Inputs) {
+        self.inputs = inputs;
+    }
+}
+
+/// Build the GPS fix of a mini message from its wire values.
+fn mini_fix(latitude: i32, longitude: i32, heading: u16, speed: u8, fix: u8) -> GpsFix {
+    // whole km/h to cm/s, rounded to nearest so that `speed()` recovers the wire value
+    let speed = (speed as u32 * 1_000 + 18) / 36;
+
+    GpsFix::new(latitude, longitude, 0, speed, heading, fix & 0x0F,
+                FixStatus::from_bits(fix >> 4), 0)
+}
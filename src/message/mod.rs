@@ -16,6 +16,213 @@
 // | Author: Sean Kerr <sean@code-box.org>                                                         |
 // +-----------------------------------------------------------------------------------------------+
 
-#[allow(dead_code)]
+pub mod accumulator;
 pub mod acknowledgement;
+pub mod application;
+pub mod event_report;
+pub mod id_report;
+pub mod locate_report;
+pub mod mini_event_report;
+pub mod mini_report_header;
+pub mod mini_user;
 pub mod null;
+pub mod report_header;
+pub mod user;
+
+use CalAmpError;
+use dissect::Trace;
+use gps_fix::GpsFix;
+use message::accumulator::AccumulatorMessage;
+use message::acknowledgement::AcknowledgementMessage;
+use message::application::ApplicationMessage;
+use message::event_report::EventReportMessage;
+use message::id_report::IdReportMessage;
+use message::locate_report::LocateReportMessage;
+use message::mini_event_report::MiniEventReportMessage;
+use message::mini_user::MiniUserMessage;
+use message::null::NullMessage;
+use message::mini_report_header::MiniReportHeader;
+use message::report_header::ReportHeader;
+use message::user::UserMessage;
+use message_header::MessageType;
+
+/// Message body.
+#[derive(Clone,Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Message {
+    /// ACK/NAK message.
+    AckNak(AcknowledgementMessage),
+
+    /// Application data message.
+    ApplicationData(ApplicationMessage),
+
+    /// Event report message.
+    EventReport(EventReportMessage),
+
+    /// ID report message.
+    IdReport(IdReportMessage),
+
+    /// Locate report message.
+    LocateReport(LocateReportMessage),
+
+    /// Mini event report message.
+    MiniEventReport(MiniEventReportMessage),
+
+    /// Mini user data message.
+    MiniUser(MiniUserMessage),
+
+    /// Null message.
+    Null(NullMessage),
+
+    /// Message body that is not decoded.
+    Raw(Vec<u8>),
+
+    /// User data message.
+    UserData(UserMessage),
+
+    /// User data message with accumulators.
+    UserDataAccumulators(AccumulatorMessage)
+}
+
+impl Message {
+    /// Parse a message body of type `message_type` from a slice.
+    ///
+    /// Returns the Message and parsed byte count.
+    pub fn parse(message_type: &MessageType, slice: &[u8]) -> Result<(Message, usize), CalAmpError> {
+        Message::parse_traced(message_type, slice, &mut Trace::disabled())
+    }
+
+    /// Parse a message body of type `message_type` from a slice, recording field spans into
+    /// `trace`.
+    ///
+    /// Returns the Message and parsed byte count.
+    pub fn parse_traced(message_type: &MessageType, slice: &[u8], trace: &mut Trace)
+    -> Result<(Message, usize), CalAmpError> {
+        match *message_type {
+            MessageType::AckNak => {
+                let (message, byte_count) = AcknowledgementMessage::parse_traced(slice, trace)?;
+
+                Ok((Message::AckNak(message), byte_count))
+            },
+            MessageType::ApplicationData => {
+                let (message, byte_count) = ApplicationMessage::parse_traced(slice, trace)?;
+
+                Ok((Message::ApplicationData(message), byte_count))
+            },
+            MessageType::EventReport => {
+                let (message, byte_count) = EventReportMessage::parse_traced(slice, trace)?;
+
+                Ok((Message::EventReport(message), byte_count))
+            },
+            MessageType::IdReport => {
+                let (message, byte_count) = IdReportMessage::parse_traced(slice, trace)?;
+
+                Ok((Message::IdReport(message), byte_count))
+            },
+            MessageType::LocateReport => {
+                let (message, byte_count) = LocateReportMessage::parse_traced(slice, trace)?;
+
+                Ok((Message::LocateReport(message), byte_count))
+            },
+            MessageType::MiniEventReport => {
+                let (message, byte_count) = MiniEventReportMessage::parse_traced(slice, trace)?;
+
+                Ok((Message::MiniEventReport(message), byte_count))
+            },
+            MessageType::MiniUser => {
+                let (message, byte_count) = MiniUserMessage::parse_traced(slice, trace)?;
+
+                Ok((Message::MiniUser(message), byte_count))
+            },
+            MessageType::Null => {
+                let (message, byte_count) = NullMessage::parse_traced(slice, trace)?;
+
+                Ok((Message::Null(message), byte_count))
+            },
+            MessageType::UserData => {
+                let (message, byte_count) = UserMessage::parse_traced(slice, trace)?;
+
+                Ok((Message::UserData(message), byte_count))
+            },
+            MessageType::UserDataAccumulators => {
+                let (message, byte_count) = AccumulatorMessage::parse_traced(slice, trace)?;
+
+                Ok((Message::UserDataAccumulators(message), byte_count))
+            },
+            _ => {
+                trace.field("Message data", 0, slice.len(), || format!("{} bytes", slice.len()));
+
+                Ok((Message::Raw(slice.to_vec()), slice.len()))
+            }
+        }
+    }
+
+    /// Encode the message body into a buffer.
+    pub fn encode(&self, buffer: &mut Vec<u8>) {
+        match *self {
+            Message::AckNak(ref message) => {
+                message.encode(buffer)
+            },
+            Message::ApplicationData(ref message) => {
+                message.encode(buffer)
+            },
+            Message::EventReport(ref message) => {
+                message.encode(buffer)
+            },
+            Message::IdReport(ref message) => {
+                message.encode(buffer)
+            },
+            Message::LocateReport(ref message) => {
+                message.encode(buffer)
+            },
+            Message::MiniEventReport(ref message) => {
+                message.encode(buffer)
+            },
+            Message::MiniUser(ref message) => {
+                message.encode(buffer)
+            },
+            Message::Null(ref message) => {
+                message.encode(buffer)
+            },
+            Message::Raw(ref bytes) => {
+                buffer.extend_from_slice(bytes)
+            },
+            Message::UserData(ref message) => {
+                message.encode(buffer)
+            },
+            Message::UserDataAccumulators(ref message) => {
+                message.encode(buffer)
+            }
+        }
+    }
+
+    /// Retrieve the report header of position-carrying messages other than mini messages.
+    pub fn report_header(&self) -> Option<&ReportHeader> {
+        match *self {
+            Message::ApplicationData(ref message) => Some(message.report_header()),
+            Message::EventReport(ref message) => Some(message.report_header()),
+            Message::LocateReport(ref message) => Some(message.report_header()),
+            Message::UserData(ref message) => Some(message.report_header()),
+            Message::UserDataAccumulators(ref message) => Some(message.report_header()),
+            _ => None
+        }
+    }
+
+    /// Retrieve the mini report header of mini messages.
+    pub fn mini_report_header(&self) -> Option<&MiniReportHeader> {
+        match *self {
+            Message::MiniEventReport(ref message) => Some(message.report_header()),
+            Message::MiniUser(ref message) => Some(message.report_header()),
+            _ => None
+        }
+    }
+
+    /// Retrieve the GPS fix of position-carrying messages, including mini messages.
+    pub fn gps_fix(&self) -> Option<&GpsFix> {
+        match self.mini_report_header() {
+            Some(report_header) => Some(report_header.gps_fix()),
+            None => self.report_header().map(|report_header| report_header.gps_fix())
+        }
+    }
+}
@@ -16,5 +16,55 @@
 // | Author: Sean Kerr <sean@metatomic.io>                                                         |
 // +-----------------------------------------------------------------------------------------------+
 
+use CalAmpError;
+use dissect::Trace;
+
 /// Null message.
-struct NullMessage;
+#[derive(Clone,Debug,Default)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct NullMessage {
+    /// Data following the message header, which a null message is not expected to carry.
+    #[cfg_attr(feature = "serde", serde(default))]
+    data: Vec<u8>
+}
+
+impl NullMessage {
+    /// Create a new NullMessage.
+    pub fn new() -> NullMessage {
+        NullMessage::default()
+    }
+
+    /// Parse null message data from a slice. A null message carries no data, and any data present
+    /// is kept as is.
+    ///
+    /// Returns the NullMessage and parsed byte count.
+    pub fn parse(slice: &[u8]) -> Result<(NullMessage, usize), CalAmpError> {
+        NullMessage::parse_traced(slice, &mut Trace::disabled())
+    }
+
+    /// Parse null message data from a slice, recording field spans into `trace`. A null message
+    /// carries no data, and any data present is kept as is.
+    ///
+    /// Returns the NullMessage and parsed byte count.
+    pub fn parse_traced(slice: &[u8], trace: &mut Trace)
+    -> Result<(NullMessage, usize), CalAmpError> {
+        if !slice.is_empty() {
+            trace.field("Unexpected data", 0, slice.len(), || format!("{:?}", slice));
+        }
+
+        Ok((NullMessage{
+            data: slice.to_vec()
+        }, slice.len()))
+    }
+
+    /// Encode null message data into a buffer.
+    pub fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.data);
+    }
+
+    /// Retrieve the data following the message header, which is empty for a well formed null
+    /// message.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
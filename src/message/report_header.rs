@@ -0,0 +1,221 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+use CalAmpError;
+use dissect::Trace;
+use flags::{CommState, FixStatus, Inputs, UnitStatus};
+use gps_fix::GpsFix;
+use lmu_time::{FixTime, UpdateTime};
+use signal::{CarrierId, Rssi};
+
+/// Common fields that lead event, locate, user, and application messages.
+#[derive(Clone,Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct ReportHeader {
+    /// Carrier ID.
+    carrier: CarrierId,
+
+    /// Communication state.
+    comm_state: CommState,
+
+    /// GPS fix.
+    gps_fix: GpsFix,
+
+    /// Input states.
+    inputs: Inputs,
+
+    /// Received signal strength.
+    rssi: Rssi,
+
+    /// Time of fix.
+    time_of_fix: FixTime,
+
+    /// Unit status.
+    unit_status: UnitStatus,
+
+    /// Update time.
+    update_time: UpdateTime
+}
+
+impl ReportHeader {
+    /// Create a new ReportHeader with no carrier, signal, input or status details.
+    pub fn new(update_time: UpdateTime, time_of_fix: FixTime, gps_fix: GpsFix) -> ReportHeader {
+        ReportHeader{
+            carrier:     CarrierId::new(0),
+            comm_state:  CommState::default(),
+            gps_fix,
+            inputs:      Inputs::default(),
+            rssi:        Rssi::new(0),
+            time_of_fix,
+            unit_status: UnitStatus::default(),
+            update_time
+        }
+    }
+
+    /// Parse report header data from a slice.
+    ///
+    /// Returns the ReportHeader and parsed byte count.
+    pub fn parse(slice: &[u8]) -> Result<(ReportHeader, usize), CalAmpError> {
+        ReportHeader::parse_traced(slice, &mut Trace::disabled())
+    }
+
+    /// Parse report header data from a slice, recording field spans into `trace`.
+    ///
+    /// Returns the ReportHeader and parsed byte count.
+    pub fn parse_traced(slice: &[u8], trace: &mut Trace)
+    -> Result<(ReportHeader, usize), CalAmpError> {
+        // slice index
+        let mut index = 0;
+
+        let update_time = trace_field!(trace, "Update time", index,
+                                       UpdateTime::from_secs(read_u32!(slice, index)), "{}");
+        let time_of_fix = trace_field!(trace, "Time of fix", index,
+                                       FixTime::from_secs(read_u32!(slice, index)), "{}");
+        let latitude    = read_u32!(slice, index) as i32;
+
+        trace.field("Latitude", index - 4, index, || {
+            format!("{} ({})", f64::from(latitude) / 10_000_000.0, latitude)
+        });
+
+        let longitude = read_u32!(slice, index) as i32;
+
+        trace.field("Longitude", index - 4, index, || {
+            format!("{} ({})", f64::from(longitude) / 10_000_000.0, longitude)
+        });
+
+        let altitude    = trace_field!(trace, "Altitude (cm)", index,
+                                       read_u32!(slice, index) as i32, "{}");
+        let speed       = trace_field!(trace, "Speed (cm/s)", index, read_u32!(slice, index), "{}");
+        let heading     = trace_field!(trace, "Heading", index, read_u16!(slice, index), "{}");
+        let satellites  = trace_field!(trace, "Satellites", index, read_u8!(slice, index), "{}");
+        let fix_status  = trace_field!(trace, "Fix status", index,
+                                       FixStatus::from_bits(read_u8!(slice, index)), "{:?}");
+        let carrier     = trace_field!(trace, "Carrier", index,
+                                       CarrierId::new(read_u16!(slice, index)), "{}");
+        let rssi        = trace_field!(trace, "RSSI", index,
+                                       Rssi::new(read_u16!(slice, index) as i16), "{}");
+        let comm_state  = trace_field!(trace, "Comm state", index,
+                                       CommState::from_bits(read_u8!(slice, index)), "{:?}");
+        let hdop        = read_u8!(slice, index);
+
+        trace.field("HDOP", index - 1, index, || format!("{} ({})", f32::from(hdop) / 10.0, hdop));
+
+        let inputs      = trace_field!(trace, "Inputs", index,
+                                       Inputs::from_bits(read_u8!(slice, index)), "{:?}");
+        let unit_status = trace_field!(trace, "Unit status", index,
+                                       UnitStatus::from_bits(read_u8!(slice, index)), "{:?}");
+
+        Ok((ReportHeader{
+            carrier,
+            comm_state,
+            gps_fix: GpsFix::new(latitude, longitude, altitude, speed, heading, satellites,
+                                 fix_status, hdop),
+            inputs,
+            rssi,
+            time_of_fix,
+            unit_status,
+            update_time
+        }, index))
+    }
+
+    /// Encode report header data into a buffer.
+    pub fn encode(&self, buffer: &mut Vec<u8>) {
+        let fix = &self.gps_fix;
+
+        buffer.extend_from_slice(&self.update_time.as_secs().to_be_bytes());
+        buffer.extend_from_slice(&self.time_of_fix.as_secs().to_be_bytes());
+        buffer.extend_from_slice(&fix.latitude_raw().to_be_bytes());
+        buffer.extend_from_slice(&fix.longitude_raw().to_be_bytes());
+        buffer.extend_from_slice(&fix.altitude_cm().to_be_bytes());
+        buffer.extend_from_slice(&fix.speed_cm_s().to_be_bytes());
+        buffer.extend_from_slice(&fix.heading().to_be_bytes());
+        buffer.push(fix.satellites());
+        buffer.push(fix.fix_status().bits());
+        buffer.extend_from_slice(&self.carrier.value().to_be_bytes());
+        buffer.extend_from_slice(&self.rssi.dbm().to_be_bytes());
+        buffer.push(self.comm_state.bits());
+        buffer.push(fix.hdop_raw());
+        buffer.push(self.inputs.bits());
+        buffer.push(self.unit_status.bits());
+    }
+
+    /// Retrieve the carrier ID.
+    pub fn carrier(&self) -> CarrierId {
+        self.carrier
+    }
+
+    /// Retrieve the communication state.
+    pub fn comm_state(&self) -> CommState {
+        self.comm_state
+    }
+
+    /// Retrieve the GPS fix.
+    pub fn gps_fix(&self) -> &GpsFix {
+        &self.gps_fix
+    }
+
+    /// Retrieve the input states.
+    pub fn inputs(&self) -> Inputs {
+        self.inputs
+    }
+
+    /// Retrieve the received signal strength.
+    pub fn rssi(&self) -> Rssi {
+        self.rssi
+    }
+
+    /// Retrieve the time of fix.
+    pub fn time_of_fix(&self) -> FixTime {
+        self.time_of_fix
+    }
+
+    /// Retrieve the unit status.
+    pub fn unit_status(&self) -> UnitStatus {
+        self.unit_status
+    }
+
+    /// Retrieve the update time.
+    pub fn update_time(&self) -> UpdateTime {
+        self.update_time
+    }
+
+    /// Set the carrier ID.
+    pub fn set_carrier(&mut self, carrier: CarrierId) {
+        self.carrier = carrier;
+    }
+
+    /// Set the communication state.
+    pub fn set_comm_state(&mut self, comm_state: CommState) {
+        self.comm_state = comm_state;
+    }
+
+    /// Set the input states.
+    pub fn set_inputs(&mut self, inputs: Inputs) {
+        self.inputs = inputs;
+    }
+
+    /// Set the received signal strength.
+    pub fn set_rssi(&mut self, rssi: Rssi) {
+        self.rssi = rssi;
+    }
+
+    /// Set the unit status.
+    pub fn set_unit_status(&mut self, unit_status: UnitStatus) {
+        self.unit_status = unit_status;
+    }
+}
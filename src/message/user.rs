@@ -0,0 +1,114 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+use CalAmpError;
+use dissect::Trace;
+use message::report_header::ReportHeader;
+
+/// User data message.
+#[derive(Clone,Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct UserMessage {
+    /// User message data.
+    data: Vec<u8>,
+
+    /// User message ID.
+    id: u8,
+
+    /// Report header.
+    report_header: ReportHeader,
+
+    /// User message route.
+    route: u8
+}
+
+impl UserMessage {
+    /// Create a new UserMessage.
+    pub fn new(report_header: ReportHeader, route: u8, id: u8, data: Vec<u8>) -> UserMessage {
+        UserMessage{
+            data,
+            id,
+            report_header,
+            route
+        }
+    }
+
+    /// Parse user data from a slice.
+    ///
+    /// Returns the UserMessage and parsed byte count.
+    pub fn parse(slice: &[u8]) -> Result<(UserMessage, usize), CalAmpError> {
+        UserMessage::parse_traced(slice, &mut Trace::disabled())
+    }
+
+    /// Parse user data from a slice, recording field spans into `trace`.
+    ///
+    /// Returns the UserMessage and parsed byte count.
+    pub fn parse_traced(slice: &[u8], trace: &mut Trace)
+    -> Result<(UserMessage, usize), CalAmpError> {
+        trace.begin("Report header", 0);
+
+        let (report_header, mut index) = ReportHeader::parse_traced(slice, trace)?;
+
+        trace.end(index);
+
+        let route  = trace_field!(trace, "Route", index, read_u8!(slice, index), "{}");
+        let id     = trace_field!(trace, "Message id", index, read_u8!(slice, index), "{}");
+        let length = trace_field!(trace, "Message length", index,
+                                  read_u16!(slice, index) as usize, "{}");
+        let data   = read_vector!(slice, index, length);
+
+        trace.field("Message data", index - length, index, || format!("{} bytes", length));
+
+        Ok((UserMessage{
+            data,
+            id,
+            report_header,
+            route
+        }, index))
+    }
+
+    /// Encode user data into a buffer.
+    pub fn encode(&self, buffer: &mut Vec<u8>) {
+        self.report_header.encode(buffer);
+
+        buffer.push(self.route);
+        buffer.push(self.id);
+        buffer.extend_from_slice(&(self.data.len() as u16).to_be_bytes());
+        buffer.extend_from_slice(&self.data);
+    }
+
+    /// Retrieve the user message data.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Retrieve the user message ID.
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    /// Retrieve the report header.
+    pub fn report_header(&self) -> &ReportHeader {
+        &self.report_header
+    }
+
+    /// Retrieve the user message route.
+    pub fn route(&self) -> u8 {
+        self.route
+    }
+}
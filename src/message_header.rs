@@ -17,9 +17,11 @@
 // +-----------------------------------------------------------------------------------------------+
 
 use CalAmpError;
+use dissect::Trace;
 use std::fmt;
 
 #[derive(Clone,Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct MessageHeader {
     /// Message type.
     message_type: MessageType,
@@ -33,6 +35,7 @@ pub struct MessageHeader {
 
 impl MessageHeader {
     /// Create a new MessageHeader.
+    #[allow(clippy::redundant_field_names)]
     pub fn new(service_type: ServiceType, message_type: MessageType, sequence_number: u16)
     -> MessageHeader {
         MessageHeader{
@@ -46,39 +49,35 @@ impl MessageHeader {
     ///
     /// Returns the MessageHeader and parsed byte count.
     pub fn parse(slice: &[u8]) -> Result<(MessageHeader, usize), CalAmpError> {
+        MessageHeader::parse_traced(slice, &mut Trace::disabled())
+    }
+
+    /// Parse message header data from a slice, recording field spans into `trace`.
+    ///
+    /// Returns the MessageHeader and parsed byte count.
+    pub fn parse_traced(slice: &[u8], trace: &mut Trace)
+    -> Result<(MessageHeader, usize), CalAmpError> {
         // slice index
         let mut index = 0;
 
         Ok((MessageHeader{
-            service_type: match read_u8!(slice, index) {
-                0 => ServiceType::UnacknowledgedRequest,
-                1 => ServiceType::AcknowledgedRequest,
-                2 => ServiceType::Response,
-                x @ _ => {
-                    return Err(CalAmpError::ServiceType(x));
-                }
-            },
-            message_type: match read_u8!(slice, index) {
-                0 => MessageType::Null,
-                1 => MessageType::AckNak,
-                2 => MessageType::EventReport,
-                3 => MessageType::IdReport,
-                4 => MessageType::UserData,
-                5 => MessageType::ApplicationData,
-                6 => MessageType::ConfigurationParameter,
-                7 => MessageType::UnitRequest,
-                8 => MessageType::LocateReport,
-                9 => MessageType::UserDataAccumulators,
-                10 => MessageType::MiniEventReport,
-                11 => MessageType::MiniUser,
-                x @ _ => {
-                    return Err(CalAmpError::MessageType(x));
-                }
-            },
-            sequence_number: read_u16!(slice, index)
+            service_type:    trace_field!(trace, "Service type", index,
+                                          ServiceType::from_u8(read_u8!(slice, index))?, "{}"),
+            message_type:    trace_field!(trace, "Message type", index,
+                                          MessageType::from_u8(read_u8!(slice, index))?, "{}"),
+            sequence_number: trace_field!(trace, "Sequence number", index,
+                                          read_u16!(slice, index), "{}")
         }, index))
     }
 
+    /// Encode message header data into a buffer.
+    pub fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.push(self.service_type.as_u8());
+        buffer.push(self.message_type.as_u8());
+        buffer.push((self.sequence_number >> 8) as u8);
+        buffer.push(self.sequence_number as u8);
+    }
+
     /// Retrieve the message type.
     pub fn message_type(&self) -> &MessageType {
         &self.message_type
@@ -95,7 +94,9 @@ impl MessageHeader {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone,Copy,Eq,Hash,PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum MessageType {
     /// ACK/NAK message.
     AckNak,
@@ -134,6 +135,45 @@ pub enum MessageType {
     UserDataAccumulators
 }
 
+impl MessageType {
+    /// Convert a wire value into a MessageType.
+    pub fn from_u8(value: u8) -> Result<MessageType, CalAmpError> {
+        match value {
+            0 => Ok(MessageType::Null),
+            1 => Ok(MessageType::AckNak),
+            2 => Ok(MessageType::EventReport),
+            3 => Ok(MessageType::IdReport),
+            4 => Ok(MessageType::UserData),
+            5 => Ok(MessageType::ApplicationData),
+            6 => Ok(MessageType::ConfigurationParameter),
+            7 => Ok(MessageType::UnitRequest),
+            8 => Ok(MessageType::LocateReport),
+            9 => Ok(MessageType::UserDataAccumulators),
+            10 => Ok(MessageType::MiniEventReport),
+            11 => Ok(MessageType::MiniUser),
+            x => Err(CalAmpError::MessageType(x))
+        }
+    }
+
+    /// Retrieve the wire value.
+    pub fn as_u8(&self) -> u8 {
+        match *self {
+            MessageType::Null => 0,
+            MessageType::AckNak => 1,
+            MessageType::EventReport => 2,
+            MessageType::IdReport => 3,
+            MessageType::UserData => 4,
+            MessageType::ApplicationData => 5,
+            MessageType::ConfigurationParameter => 6,
+            MessageType::UnitRequest => 7,
+            MessageType::LocateReport => 8,
+            MessageType::UserDataAccumulators => 9,
+            MessageType::MiniEventReport => 10,
+            MessageType::MiniUser => 11
+        }
+    }
+}
+
 impl fmt::Debug for MessageType {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -220,7 +260,9 @@ impl fmt::Display for MessageType {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone,Copy,Eq,Hash,PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum ServiceType {
     /// Acknowledged request.
     AcknowledgedRequest,
@@ -232,6 +274,27 @@ pub enum ServiceType {
     UnacknowledgedRequest
 }
 
+impl ServiceType {
+    /// Convert a wire value into a ServiceType.
+    pub fn from_u8(value: u8) -> Result<ServiceType, CalAmpError> {
+        match value {
+            0 => Ok(ServiceType::UnacknowledgedRequest),
+            1 => Ok(ServiceType::AcknowledgedRequest),
+            2 => Ok(ServiceType::Response),
+            x => Err(CalAmpError::ServiceType(x))
+        }
+    }
+
+    /// Retrieve the wire value.
+    pub fn as_u8(&self) -> u8 {
+        match *self {
+            ServiceType::UnacknowledgedRequest => 0,
+            ServiceType::AcknowledgedRequest => 1,
+            ServiceType::Response => 2
+        }
+    }
+}
+
 impl fmt::Debug for ServiceType {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         match *self {
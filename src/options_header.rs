@@ -16,16 +16,124 @@
 // | Author: Sean Kerr <sean@metatomic.io>                                                         |
 // +-----------------------------------------------------------------------------------------------+
 
-use CalAmpError;
+use {CalAmpError, ParseOptions, ParseWarning};
+use bcd;
+use dissect::Trace;
+use util::decode_hex;
 use std::fmt;
+use std::net::Ipv4Addr;
+use std::str::FromStr;
 
-#[derive(Clone)]
+#[derive(Clone,Copy,Eq,Hash,PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum EncryptionType {
+    /// Encryption is based on the LMU/TTU ESN.
+    Esn,
+
+    /// Encryption is based on the IMEI or MEID.
+    ImeiMeid,
+
+    /// Encryption is based on the mobile ID.
+    MobileId,
+
+    /// No encryption.
+    Unencrypted
+}
+
+impl EncryptionType {
+    /// Create a new EncryptionType from its wire value.
+    pub fn from_u8(value: u8) -> Result<EncryptionType, CalAmpError> {
+        match value {
+            0 => Ok(EncryptionType::Unencrypted),
+            1 => Ok(EncryptionType::Esn),
+            2 => Ok(EncryptionType::ImeiMeid),
+            3 => Ok(EncryptionType::MobileId),
+            x => Err(CalAmpError::EncryptionType(x))
+        }
+    }
+
+    /// Retrieve the wire value.
+    pub fn as_u8(&self) -> u8 {
+        match *self {
+            EncryptionType::Unencrypted => 0,
+            EncryptionType::Esn => 1,
+            EncryptionType::ImeiMeid => 2,
+            EncryptionType::MobileId => 3
+        }
+    }
+}
+
+impl fmt::Debug for EncryptionType {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EncryptionType::Esn => {
+                write!(formatter, "EncryptionType::Esn")
+            },
+            EncryptionType::ImeiMeid => {
+                write!(formatter, "EncryptionType::ImeiMeid")
+            },
+            EncryptionType::MobileId => {
+                write!(formatter, "EncryptionType::MobileId")
+            },
+            EncryptionType::Unencrypted => {
+                write!(formatter, "EncryptionType::Unencrypted")
+            }
+        }
+    }
+}
+
+impl fmt::Display for EncryptionType {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EncryptionType::Esn => {
+                write!(formatter, "Esn")
+            },
+            EncryptionType::ImeiMeid => {
+                write!(formatter, "ImeiMeid")
+            },
+            EncryptionType::MobileId => {
+                write!(formatter, "MobileId")
+            },
+            EncryptionType::Unencrypted => {
+                write!(formatter, "Unencrypted")
+            }
+        }
+    }
+}
+
+#[derive(Clone,Copy,Eq,Hash,PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum ForwardingProtocol {
     /// TCP protocol.
     Tcp,
 
     /// UDP protocol.
-    Udp
+    Udp,
+
+    /// Unknown protocol, kept as its wire value.
+    Unknown(u8)
+}
+
+impl ForwardingProtocol {
+    /// Create a new ForwardingProtocol from its wire value.
+    pub fn from_u8(value: u8) -> ForwardingProtocol {
+        match value {
+            6 => ForwardingProtocol::Tcp,
+            17 => ForwardingProtocol::Udp,
+            x => ForwardingProtocol::Unknown(x)
+        }
+    }
+
+    /// Retrieve the wire value.
+    pub fn as_u8(&self) -> u8 {
+        match *self {
+            ForwardingProtocol::Tcp => 6,
+            ForwardingProtocol::Udp => 17,
+            ForwardingProtocol::Unknown(x) => x
+        }
+    }
 }
 
 impl fmt::Debug for ForwardingProtocol {
@@ -36,6 +144,9 @@ impl fmt::Debug for ForwardingProtocol {
             },
             ForwardingProtocol::Udp => {
                 write!(formatter, "ForwardingProtocol::Udp")
+            },
+            ForwardingProtocol::Unknown(x) => {
+                write!(formatter, "ForwardingProtocol::Unknown({})", x)
             }
         }
     }
@@ -49,12 +160,17 @@ impl fmt::Display for ForwardingProtocol {
             },
             ForwardingProtocol::Udp => {
                 write!(formatter, "UDP")
+            },
+            ForwardingProtocol::Unknown(x) => {
+                write!(formatter, "Unknown({})", x)
             }
         }
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone,Copy,Eq,Hash,PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum ForwardingOperationType {
     /// Standard forwarding.
     Forward,
@@ -63,7 +179,32 @@ pub enum ForwardingOperationType {
     ForwardLookup,
 
     /// Proxy forwarding.
-    Proxy
+    Proxy,
+
+    /// Unknown operation type, kept as its wire value.
+    Unknown(u8)
+}
+
+impl ForwardingOperationType {
+    /// Create a new ForwardingOperationType from its wire value.
+    pub fn from_u8(value: u8) -> ForwardingOperationType {
+        match value {
+            0 => ForwardingOperationType::Forward,
+            1 => ForwardingOperationType::Proxy,
+            2 => ForwardingOperationType::ForwardLookup,
+            x => ForwardingOperationType::Unknown(x)
+        }
+    }
+
+    /// Retrieve the wire value.
+    pub fn as_u8(&self) -> u8 {
+        match *self {
+            ForwardingOperationType::Forward => 0,
+            ForwardingOperationType::Proxy => 1,
+            ForwardingOperationType::ForwardLookup => 2,
+            ForwardingOperationType::Unknown(x) => x
+        }
+    }
 }
 
 impl fmt::Debug for ForwardingOperationType {
@@ -77,6 +218,9 @@ impl fmt::Debug for ForwardingOperationType {
             },
             ForwardingOperationType::Proxy => {
                 write!(formatter, "ForwardingOperationType::Proxy")
+            },
+            ForwardingOperationType::Unknown(x) => {
+                write!(formatter, "ForwardingOperationType::Unknown({})", x)
             }
         }
     }
@@ -93,12 +237,17 @@ impl fmt::Display for ForwardingOperationType {
             },
             ForwardingOperationType::Proxy => {
                 write!(formatter, "Proxy")
+            },
+            ForwardingOperationType::Unknown(x) => {
+                write!(formatter, "Unknown({})", x)
             }
         }
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone,Eq,Hash,PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum MobileId {
     /// Electronic serial number.
     Esn(String),
@@ -115,10 +264,48 @@ pub enum MobileId {
     /// Phone number.
     Phone(String),
 
+    /// Mobile ID of an unknown type, or an IP address that is not 4 bytes, kept as its type wire
+    /// value and details. A mobile ID supplied without a type has type 0.
+    Unknown(u8, Vec<u8>),
+
     /// User defined mobile ID.
     User(Vec<u8>)
 }
 
+impl MobileId {
+    /// Retrieve the mobile ID type wire value.
+    pub fn type_value(&self) -> u8 {
+        match *self {
+            MobileId::Esn(_) => 1,
+            MobileId::ImeiEid(_) => 2,
+            MobileId::Imsi(_) => 3,
+            MobileId::User(_) => 4,
+            MobileId::Phone(_) => 5,
+            MobileId::IpAddress(_) => 6,
+            MobileId::Unknown(x, _) => x
+        }
+    }
+
+    /// Encode the mobile ID details into a buffer.
+    pub fn encode(&self, buffer: &mut Vec<u8>) {
+        match *self {
+            MobileId::Esn(ref digits) |
+            MobileId::ImeiEid(ref digits) |
+            MobileId::Imsi(ref digits) |
+            MobileId::Phone(ref digits) => {
+                bcd::encode(digits, buffer)
+            },
+            MobileId::IpAddress(ref ip) => {
+                encode_ip(ip, buffer)
+            },
+            MobileId::Unknown(_, ref bytes) |
+            MobileId::User(ref bytes) => {
+                buffer.extend_from_slice(bytes)
+            }
+        }
+    }
+}
+
 impl fmt::Debug for MobileId {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -137,6 +324,9 @@ impl fmt::Debug for MobileId {
             MobileId::Phone(ref phone) => {
                 write!(formatter, "MobileId::Phone({})", phone)
             },
+            MobileId::Unknown(x, ref bytes) => {
+                write!(formatter, "MobileId::Unknown({}, {:?})", x, bytes)
+            },
             MobileId::User(ref user) => {
                 write!(formatter, "MobileId::User({:?})", user)
             }
@@ -162,6 +352,9 @@ impl fmt::Display for MobileId {
             MobileId::Phone(ref phone) => {
                 write!(formatter, "{}", phone)
             },
+            MobileId::Unknown(_, ref bytes) => {
+                write!(formatter, "{:?}", bytes)
+            },
             MobileId::User(ref user) => {
                 write!(formatter, "{:?}", user)
             }
@@ -169,49 +362,189 @@ impl fmt::Display for MobileId {
     }
 }
 
+impl FromStr for MobileId {
+    type Err = String;
+
+    /// Parse a mobile ID written as `type:value`, where the type is one of `esn`, `imei`, `imsi`,
+    /// `phone`, `ip`, or `user` with a hex value.
+    fn from_str(value: &str) -> Result<MobileId, String> {
+        let (kind, id) = match value.find(':') {
+            Some(position) => (&value[..position], value[position + 1..].trim()),
+            None => return Err(format!("mobile id '{}' is not written as type:value", value))
+        };
+
+        let digits = || {
+            if !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()) {
+                Ok(id.to_string())
+            } else {
+                Err(format!("invalid {} mobile id '{}'", kind, id))
+            }
+        };
+
+        match kind.trim() {
+            "esn" => Ok(MobileId::Esn(digits()?)),
+            "imei" => Ok(MobileId::ImeiEid(digits()?)),
+            "imsi" => Ok(MobileId::Imsi(digits()?)),
+            "phone" => Ok(MobileId::Phone(digits()?)),
+            "ip" => {
+                id.parse::<Ipv4Addr>()
+                  .map(|ip| MobileId::IpAddress(ip.to_string()))
+                  .map_err(|_| format!("invalid ip mobile id '{}'", id))
+            },
+            "user" => {
+                decode_hex(id).map(MobileId::User)
+                              .ok_or_else(|| format!("invalid user mobile id '{}'", id))
+            },
+            kind => Err(format!("unknown mobile id type '{}'", kind))
+        }
+    }
+}
+
+/// Mobile ID fields as received.
 #[derive(Clone,Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+struct MobileIdBytes {
+    /// Mobile ID details, when supplied.
+    details: Option<Vec<u8>>,
+
+    /// Mobile ID type details, when supplied.
+    type_details: Option<Vec<u8>>
+}
+
+/// Options extension, a bitmap of extension bits each followed by its field.
+///
+/// Only bits 0 through 2 are documented. Fields for later bits are assumed to be length prefixed
+/// like the documented ones: a length byte followed by that many bytes. A unit sending a field of
+/// another layout would have the rest of its packet misread.
+#[derive(Clone,Debug,Default)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct OptionExtension {
-    /// Encryption service.
+    /// Length of the extension bitmap as received, kept so that padded bitmaps round-trip.
+    #[cfg_attr(feature = "serde", serde(default))]
+    bitmap_length: usize,
+
+    /// Encryption service field as received, kept until the encryption service is set when its
+    /// length or encryption type would not encode back the same.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    encryption_bytes: Option<Vec<u8>>,
+
+    /// Encryption service random key.
     encryption_service: Option<[u8;4]>,
 
+    /// Encryption type sub-field, supplied with the encryption service.
+    encryption_type: Option<EncryptionType>,
+
     /// Electronic serial number.
     esn: Option<String>,
 
+    /// ESN as received, kept until the ESN is set when its digits would not encode back the same.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    esn_bytes: Option<Vec<u8>>,
+
+    /// Extension fields for bits past the known ones, as (bit, bytes) pairs in bit order.
+    #[cfg_attr(feature = "serde", serde(default))]
+    unknown_fields: Vec<(u16, Vec<u8>)>,
+
     /// Vehicle identification number.
-    vin: Option<String>
+    vin: Option<String>,
+
+    /// VIN as received, kept until the VIN is set when it is not UTF-8.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    vin_bytes: Option<Vec<u8>>
 }
 
 impl OptionExtension {
-    /// Retrieve the encryption service.
+    /// Create a new OptionExtension with no extensions supplied.
+    pub fn new() -> OptionExtension {
+        OptionExtension::default()
+    }
+
+    /// Retrieve the encryption service random key.
     pub fn encryption_service(&self) -> &Option<[u8;4]> {
         &self.encryption_service
     }
 
+    /// Retrieve the encryption type, supplied with the encryption service.
+    pub fn encryption_type(&self) -> Option<EncryptionType> {
+        self.encryption_type
+    }
+
     /// Retrieve the ESN.
     pub fn esn(&self) -> &Option<String> {
        &self.esn
     }
 
+    /// Retrieve the extension fields for bits past the known ones, as (bit, bytes) pairs in bit
+    /// order. The bytes exclude the length prefix each field is assumed to carry.
+    pub fn unknown_fields(&self) -> &[(u16, Vec<u8>)] {
+        &self.unknown_fields
+    }
+
     /// Retrieve the VIN.
     pub fn vin(&self) -> &Option<String> {
         &self.vin
     }
+
+    /// Set the encryption service type and random key.
+    pub fn set_encryption_service(&mut self, encryption: Option<(EncryptionType, [u8;4])>) {
+        self.encryption_bytes   = None;
+        self.encryption_type    = encryption.map(|(encryption_type, _)| encryption_type);
+        self.encryption_service = encryption.map(|(_, random_key)| random_key);
+    }
+
+    /// Set the ESN.
+    pub fn set_esn(&mut self, esn: Option<String>) {
+        self.esn       = esn;
+        self.esn_bytes = None;
+    }
+
+    /// Set the extension fields for bits past the known ones, as (bit, bytes) pairs. Fields for
+    /// the known bits 0 through 2, or for bits past a 255 byte bitmap, are dropped, and the rest
+    /// are sorted by bit, keeping the first field of a repeated bit.
+    pub fn set_unknown_fields(&mut self, mut unknown_fields: Vec<(u16, Vec<u8>)>) {
+        unknown_fields.retain(|&(bit, _)| bit > 2 && bit < 255 * 8);
+        unknown_fields.sort_by_key(|&(bit, _)| bit);
+        unknown_fields.dedup_by_key(|&mut (bit, _)| bit);
+
+        self.unknown_fields = unknown_fields;
+    }
+
+    /// Set the VIN.
+    pub fn set_vin(&mut self, vin: Option<String>) {
+        self.vin       = vin;
+        self.vin_bytes = None;
+    }
 }
 
-#[derive(Clone)]
+#[derive(Clone,Debug,Default)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct OptionsHeader {
     /// Authentication details.
     authentication: Option<Vec<u8>>,
 
+    /// Indicates the options header was received without any options, and is written back so.
+    #[cfg_attr(feature = "serde", serde(default))]
+    empty: bool,
+
     /// Options extension.
     extension: Option<OptionExtension>,
 
     /// Forwarding IP address and port.
     forwarding: Option<(String, u16, ForwardingProtocol, ForwardingOperationType)>,
 
+    /// Forwarding details as received, kept until the forwarding is set when their length is not
+    /// 8 bytes.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    forwarding_bytes: Option<Vec<u8>>,
+
     /// Mobile ID.
     mobile_id: Option<MobileId>,
 
+    /// Mobile ID details and type details as received, each when supplied, kept until the mobile
+    /// ID is set when they would not encode back the same.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    mobile_id_bytes: Option<MobileIdBytes>,
+
     /// Redirection IP address and port.
     redirection: Option<(String, u16)>,
 
@@ -220,85 +553,144 @@ pub struct OptionsHeader {
 }
 
 impl OptionsHeader {
+    /// Create a new OptionsHeader with no options supplied.
+    pub fn new() -> OptionsHeader {
+        OptionsHeader::default()
+    }
+
     /// Parse options header data from a slice.
     ///
     /// Returns the OptionsHeader and parsed byte count.
     pub fn parse(slice: &[u8]) -> Result<(OptionsHeader, usize), CalAmpError> {
+        OptionsHeader::parse_traced(slice, &mut Trace::disabled())
+    }
+
+    /// Parse options header data from a slice, recording field spans into `trace`.
+    ///
+    /// Returns the OptionsHeader and parsed byte count.
+    pub fn parse_traced(slice: &[u8], trace: &mut Trace)
+    -> Result<(OptionsHeader, usize), CalAmpError> {
+        OptionsHeader::parse_inner(slice, &ParseOptions::strict(), &mut Vec::new(), trace)
+    }
+
+    /// Parse options header data from a slice. In lenient mode, anomalies that would fail a
+    /// strict parse are appended to `warnings` instead.
+    ///
+    /// Returns the OptionsHeader and parsed byte count.
+    pub fn parse_with_options(slice: &[u8], options: &ParseOptions,
+                              warnings: &mut Vec<ParseWarning>)
+    -> Result<(OptionsHeader, usize), CalAmpError> {
+        OptionsHeader::parse_inner(slice, options, warnings, &mut Trace::disabled())
+    }
+
+    /// Parse options header data from a slice.
+    pub(crate) fn parse_inner(slice: &[u8], parse_options: &ParseOptions,
+                              warnings: &mut Vec<ParseWarning>, trace: &mut Trace)
+    -> Result<(OptionsHeader, usize), CalAmpError> {
         // slice index
         let mut index = 0;
 
         // option bits
         let bits = read_u8!(slice, index);
 
-        let mut options = OptionsHeader{
-            authentication: None,
-            extension: None,
-            forwarding: None,
-            mobile_id: None,
-            redirection: None,
-            routing: None
-        };
+        let mut options = OptionsHeader::new();
 
         if bits >> 7 == 0 {
-            // options header is not present
-            return Ok((options, index));
+            // options header is not present, and the byte belongs to the message header
+            return Ok((options, 0));
         }
 
+        trace.field("Option bits", 0, 1, || describe_bits(bits, &OPTION_BITS));
+
+        options.empty = bits == 0x80;
+
+        let mut id_bytes   = None;
+        let mut type_bytes = None;
+
         // bit 0: indicates a mobile id has been supplied
         if bits & 1 == 1 {
             // byte 1:          length of mobile id details
             // bytes 2..length: mobile id details
-            let mut length = read_u8!(slice, index) as usize;
-
-            if length > 0 {
-                let id_bytes = read_vector!(slice, index, length);
-
-                // bit 1: mobile id type
-                if (bits >> 1) & 1 == 1 {
-                    // byte 1:          length of mobile id type details
-                    // bytes 2..length: mobile id type details
-                    length = read_u8!(slice, index) as usize;
-
-                    options.mobile_id = match read_u8!(slice, index) {
-                        1 => {
-                            // mobile id is an ESN
-                            let mut esn = String::with_capacity(length * 2);
-
-                            for n in id_bytes {
-                                esn.push((0x30 + (n >> 4)) as char);
-                                esn.push((0x30 + (n & 0xF)) as char);
-                            }
-
-                            Some(MobileId::Esn(esn))
-                        },
-                        2 => {
-                            // mobile id is an IMEI or EID
-                            None
-                        },
-                        3 => {
-                            // mobile id is an IMSI
-                            None
-                        },
-                        4 => {
-                            // mobile id is user defined
-                            None
-                        },
-                        5 => {
-                            // mobile id is a phone number
-                            None
-                        },
-                        6 => {
-                            // mobile id is an ip address
-                            None
-                        },
-                        _ => {
-                            // mobile id is empty
-                            None
-                        }
-                    }
+            let length = trace_field!(trace, "Mobile id length", index,
+                                      read_u8!(slice, index) as usize, "{}");
+
+            id_bytes = Some(read_vector!(slice, index, length));
+        }
+
+        let id_end = index;
+
+        // bit 1: indicates a mobile id type has been supplied
+        if (bits >> 1) & 1 == 1 {
+            // byte 1:          length of mobile id type details
+            // bytes 2..length: mobile id type details
+            let length = trace_field!(trace, "Mobile id type length", index,
+                                      read_u8!(slice, index) as usize, "{}");
+
+            let bytes = read_vector!(slice, index, length);
+
+            trace.field("Mobile id type", index - length, index, || {
+                bytes.iter().map(|b| b.to_string()).collect::<Vec<_>>().join(", ")
+            });
+
+            type_bytes = Some(bytes);
+        }
+
+        if let Some(ref id_bytes) = id_bytes {
+            // the first type byte is the type, and a mobile id without one has type 0
+            let id_type = type_bytes.as_ref().and_then(|bytes| bytes.first()).cloned();
+
+            let mobile_id = match id_type.unwrap_or(0) {
+                1 => {
+                    // mobile id is an ESN
+                    MobileId::Esn(bcd::decode(id_bytes))
+                },
+                2 => {
+                    // mobile id is an IMEI or EID
+                    MobileId::ImeiEid(bcd::decode(id_bytes))
+                },
+                3 => {
+                    // mobile id is an IMSI
+                    MobileId::Imsi(bcd::decode(id_bytes))
+                },
+                4 => {
+                    // mobile id is user defined
+                    MobileId::User(id_bytes.clone())
+                },
+                5 => {
+                    // mobile id is a phone number
+                    MobileId::Phone(bcd::decode(id_bytes))
+                },
+                6 if id_bytes.len() == 4 => {
+                    // mobile id is an ip address
+                    MobileId::IpAddress(format!("{}.{}.{}.{}", id_bytes[0], id_bytes[1],
+                                                               id_bytes[2], id_bytes[3]))
+                },
+                x => {
+                    // mobile id type is unknown, so the details are kept as is
+                    MobileId::Unknown(x, id_bytes.clone())
                 }
-            } else {
-                options.mobile_id = None;
+            };
+
+            trace.field("Mobile id", id_end - id_bytes.len(), id_end, || {
+                format!("{:?}", mobile_id)
+            });
+
+            options.mobile_id = Some(mobile_id);
+        }
+
+        if id_bytes.is_some() || type_bytes.is_some() {
+            let mut encoded = Vec::new();
+
+            if let Some(ref mobile_id) = options.mobile_id {
+                mobile_id.encode(&mut encoded);
+            }
+
+            if id_bytes.as_ref() != Some(&encoded) ||
+               type_bytes.as_ref().map(Vec::len) != Some(1) {
+                options.mobile_id_bytes = Some(MobileIdBytes{
+                    details: id_bytes,
+                    type_details: type_bytes
+                });
             }
         }
 
@@ -306,148 +698,201 @@ impl OptionsHeader {
         if (bits >> 2) & 1 == 1 {
             // byte 1:          length of authentication details
             // bytes 2..length: authentication details
-            let length = read_u8!(slice, index) as usize;
+            let length = trace_field!(trace, "Authentication length", index,
+                                      read_u8!(slice, index) as usize, "{}");
 
-            if length > 0 {
-                options.authentication = Some(read_vector!(slice, index, length));
-            }
+            options.authentication = Some(trace_field!(trace, "Authentication", index,
+                                                       read_vector!(slice, index, length),
+                                                       "{:?}"));
         }
 
         // bit 3: indicates routing has been supplied
         if (bits >> 3) & 1 == 1 {
             // byte 1:          length of routing details
             // bytes 2..length: routing details
-            let length = read_u8!(slice, index) as usize;
+            let length = trace_field!(trace, "Routing length", index,
+                                      read_u8!(slice, index) as usize, "{}");
 
-            if length > 0 {
-                options.routing = Some(read_vector!(slice, index, length));
-            }
+            options.routing = Some(trace_field!(trace, "Routing", index,
+                                                read_vector!(slice, index, length), "{:?}"));
         }
 
         // bit 4: indicates forwarding has been supplied
         if (bits >> 4) & 1 == 1 {
             // byte 1:          length of forwarding details
             // bytes 2..length: forwarding details
-            let length = read_u8!(slice, index) as usize;
+            let length = trace_field!(trace, "Forwarding length", index,
+                                      read_u8!(slice, index) as usize, "{}");
+
+            let start = index;
+            let bytes = read_vector!(slice, index, length);
 
-            if length > 0 {
-                let ip = format!("{}.{}.{}.{}", read_u8!(slice, index),
-                                                read_u8!(slice, index),
-                                                read_u8!(slice, index),
-                                                read_u8!(slice, index));
+            if length >= 8 {
+                let mut index = start;
+
+                let ip = trace_field!(trace, "Forwarding address", index,
+                                      format!("{}.{}.{}.{}", read_u8!(slice, index),
+                                                             read_u8!(slice, index),
+                                                             read_u8!(slice, index),
+                                                             read_u8!(slice, index)), "{}");
 
                 options.forwarding = Some((ip,
                                            // port
-                                           read_u16!(slice, index),
+                                           trace_field!(trace, "Forwarding port", index,
+                                                        read_u16!(slice, index), "{}"),
 
                                            // protocol
-                                           match read_u8!(slice, index) {
-                                               17 => ForwardingProtocol::Udp,
-                                               _ => ForwardingProtocol::Tcp
-                                           },
+                                           trace_field!(trace, "Forwarding protocol", index,
+                                                        ForwardingProtocol::from_u8(
+                                                            read_u8!(slice, index)), "{}"),
 
                                            // operation type
-                                           match read_u8!(slice, index) {
-                                               0 => ForwardingOperationType::Forward,
-                                               1 => ForwardingOperationType::Proxy,
-                                               _ => ForwardingOperationType::ForwardLookup
-                                           }));
+                                           trace_field!(trace, "Forwarding operation type", index,
+                                                        ForwardingOperationType::from_u8(
+                                                            read_u8!(slice, index)), "{}")));
+            }
+
+            if length != 8 {
+                options.forwarding_bytes = Some(bytes);
             }
         }
 
         // bit 5: indicates response redirection has been supplied
         if (bits >> 5) & 1 == 1 {
-            let ip = format!("{}.{}.{}.{}", read_u8!(slice, index),
-                                            read_u8!(slice, index),
-                                            read_u8!(slice, index),
-                                            read_u8!(slice, index));
+            let ip = trace_field!(trace, "Redirection address", index,
+                                  format!("{}.{}.{}.{}", read_u8!(slice, index),
+                                                         read_u8!(slice, index),
+                                                         read_u8!(slice, index),
+                                                         read_u8!(slice, index)), "{}");
 
             options.redirection = Some((ip,
                                         // port
-                                        read_u16!(slice, index)));
+                                        trace_field!(trace, "Redirection port", index,
+                                                     read_u16!(slice, index), "{}")));
         }
 
         // bit 6: indicates options extension has been supplied
         if (bits >> 6) & 1 == 1 {
-            // byte 1:          length of options extension (always 1 byte)
-            // bytes 2..length: options extension
-            let length = read_u8!(slice, index);
+            // byte 1:          length of options extension bitmap
+            // bytes 2..length: options extension bitmap, holding bits 0-7 in the first byte, bits
+            //                  8-15 in the second byte, and so on
+            let length = trace_field!(trace, "Extension length", index,
+                                      read_u8!(slice, index) as usize, "{}");
+
+            let mut extension = OptionExtension::new();
 
-            if length > 1 {
-                return Err(CalAmpError::OptionExtensionBitLength(length));
+            let bitmap = read_vector!(slice, index, length);
+            let is_set = |bit: usize| {
+                bitmap.get(bit / 8).is_some_and(|byte| (byte >> (bit % 8)) & 1 == 1)
             };
 
-            let mut extension = OptionExtension{ encryption_service: None,
-                                                 esn: None,
-                                                 vin: None};
+            trace.field("Extension bits", index - length, index, || describe_bitmap(&bitmap));
 
-            let extension_bits = read_u8!(slice, index);
+            extension.bitmap_length = length;
 
-            if (extension_bits & 1) == 1 {
+            if is_set(0) {
                 // extension bit 0: indicates ESN has been supplied
                 // byte 1:          length of ESN
                 // bytes 2..length: ESN
-                let length  = read_u8!(slice, index) as usize;
-                let mut esn = String::with_capacity(length * 2);
+                let length = trace_field!(trace, "ESN length", index,
+                                          read_u8!(slice, index) as usize, "{}");
 
-                for n in read_vector!(slice, index, length) {
-                    esn.push((0x30 + (n >> 4)) as char);
-                    esn.push((0x30 + (n & 0xF)) as char);
-                }
+                let (esn, bytes) = bcd::decode_exact(&read_vector!(slice, index, length));
+
+                trace.field("ESN", index - length, index, || esn.clone());
 
-                extension.esn = Some(esn)
+                extension.esn       = Some(esn);
+                extension.esn_bytes = bytes;
             }
 
-            if ((extension_bits >> 1) & 1) == 1 {
+            if is_set(1) {
                 // extension bit 1: indicates VIN has been supplied
                 // byte 1:          length of VIN
                 // bytes 2..length: VIN
-                let length  = read_u8!(slice, index) as usize;
+                let length  = trace_field!(trace, "VIN length", index,
+                                           read_u8!(slice, index) as usize, "{}");
 
                 if length != 17 {
-                    return Err(CalAmpError::VinLength);
+                    if parse_options.strict {
+                        return Err(CalAmpError::VinLength);
+                    }
+
+                    warnings.push(ParseWarning::VinLength(length));
                 }
 
-                let mut vin = String::with_capacity(length);
+                let bytes = read_vector!(slice, index, length);
+                let vin   = String::from_utf8_lossy(&bytes).into_owned();
 
-                unsafe {
-                    read_into_vector!(slice, index, length, vin.as_mut_vec());
+                trace.field("VIN", index - length, index, || vin.clone());
+
+                if vin.as_bytes() != &bytes[..] {
+                    extension.vin_bytes = Some(bytes);
                 }
 
                 extension.vin = Some(vin);
             }
 
-            if ((extension_bits >> 2) & 1) == 1 {
+            if is_set(2) {
                 // extension bit 2: indicates encryption service has been supplied
                 // byte 1:          length of encryption service
                 // byte 2:          encryption type sub-field
                 // bytes 3..length: encryption service details
-                let length          = read_u8!(slice, index);
-                let encryption_type = read_u8!(slice, index);
-
-                match read_u8!(slice, index) {
-                    0 => {
-                        // no encryption
-                    },
-                    1 => {
-                        // encryption is based on LMU/TTU ESN
-                    },
-                    2 => {
-                        // encryption is based on IMEI or MEID
-                    },
-                    3 => {
-                        // encryption is based on mobile id
-                    },
-                    x @ _ => {
-                        return Err(CalAmpError::EncryptionType(x));
-                    }
+                let length = trace_field!(trace, "Encryption length", index,
+                                          read_u8!(slice, index) as usize, "{}");
+
+                let start = index;
+                let bytes = read_vector!(slice, index, length);
+                let mut encryption_type = None;
+
+                if let Some(&value) = bytes.first() {
+                    encryption_type = match EncryptionType::from_u8(value) {
+                        Ok(encryption_type) => Some(encryption_type),
+                        Err(error) if parse_options.strict => return Err(error),
+                        Err(_) => {
+                            // the message body is kept encrypted, as the algorithm is unknown
+                            warnings.push(ParseWarning::EncryptionType(value));
+
+                            None
+                        }
+                    };
+
+                    trace.field("Encryption type", start, start + 1, || {
+                        match encryption_type {
+                            Some(encryption_type) => format!("{}", encryption_type),
+                            None => format!("unknown {}", value)
+                        }
+                    });
                 }
 
-                let mut random_key = [0; 4];
+                if length >= 5 {
+                    let mut random_key = [0; 4];
+
+                    random_key.copy_from_slice(&bytes[1..5]);
+
+                    trace.field("Encryption random key", start + 1, start + 5, || {
+                        format!("{:?}", random_key)
+                    });
+
+                    extension.encryption_service = Some(random_key);
+                    extension.encryption_type    = encryption_type;
+                }
+
+                if length != 5 || encryption_type.is_none() {
+                    extension.encryption_bytes = Some(bytes);
+                }
+            }
+
+            // unknown extension bits: fields are kept as is, assuming they are length prefixed
+            //                         like the known fields
+            for bit in (3..length * 8).filter(|&bit| is_set(bit)) {
+                let length = read_u8!(slice, index) as usize;
+                let data   = read_vector!(slice, index, length);
 
-                read_into_array!(slice, index, random_key);
+                trace.field("Unknown extension field", index - length - 1, index, || {
+                    format!("bit {}, {} bytes", bit, length)
+                });
 
-                extension.encryption_service = Some(random_key);
+                extension.unknown_fields.push((bit as u16, data));
             }
 
             options.extension = Some(extension);
@@ -456,6 +901,160 @@ impl OptionsHeader {
         Ok((options, index))
     }
 
+    /// Encode options header data into a buffer.
+    ///
+    /// Nothing is written when no options are supplied, unless the options header was received
+    /// without any.
+    pub fn encode(&self, buffer: &mut Vec<u8>) {
+        let mut bits = 0;
+
+        match self.mobile_id_bytes {
+            Some(ref bytes) => {
+                bits |= bytes.details.is_some() as u8 | (bytes.type_details.is_some() as u8) << 1;
+            },
+            None if self.mobile_id.is_some() => {
+                bits |= 0b11;
+            },
+            None => {}
+        }
+
+        if self.authentication.is_some() {
+            bits |= 1 << 2;
+        }
+
+        if self.routing.is_some() {
+            bits |= 1 << 3;
+        }
+
+        if self.forwarding.is_some() || self.forwarding_bytes.is_some() {
+            bits |= 1 << 4;
+        }
+
+        if self.redirection.is_some() {
+            bits |= 1 << 5;
+        }
+
+        if self.extension.is_some() {
+            bits |= 1 << 6;
+        }
+
+        if bits == 0 && !self.empty {
+            return;
+        }
+
+        buffer.push(0x80 | bits);
+
+        if let Some(ref mobile_id_bytes) = self.mobile_id_bytes {
+            for bytes in mobile_id_bytes.details.iter().chain(&mobile_id_bytes.type_details) {
+                buffer.push(bytes.len() as u8);
+                buffer.extend_from_slice(bytes);
+            }
+        } else if let Some(ref mobile_id) = self.mobile_id {
+            let mut id_bytes = Vec::new();
+
+            mobile_id.encode(&mut id_bytes);
+
+            buffer.push(id_bytes.len() as u8);
+            buffer.extend_from_slice(&id_bytes);
+            buffer.push(1);
+            buffer.push(mobile_id.type_value());
+        }
+
+        if let Some(ref authentication) = self.authentication {
+            buffer.push(authentication.len() as u8);
+            buffer.extend_from_slice(authentication);
+        }
+
+        if let Some(ref routing) = self.routing {
+            buffer.push(routing.len() as u8);
+            buffer.extend_from_slice(routing);
+        }
+
+        if let Some(ref forwarding) = self.forwarding_bytes {
+            buffer.push(forwarding.len() as u8);
+            buffer.extend_from_slice(forwarding);
+        } else if let Some((ref ip, port, ref protocol, ref operation)) = self.forwarding {
+            buffer.push(8);
+            encode_ip(ip, buffer);
+            buffer.push((port >> 8) as u8);
+            buffer.push(port as u8);
+            buffer.push(protocol.as_u8());
+            buffer.push(operation.as_u8());
+        }
+
+        if let Some((ref ip, port)) = self.redirection {
+            encode_ip(ip, buffer);
+            buffer.push((port >> 8) as u8);
+            buffer.push(port as u8);
+        }
+
+        if let Some(ref extension) = self.extension {
+            // the bitmap covers the highest set bit, and is padded to its received length
+            let encryption = extension.encryption_service.is_some() ||
+                             extension.encryption_bytes.is_some();
+            let known      = extension.esn.is_some() || extension.vin.is_some() || encryption;
+            let needed = match extension.unknown_fields.last() {
+                Some(&(bit, _)) => bit as usize / 8 + 1,
+                None => known as usize
+            };
+
+            let mut bitmap = vec![0; extension.bitmap_length.max(needed)];
+
+            if extension.esn.is_some() {
+                bitmap[0] |= 1;
+            }
+
+            if extension.vin.is_some() {
+                bitmap[0] |= 1 << 1;
+            }
+
+            if encryption {
+                bitmap[0] |= 1 << 2;
+            }
+
+            for &(bit, _) in &extension.unknown_fields {
+                bitmap[bit as usize / 8] |= 1 << (bit % 8);
+            }
+
+            buffer.push(bitmap.len() as u8);
+            buffer.extend_from_slice(&bitmap);
+
+            if let Some(ref esn) = extension.esn {
+                let mut esn_bytes = Vec::new();
+
+                match extension.esn_bytes {
+                    Some(ref bytes) => esn_bytes.extend_from_slice(bytes),
+                    None => bcd::encode(esn, &mut esn_bytes)
+                }
+
+                buffer.push(esn_bytes.len() as u8);
+                buffer.extend_from_slice(&esn_bytes);
+            }
+
+            if let Some(ref vin) = extension.vin {
+                let vin = extension.vin_bytes.as_ref().map_or(vin.as_bytes(), |bytes| &bytes[..]);
+
+                buffer.push(vin.len() as u8);
+                buffer.extend_from_slice(vin);
+            }
+
+            if let Some(ref encryption) = extension.encryption_bytes {
+                buffer.push(encryption.len() as u8);
+                buffer.extend_from_slice(encryption);
+            } else if let Some(ref random_key) = extension.encryption_service {
+                buffer.push(5);
+                buffer.push(extension.encryption_type.unwrap_or(EncryptionType::Unencrypted)
+                                                     .as_u8());
+                buffer.extend_from_slice(random_key);
+            }
+
+            for (_, data) in &extension.unknown_fields {
+                buffer.push(data.len() as u8);
+                buffer.extend_from_slice(data);
+            }
+        }
+    }
+
     /// Retrieve the authentication details.
     pub fn authentication(&self) -> &Option<Vec<u8>> {
         &self.authentication
@@ -471,6 +1070,17 @@ impl OptionsHeader {
         &self.forwarding
     }
 
+    /// Indicates an encryption service other than `EncryptionType::Unencrypted` is supplied.
+    pub fn is_encrypted(&self) -> bool {
+        match self.extension {
+            Some(ref extension) => {
+                extension.encryption_service.is_some()
+                && extension.encryption_type != Some(EncryptionType::Unencrypted)
+            },
+            None => false
+        }
+    }
+
     /// Retrieve the mobile ID.
     pub fn mobile_id(&self) -> &Option<MobileId> {
         &self.mobile_id
@@ -485,4 +1095,72 @@ impl OptionsHeader {
     pub fn routing(&self) -> &Option<Vec<u8>> {
         &self.routing
     }
+
+    /// Set the authentication details.
+    pub fn set_authentication(&mut self, authentication: Option<Vec<u8>>) {
+        self.authentication = authentication;
+    }
+
+    /// Set the extension details.
+    pub fn set_extension(&mut self, extension: Option<OptionExtension>) {
+        self.extension = extension;
+    }
+
+    /// Set the forwarding details.
+    pub fn set_forwarding(&mut self,
+                          forwarding: Option<(String, u16, ForwardingProtocol,
+                                              ForwardingOperationType)>) {
+        self.forwarding       = forwarding;
+        self.forwarding_bytes = None;
+    }
+
+    /// Set the mobile ID.
+    pub fn set_mobile_id(&mut self, mobile_id: Option<MobileId>) {
+        self.mobile_id       = mobile_id;
+        self.mobile_id_bytes = None;
+    }
+
+    /// Set the redirection details.
+    pub fn set_redirection(&mut self, redirection: Option<(String, u16)>) {
+        self.redirection = redirection;
+    }
+
+    /// Set the routing details.
+    pub fn set_routing(&mut self, routing: Option<Vec<u8>>) {
+        self.routing = routing;
+    }
+}
+
+/// Encode a dotted IPv4 address as 4 bytes. Unparsable addresses are encoded as 0.0.0.0.
+fn encode_ip(ip: &str, buffer: &mut Vec<u8>) {
+    buffer.extend_from_slice(&ip.parse::<Ipv4Addr>().unwrap_or(Ipv4Addr::new(0, 0, 0, 0)).octets());
+}
+
+/// Option bit names, indexed by bit.
+const OPTION_BITS: [&str; 8] = ["mobile id", "mobile id type", "authentication", "routing",
+                                "forwarding", "redirection", "extension", "options header"];
+
+/// Known option extension bit names, indexed by bit.
+const EXTENSION_BITS: [&str; 3] = ["esn", "vin", "encryption"];
+
+/// Describe a bit field as its hex value followed by the names of its set bits.
+fn describe_bits(bits: u8, names: &[&str; 8]) -> String {
+    let set: Vec<&str> = (0..8).filter(|n| (bits >> n) & 1 == 1)
+                               .map(|n| names[n])
+                               .collect();
+
+    format!("0x{:02x} ({})", bits, set.join(", "))
+}
+
+/// Describe a bitmap of any length as its hex bytes followed by the names of its set bits.
+fn describe_bitmap(bitmap: &[u8]) -> String {
+    let hex: Vec<String> = bitmap.iter().map(|b| format!("{:02x}", b)).collect();
+    let set: Vec<String> = (0..bitmap.len() * 8).filter(|n| (bitmap[n / 8] >> (n % 8)) & 1 == 1)
+                                                 .map(|n| match EXTENSION_BITS.get(n) {
+                                                     Some(name) => name.to_string(),
+                                                     None => format!("bit {}", n)
+                                                 })
+                                                 .collect();
+
+    format!("0x{} ({})", hex.join(""), set.join(", "))
 }
@@ -0,0 +1,193 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+use {CalAmpError, ParseOptions, ParseWarning};
+use cipher::{Cipher, CipherKey};
+use dissect::Trace;
+use message::Message;
+use message_header::MessageHeader;
+use options_header::OptionsHeader;
+
+/// Complete LMU packet.
+#[derive(Clone,Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct Packet {
+    /// Message body.
+    message: Message,
+
+    /// Message header.
+    message_header: MessageHeader,
+
+    /// Options header.
+    options_header: OptionsHeader,
+
+    /// Anomalies recorded by a lenient parse.
+    #[cfg_attr(feature = "serde", serde(default))]
+    warnings: Vec<ParseWarning>
+}
+
+impl Packet {
+    /// Create a new Packet.
+    pub fn new(options_header: OptionsHeader, message_header: MessageHeader, message: Message)
+    -> Packet {
+        Packet{
+            message,
+            message_header,
+            options_header,
+            warnings: Vec::new()
+        }
+    }
+
+    /// Parse packet data from a slice.
+    ///
+    /// Returns the Packet and parsed byte count.
+    pub fn parse(slice: &[u8]) -> Result<(Packet, usize), CalAmpError> {
+        Packet::parse_traced(slice, &mut Trace::disabled())
+    }
+
+    /// Parse packet data from a slice, recording field spans into `trace`.
+    ///
+    /// Returns the Packet and parsed byte count.
+    pub fn parse_traced(slice: &[u8], trace: &mut Trace) -> Result<(Packet, usize), CalAmpError> {
+        Packet::parse_inner(slice, None, &ParseOptions::strict(), trace)
+    }
+
+    /// Parse packet data from a slice. In lenient mode, anomalies that would fail a strict parse
+    /// are recorded as warnings on the packet instead.
+    ///
+    /// Returns the Packet and parsed byte count.
+    pub fn parse_with_options(slice: &[u8], options: &ParseOptions)
+    -> Result<(Packet, usize), CalAmpError> {
+        Packet::parse_inner(slice, None, options, &mut Trace::disabled())
+    }
+
+    /// Parse packet data from a slice, decrypting the message body with `cipher` when the options
+    /// header supplies an encryption service.
+    ///
+    /// Packets parsed without a cipher keep an encrypted message body as `Message::Raw`.
+    ///
+    /// Returns the Packet and parsed byte count.
+    pub fn parse_with_cipher(slice: &[u8], cipher: &dyn Cipher, options: &ParseOptions)
+    -> Result<(Packet, usize), CalAmpError> {
+        Packet::parse_inner(slice, Some(cipher), options, &mut Trace::disabled())
+    }
+
+    /// Parse packet data from a slice, decrypting the message body when a cipher is supplied.
+    fn parse_inner(slice: &[u8], cipher: Option<&dyn Cipher>, options: &ParseOptions,
+                   trace: &mut Trace) -> Result<(Packet, usize), CalAmpError> {
+        let mut warnings = Vec::new();
+
+        trace.begin("Options header", 0);
+
+        let (options_header, mut index) = OptionsHeader::parse_inner(slice, options,
+                                                                      &mut warnings, trace)?;
+
+        trace.end(index);
+        trace.begin("Message header", index);
+
+        let (message_header, byte_count) = MessageHeader::parse_traced(&slice[index..], trace)?;
+
+        trace.end(byte_count);
+
+        index += byte_count;
+
+        trace.begin("Message", index);
+
+        let (message, byte_count) = match cipher {
+            Some(cipher) => {
+                let mut body = slice[index..].to_vec();
+
+                if let Some(key) = CipherKey::from_options_header(&options_header)? {
+                    cipher.decrypt(&key, &mut body);
+                }
+
+                Message::parse_traced(message_header.message_type(), &body, trace)?
+            },
+            None if options_header.is_encrypted() => {
+                trace.field("Encrypted message data", 0, slice.len() - index, || {
+                    format!("{} bytes", slice.len() - index)
+                });
+
+                (Message::Raw(slice[index..].to_vec()), slice.len() - index)
+            },
+            None => {
+                Message::parse_traced(message_header.message_type(), &slice[index..], trace)?
+            }
+        };
+
+        trace.end(byte_count);
+
+        index += byte_count;
+
+        Ok((Packet{
+            message,
+            message_header,
+            options_header,
+            warnings
+        }, index))
+    }
+
+    /// Encode packet data into a buffer.
+    pub fn encode(&self, buffer: &mut Vec<u8>) {
+        self.options_header.encode(buffer);
+        self.message_header.encode(buffer);
+        self.message.encode(buffer);
+    }
+
+    /// Encode packet data into a buffer, encrypting the message body with `cipher` when the
+    /// options header supplies an encryption service.
+    ///
+    /// Nothing is written when the identifier selected by the encryption type is not present.
+    pub fn encode_with_cipher(&self, cipher: &dyn Cipher, buffer: &mut Vec<u8>)
+    -> Result<(), CalAmpError> {
+        let key = CipherKey::from_options_header(&self.options_header)?;
+
+        self.options_header.encode(buffer);
+        self.message_header.encode(buffer);
+
+        let start = buffer.len();
+
+        self.message.encode(buffer);
+
+        if let Some(key) = key {
+            cipher.encrypt(&key, &mut buffer[start..]);
+        }
+
+        Ok(())
+    }
+
+    /// Retrieve the message body.
+    pub fn message(&self) -> &Message {
+        &self.message
+    }
+
+    /// Retrieve the message header.
+    pub fn message_header(&self) -> &MessageHeader {
+        &self.message_header
+    }
+
+    /// Retrieve the options header.
+    pub fn options_header(&self) -> &OptionsHeader {
+        &self.options_header
+    }
+
+    /// Retrieve the anomalies recorded by a lenient parse.
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+}
@@ -0,0 +1,565 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+use CalAmpError;
+use options_header::{ForwardingOperationType, ForwardingProtocol, MobileId, OptionsHeader};
+use packet::Packet;
+use server::{MAX_DATAGRAM_SIZE, reply_address};
+
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpStream, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Default TCP connect and write timeout.
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Maximum number of bytes queued for a TCP connection being established.
+const MAX_QUEUED: usize = 65536;
+
+/// Number of worker threads performing TCP connects and sends.
+const WORKERS: usize = 4;
+
+/// Maximum number of TCP connects and sends waiting for a worker thread.
+const WORKER_QUEUE: usize = 256;
+
+/// Blocking TCP work run on a worker thread.
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Handling of the forwarding option in forwarded packets.
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum Rewrite {
+    /// Replace the forwarding option with a redirection to the originating unit, so the
+    /// destination answers the unit directly. Proxied packets are always stripped, since their
+    /// responses return through the relay.
+    Redirect,
+
+    /// Remove the forwarding option.
+    Strip
+}
+
+/// Upstream connection carrying proxied traffic for a unit.
+enum Connection {
+    /// TCP connection being established, with data to send once it is.
+    Connecting(Receiver<io::Result<TcpStream>>, Vec<u8>),
+
+    /// TCP stream, with data received but not yet relayed.
+    Tcp(TcpStream, Vec<u8>),
+
+    /// Connected UDP socket.
+    Udp(UdpSocket)
+}
+
+/// Fixed pool of worker threads.
+struct Workers {
+    /// Job queue shared by the worker threads.
+    sender: SyncSender<Job>
+}
+
+impl Workers {
+    /// Start `count` worker threads taking jobs from a queue holding at most `queue` jobs. The
+    /// threads exit once the pool is dropped.
+    fn new(count: usize, queue: usize) -> Workers {
+        let (sender, receiver) = mpsc::sync_channel::<Job>(queue);
+        let receiver           = Arc::new(Mutex::new(receiver));
+
+        for _ in 0..count {
+            let receiver = Arc::clone(&receiver);
+
+            thread::spawn(move || loop {
+                let job = match receiver.lock() {
+                    Ok(receiver) => receiver.recv(),
+                    Err(_) => return
+                };
+
+                match job {
+                    Ok(job) => job(),
+                    Err(_) => return
+                }
+            });
+        }
+
+        Workers{
+            sender
+        }
+    }
+
+    /// Queue `job`, failing with `io::ErrorKind::WouldBlock` when the queue is full.
+    fn run(&self, job: Job) -> io::Result<()> {
+        self.sender.try_send(job).map_err(|_| {
+            io::Error::new(io::ErrorKind::WouldBlock, "relay workers are busy")
+        })
+    }
+}
+
+/// Proxied unit and its upstream connection.
+struct Upstream {
+    /// Upstream connection.
+    connection: Connection,
+
+    /// Time of the last forwarded or relayed message.
+    last_used: Instant,
+
+    /// Address responses are relayed to.
+    unit: SocketAddr
+}
+
+/// Relay performing the forwarding requested by the options header.
+///
+/// - `ForwardingOperationType::Forward` sends the packet to the forwarding address, and responses
+///   are left to the destination.
+/// - `ForwardingOperationType::Proxy` sends the packet over an upstream connection kept per unit
+///   and destination, and `poll()` relays responses arriving on it back to the unit.
+/// - `ForwardingOperationType::ForwardLookup` forwards like `Forward`, to the address looked up by
+///   mobile ID when one has been inserted, or else to the forwarding address.
+///
+/// Unknown operation types are handled like `ForwardLookup`, and unknown protocols like TCP.
+///
+/// Responses, and the redirection written by `Rewrite::Redirect`, go to the address the packet
+/// was received from. The redirection option of the packet is only followed for packets that were
+/// authenticated, like acknowledgements sent by `Server`.
+///
+/// Forwarding addresses are named by the packets themselves, so they are only used when the policy
+/// set with `set_policy()` allows them. Without a policy nothing is forwarded to them, and only
+/// lookup destinations are used.
+///
+/// TCP connections are established, and forwarded TCP packets sent, by a small pool of worker
+/// threads so that forwarding never waits for them. Forwarding fails with
+/// `io::ErrorKind::WouldBlock` when the workers are too far behind, or when too much data is queued
+/// for a TCP connection still being established. Upstream connections idle for longer than the
+/// idle timeout are closed by `poll()`.
+pub struct Relay {
+    /// TCP connect and write timeout.
+    connect_timeout: Duration,
+
+    /// Upstream idle timeout.
+    idle_timeout: Duration,
+
+    /// Forward lookup destinations by mobile ID.
+    lookup: HashMap<MobileId, SocketAddr>,
+
+    /// Forwarding addresses allowed as destinations.
+    policy: Option<Box<dyn Fn(SocketAddr) -> bool + Send>>,
+
+    /// Forwarding option handling.
+    rewrite: Rewrite,
+
+    /// Upstream connections by unit, destination, and TCP flag.
+    upstreams: HashMap<(SocketAddr, SocketAddr, bool), Upstream>,
+
+    /// Worker pool, started on first use.
+    workers: Option<Workers>
+}
+
+impl Relay {
+    /// Create a new Relay that closes upstream connections after `idle_timeout`.
+    pub fn new(idle_timeout: Duration) -> Relay {
+        Relay{
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            idle_timeout,
+            lookup: HashMap::new(),
+            policy: None,
+            rewrite: Rewrite::Strip,
+            upstreams: HashMap::new(),
+            workers: None
+        }
+    }
+
+    /// Forward a packet received from `peer` as requested by its forwarding option.
+    ///
+    /// Only the options header of `data` is decoded and rewritten, and the rest of the packet is
+    /// forwarded as received. `trusted` indicates that the packet was authenticated, so that its
+    /// redirection option may be followed.
+    ///
+    /// Returns the destination, or `None` when the packet has no forwarding option. Forwarding
+    /// addresses not allowed by the policy give an `io::ErrorKind::PermissionDenied` error.
+    pub fn forward(&mut self, data: &[u8], peer: SocketAddr, trusted: bool, now: Instant)
+    -> io::Result<Option<SocketAddr>> {
+        let (options_header, length) = OptionsHeader::parse(data).map_err(|error| {
+            io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+        })?;
+
+        let (destination, tcp, proxy) = match *options_header.forwarding() {
+            Some((ref ip, port, protocol, operation)) => {
+                let lookup = match operation {
+                    ForwardingOperationType::ForwardLookup |
+                    ForwardingOperationType::Unknown(_) => {
+                        options_header.mobile_id()
+                                      .as_ref()
+                                      .and_then(|mobile_id| self.lookup.get(mobile_id))
+                                      .cloned()
+                    },
+                    _ => None
+                };
+
+                let destination = match lookup {
+                    Some(destination) => destination,
+                    None => {
+                        let ip = ip.parse::<Ipv4Addr>().map_err(|_| {
+                            io::Error::new(io::ErrorKind::InvalidData,
+                                           format!("invalid forwarding address {}", ip))
+                        })?;
+
+                        let destination = SocketAddr::new(IpAddr::V4(ip), port);
+
+                        if !self.policy.as_ref().is_some_and(|policy| policy(destination)) {
+                            return Err(io::Error::new(io::ErrorKind::PermissionDenied,
+                                                      format!("forwarding to {} is not allowed",
+                                                              destination)));
+                        }
+
+                        destination
+                    }
+                };
+
+                (destination, protocol != ForwardingProtocol::Udp,
+                 operation == ForwardingOperationType::Proxy)
+            },
+            None => return Ok(None)
+        };
+
+        let unit = if trusted { reply_address(&options_header, peer) } else { peer };
+
+        let mut rewritten = options_header.clone();
+
+        rewritten.set_forwarding(None);
+
+        if let (false, Rewrite::Redirect, SocketAddr::V4(unit)) = (proxy, self.rewrite, unit) {
+            rewritten.set_redirection(Some((unit.ip().to_string(), unit.port())));
+        }
+
+        let mut buffer = Vec::new();
+
+        rewritten.encode(&mut buffer);
+        buffer.extend_from_slice(&data[length..]);
+
+        let connect_timeout = self.connect_timeout;
+
+        if !proxy {
+            if tcp {
+                self.workers().run(Box::new(move || {
+                    let _ = send_tcp(destination, &buffer, connect_timeout);
+                }))?;
+            } else {
+                connect_udp(destination)?.send(&buffer)?;
+            }
+
+            return Ok(Some(destination));
+        }
+
+        let key = (unit, destination, tcp);
+
+        if !self.upstreams.contains_key(&key) {
+            let connection = if tcp {
+                connect(self.workers(), destination, connect_timeout)?
+            } else {
+                Connection::Udp(connect_udp(destination)?)
+            };
+
+            let upstream = Upstream{
+                connection,
+                last_used: now,
+                unit
+            };
+
+            self.upstreams.insert(key, upstream);
+        }
+
+        let result = match self.upstreams.get_mut(&key) {
+            Some(upstream) => {
+                upstream.last_used = now;
+
+                match upstream.connection {
+                    Connection::Connecting(_, ref mut queued) => {
+                        if queued.len() + buffer.len() > MAX_QUEUED {
+                            return Err(io::Error::new(io::ErrorKind::WouldBlock,
+                                                      format!("too much data queued for {}",
+                                                              destination)));
+                        }
+
+                        queued.extend_from_slice(&buffer);
+
+                        Ok(())
+                    },
+                    Connection::Tcp(ref mut stream, _) => write_all(stream, &buffer),
+                    Connection::Udp(ref socket) => socket.send(&buffer).map(|_| ())
+                }
+            },
+            None => Ok(())
+        };
+
+        if result.is_err() {
+            self.upstreams.remove(&key);
+        }
+
+        result.map(|_| Some(destination))
+    }
+
+    /// Relay responses received on upstream connections back to their units over `socket`, and
+    /// close upstream connections that have failed, been closed by the destination, or been idle
+    /// for longer than the idle timeout.
+    ///
+    /// TCP responses are split into packets and relayed as one datagram each. Data that does not
+    /// parse as a packet is dropped and closes the connection. UDP datagrams that do not parse as a
+    /// packet are dropped as well, and the connection stays open.
+    ///
+    /// Returns the number of responses relayed. A response that fails to send does not stop the
+    /// others from being relayed, and the first failure is returned once every upstream connection
+    /// has been polled.
+    pub fn poll(&mut self, socket: &UdpSocket, now: Instant) -> io::Result<usize> {
+        let mut buffer  = vec![0; MAX_DATAGRAM_SIZE];
+        let mut closed  = Vec::new();
+        let mut error   = None;
+        let mut relayed = 0;
+
+        for (key, upstream) in &mut self.upstreams {
+            let mut open = establish(&mut upstream.connection).is_ok();
+
+            match upstream.connection {
+                _ if !open => {},
+                Connection::Connecting(..) => {},
+                Connection::Udp(ref upstream_socket) => {
+                    loop {
+                        match upstream_socket.recv(&mut buffer) {
+                            Ok(length) => {
+                                upstream.last_used = now;
+
+                                if Packet::parse(&buffer[..length]).is_err() {
+                                    continue;
+                                }
+
+                                match socket.send_to(&buffer[..length], upstream.unit) {
+                                    Ok(_) => relayed += 1,
+                                    Err(send_error) => error = error.or(Some(send_error))
+                                }
+                            },
+                            Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                            Err(_) => {
+                                open = false;
+
+                                break;
+                            }
+                        }
+                    }
+                },
+                Connection::Tcp(ref mut stream, ref mut pending) => {
+                    loop {
+                        match stream.read(&mut buffer) {
+                            Ok(0) => {
+                                open = false;
+
+                                break;
+                            },
+                            Ok(length) => {
+                                pending.extend_from_slice(&buffer[..length]);
+
+                                upstream.last_used = now;
+                            },
+                            Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                            Err(_) => {
+                                open = false;
+
+                                break;
+                            }
+                        }
+                    }
+
+                    while !pending.is_empty() {
+                        let length = match Packet::parse(pending) {
+                            Ok((_, length)) => length,
+                            Err(CalAmpError::Eos) if open => break,
+                            Err(_) => {
+                                pending.clear();
+
+                                open = false;
+
+                                break;
+                            }
+                        };
+
+                        match socket.send_to(&pending[..length], upstream.unit) {
+                            Ok(_) => relayed += 1,
+                            Err(send_error) => error = error.or(Some(send_error))
+                        }
+
+                        pending.drain(..length);
+                    }
+                }
+            }
+
+            if !open || now.duration_since(upstream.last_used) > self.idle_timeout {
+                closed.push(*key);
+            }
+        }
+
+        for key in closed {
+            self.upstreams.remove(&key);
+        }
+
+        match error {
+            Some(error) => Err(error),
+            None => Ok(relayed)
+        }
+    }
+
+    /// Retrieve the TCP connect and write timeout.
+    pub fn connect_timeout(&self) -> Duration {
+        self.connect_timeout
+    }
+
+    /// Retrieve the upstream idle timeout.
+    pub fn idle_timeout(&self) -> Duration {
+        self.idle_timeout
+    }
+
+    /// Set the forward lookup destination for `mobile_id`.
+    pub fn insert_lookup(&mut self, mobile_id: MobileId, destination: SocketAddr) {
+        self.lookup.insert(mobile_id, destination);
+    }
+
+    /// Indicates no upstream connections are open.
+    pub fn is_empty(&self) -> bool {
+        self.upstreams.is_empty()
+    }
+
+    /// Retrieve the count of open upstream connections.
+    pub fn len(&self) -> usize {
+        self.upstreams.len()
+    }
+
+    /// Remove the forward lookup destination for `mobile_id`.
+    pub fn remove_lookup(&mut self, mobile_id: &MobileId) -> Option<SocketAddr> {
+        self.lookup.remove(mobile_id)
+    }
+
+    /// Retrieve the forwarding option handling.
+    pub fn rewrite(&self) -> Rewrite {
+        self.rewrite
+    }
+
+    /// Set the TCP connect and write timeout.
+    pub fn set_connect_timeout(&mut self, connect_timeout: Duration) {
+        self.connect_timeout = connect_timeout;
+    }
+
+    /// Set the policy deciding which forwarding addresses may be used as destinations, or `None`
+    /// to forward only to lookup destinations.
+    pub fn set_policy(&mut self, policy: Option<Box<dyn Fn(SocketAddr) -> bool + Send>>) {
+        self.policy = policy;
+    }
+
+    /// Set the forwarding option handling.
+    pub fn set_rewrite(&mut self, rewrite: Rewrite) {
+        self.rewrite = rewrite;
+    }
+
+    /// Retrieve the worker pool, starting it on first use.
+    fn workers(&mut self) -> &Workers {
+        self.workers.get_or_insert_with(|| Workers::new(WORKERS, WORKER_QUEUE))
+    }
+}
+
+/// Start establishing a TCP connection to `destination` on a worker thread.
+fn connect(workers: &Workers, destination: SocketAddr, connect_timeout: Duration)
+-> io::Result<Connection> {
+    let (sender, receiver) = mpsc::channel();
+
+    workers.run(Box::new(move || {
+        let _ = sender.send(connect_tcp(destination, connect_timeout));
+    }))?;
+
+    Ok(Connection::Connecting(receiver, Vec::new()))
+}
+
+/// Open a nonblocking TCP stream to `destination`, whose writes time out after `connect_timeout`
+/// once made blocking.
+fn connect_tcp(destination: SocketAddr, connect_timeout: Duration) -> io::Result<TcpStream> {
+    let stream = TcpStream::connect_timeout(&destination, connect_timeout)?;
+
+    stream.set_nodelay(true)?;
+    stream.set_write_timeout(Some(connect_timeout))?;
+    stream.set_nonblocking(true)?;
+
+    Ok(stream)
+}
+
+/// Open a nonblocking UDP socket connected to `destination`.
+fn connect_udp(destination: SocketAddr) -> io::Result<UdpSocket> {
+    let local = match destination {
+        SocketAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+        SocketAddr::V6(_) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0)
+    };
+
+    let socket = UdpSocket::bind(local)?;
+
+    socket.connect(destination)?;
+    socket.set_nonblocking(true)?;
+
+    Ok(socket)
+}
+
+/// Complete a TCP connection being established, sending the data queued for it.
+///
+/// Returns an error when the connection failed.
+fn establish(connection: &mut Connection) -> io::Result<()> {
+    let mut stream = match *connection {
+        Connection::Connecting(ref receiver, _) => {
+            match receiver.try_recv() {
+                Ok(result) => result?,
+                Err(TryRecvError::Empty) => return Ok(()),
+                Err(TryRecvError::Disconnected) => {
+                    return Err(io::Error::new(io::ErrorKind::Other, "connect failed"));
+                }
+            }
+        },
+        _ => return Ok(())
+    };
+
+    if let Connection::Connecting(_, ref queued) = *connection {
+        write_all(&mut stream, queued)?;
+    }
+
+    *connection = Connection::Tcp(stream, Vec::new());
+
+    Ok(())
+}
+
+/// Send a single forwarded packet to `destination` over a new TCP connection, without waiting for
+/// a response.
+fn send_tcp(destination: SocketAddr, buffer: &[u8], connect_timeout: Duration) -> io::Result<()> {
+    let mut stream = TcpStream::connect_timeout(&destination, connect_timeout)?;
+
+    stream.set_write_timeout(Some(connect_timeout))?;
+    stream.write_all(buffer)?;
+
+    stream.shutdown(Shutdown::Write)
+}
+
+/// Write all of `buffer` to a nonblocking stream, waiting for it to become writable as needed.
+fn write_all(stream: &mut TcpStream, buffer: &[u8]) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+
+    let result = stream.write_all(buffer);
+
+    stream.set_nonblocking(true)?;
+
+    result
+}
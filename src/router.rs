@@ -0,0 +1,341 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+#[cfg(any(feature = "toml", feature = "json"))]
+use ConfigError;
+#[cfg(any(feature = "toml", feature = "json"))]
+use config;
+use message_header::MessageType;
+use options_header::MobileId;
+use packet::Packet;
+
+use std::collections::HashMap;
+
+#[cfg(any(feature = "toml", feature = "json"))]
+use std::path::Path;
+
+/// Criteria a packet must meet to take a route. Empty criteria match every packet.
+#[derive(Clone,Debug,Default,Eq,PartialEq)]
+pub struct Rule {
+    /// Destination name, such as a tenant ID or pipeline.
+    destination: String,
+
+    /// Message types, or any message type when empty.
+    message_types: Vec<MessageType>,
+
+    /// Mobile ID prefix, written as `type:prefix` or as a bare prefix matching any type.
+    mobile_id: Option<String>,
+
+    /// Routing field prefix.
+    routing: Option<Vec<u8>>
+}
+
+impl Rule {
+    /// Create a new Rule that matches every packet.
+    pub fn new(destination: &str) -> Rule {
+        Rule{
+            destination: destination.to_string(),
+            ..Rule::default()
+        }
+    }
+
+    /// Retrieve the destination name.
+    pub fn destination(&self) -> &str {
+        &self.destination
+    }
+
+    /// Indicates the rule matches `packet`.
+    pub fn matches(&self, packet: &Packet) -> bool {
+        let options_header = packet.options_header();
+
+        if let Some(ref prefix) = self.routing {
+            match *options_header.routing() {
+                Some(ref routing) if routing.starts_with(prefix) => {},
+                _ => return false
+            }
+        }
+
+        if let Some(ref prefix) = self.mobile_id {
+            match *options_header.mobile_id() {
+                Some(ref mobile_id) if mobile_id_matches(prefix, mobile_id) => {},
+                _ => return false
+            }
+        }
+
+        self.message_types.is_empty()
+        || self.message_types.contains(packet.message_header().message_type())
+    }
+
+    /// Retrieve the message types.
+    pub fn message_types(&self) -> &[MessageType] {
+        &self.message_types
+    }
+
+    /// Retrieve the mobile ID prefix.
+    pub fn mobile_id(&self) -> &Option<String> {
+        &self.mobile_id
+    }
+
+    /// Retrieve the routing field prefix.
+    pub fn routing(&self) -> &Option<Vec<u8>> {
+        &self.routing
+    }
+
+    /// Set the message types.
+    pub fn set_message_types(&mut self, message_types: Vec<MessageType>) {
+        self.message_types = message_types;
+    }
+
+    /// Set the mobile ID prefix.
+    pub fn set_mobile_id(&mut self, mobile_id: Option<String>) {
+        self.mobile_id = mobile_id;
+    }
+
+    /// Set the routing field prefix.
+    pub fn set_routing(&mut self, routing: Option<Vec<u8>>) {
+        self.routing = routing;
+    }
+}
+
+/// Packet counts for a destination.
+#[derive(Clone,Debug,Default)]
+pub struct RouteMetrics {
+    /// Packet counts by message type.
+    message_types: HashMap<MessageType, u64>,
+
+    /// Packet count.
+    packets: u64
+}
+
+impl RouteMetrics {
+    /// Retrieve the packet count for `message_type`.
+    pub fn message_type(&self, message_type: MessageType) -> u64 {
+        self.message_types.get(&message_type).cloned().unwrap_or(0)
+    }
+
+    /// Retrieve the packet count.
+    pub fn packets(&self) -> u64 {
+        self.packets
+    }
+
+    /// Count a routed packet.
+    fn record(&mut self, message_type: MessageType) {
+        self.packets += 1;
+
+        *self.message_types.entry(message_type).or_insert(0) += 1;
+    }
+}
+
+/// Route entry within a TOML or JSON file.
+#[cfg(any(feature = "toml", feature = "json"))]
+#[derive(Deserialize)]
+struct Entry {
+    /// Destination name.
+    destination: String,
+
+    /// Message types.
+    #[serde(default)]
+    message_types: Vec<MessageType>,
+
+    /// Mobile ID prefix.
+    mobile_id: Option<String>,
+
+    /// Routing field prefix as hex.
+    routing: Option<String>
+}
+
+/// Layout of a TOML or JSON routing file.
+#[cfg(any(feature = "toml", feature = "json"))]
+#[derive(Deserialize)]
+struct File {
+    /// Default destination.
+    default: Option<String>,
+
+    /// Route entries.
+    #[serde(default)]
+    route: Vec<Entry>
+}
+
+/// Maps packets to named destinations by routing field, mobile ID and message type.
+///
+/// Rules are tried in order and the first match wins. Packets matching no rule take the default
+/// route when one is set. Packets routed to each destination are counted.
+#[derive(Clone,Debug,Default)]
+pub struct Router {
+    /// Default destination.
+    default: Option<String>,
+
+    /// Packet counts by destination.
+    metrics: HashMap<String, RouteMetrics>,
+
+    /// Rules in match order.
+    rules: Vec<Rule>,
+
+    /// Count of packets matching no rule without a default route.
+    unrouted: u64
+}
+
+impl Router {
+    /// Create a new Router without rules or a default route.
+    pub fn new() -> Router {
+        Router::default()
+    }
+
+    /// Load a routing file, choosing the format from the `.toml` or `.json` extension.
+    ///
+    /// The file names an optional default destination and lists one `route` entry per rule, in
+    /// match order. Routing prefixes are hex, and mobile ID prefixes are written as `type:prefix`
+    /// or as a bare prefix matching any mobile ID type:
+    ///
+    /// ```toml
+    /// default = "unassigned"
+    ///
+    /// [[route]]
+    /// destination   = "acme-alerts"
+    /// routing       = "0a01"
+    /// message_types = ["event_report"]
+    ///
+    /// [[route]]
+    /// destination = "acme"
+    /// mobile_id   = "esn:46411"
+    /// ```
+    #[cfg(any(feature = "toml", feature = "json"))]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Router, ConfigError> {
+        config::load(path.as_ref()).and_then(Router::from_file)
+    }
+
+    /// Parse a router from TOML text.
+    #[cfg(feature = "toml")]
+    pub fn from_toml(text: &str) -> Result<Router, ConfigError> {
+        config::from_toml(text).and_then(Router::from_file)
+    }
+
+    /// Parse a router from JSON text.
+    #[cfg(feature = "json")]
+    pub fn from_json(text: &str) -> Result<Router, ConfigError> {
+        config::from_json(text).and_then(Router::from_file)
+    }
+
+    /// Build a router from a parsed file.
+    #[cfg(any(feature = "toml", feature = "json"))]
+    fn from_file(file: File) -> Result<Router, ConfigError> {
+        let mut router = Router::new();
+
+        router.set_default(file.default);
+
+        for entry in file.route {
+            let mut rule = Rule::new(&entry.destination);
+
+            if let Some(routing) = entry.routing {
+                rule.set_routing(Some(::util::decode_hex(&routing).ok_or_else(|| {
+                    ConfigError::Invalid(format!("invalid routing prefix '{}' for {}", routing,
+                                                 rule.destination()))
+                })?));
+            }
+
+            rule.set_message_types(entry.message_types);
+            rule.set_mobile_id(entry.mobile_id);
+
+            router.push(rule);
+        }
+
+        Ok(router)
+    }
+
+    /// Retrieve the default destination.
+    pub fn default_route(&self) -> &Option<String> {
+        &self.default
+    }
+
+    /// Retrieve the packet counts for `destination`.
+    pub fn metrics(&self, destination: &str) -> Option<&RouteMetrics> {
+        self.metrics.get(destination)
+    }
+
+    /// Append a rule, matched after existing rules.
+    pub fn push(&mut self, rule: Rule) {
+        self.rules.push(rule);
+    }
+
+    /// Retrieve the destination for `packet` without counting it.
+    pub fn resolve(&self, packet: &Packet) -> Option<&str> {
+        resolve(&self.rules, &self.default, packet)
+    }
+
+    /// Retrieve the destination for `packet`, counting it against the destination metrics.
+    pub fn route(&mut self, packet: &Packet) -> Option<&str> {
+        let destination = resolve(&self.rules, &self.default, packet);
+
+        match destination {
+            Some(destination) => {
+                self.metrics.entry(destination.to_string())
+                            .or_default()
+                            .record(*packet.message_header().message_type());
+            },
+            None => self.unrouted += 1
+        }
+
+        destination
+    }
+
+    /// Retrieve the rules in match order.
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Set the default destination.
+    pub fn set_default(&mut self, default: Option<String>) {
+        self.default = default;
+    }
+
+    /// Retrieve the count of packets that matched no rule without a default route.
+    pub fn unrouted(&self) -> u64 {
+        self.unrouted
+    }
+}
+
+/// Retrieve the destination of the first rule matching `packet`, or the default destination.
+fn resolve<'a>(rules: &'a [Rule], default: &'a Option<String>, packet: &Packet)
+-> Option<&'a str> {
+    rules.iter()
+         .find(|rule| rule.matches(packet))
+         .map(|rule| rule.destination())
+         .or(default.as_deref())
+}
+
+/// Indicates `mobile_id` starts with `prefix`, written as `type:prefix` or as a bare prefix.
+fn mobile_id_matches(prefix: &str, mobile_id: &MobileId) -> bool {
+    let (kind, value) = match *mobile_id {
+        MobileId::Esn(ref esn) => ("esn", esn.clone()),
+        MobileId::ImeiEid(ref id) => ("imei", id.clone()),
+        MobileId::Imsi(ref imsi) => ("imsi", imsi.clone()),
+        MobileId::IpAddress(ref ip) => ("ip", ip.clone()),
+        MobileId::Phone(ref phone) => ("phone", phone.clone()),
+        MobileId::Unknown(_, ref bytes) => {
+            ("unknown", bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+        },
+        MobileId::User(ref user) => {
+            ("user", user.iter().map(|byte| format!("{:02x}", byte)).collect())
+        }
+    };
+
+    match prefix.find(':') {
+        Some(index) => &prefix[..index] == kind && value.starts_with(&prefix[index + 1..]),
+        None => value.starts_with(prefix)
+    }
+}
@@ -0,0 +1,275 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+use {CalAmpError, ParseOptions};
+use authentication::{AuthenticationError, CredentialStore};
+use cipher::Cipher;
+use message::Message;
+use message::acknowledgement::{AcknowledgementMessage, AcknowledgementType};
+use message_header::{MessageHeader, MessageType, ServiceType};
+use options_header::OptionsHeader;
+use packet::Packet;
+
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+
+/// Default LMU server port.
+pub const DEFAULT_PORT: u16 = 20500;
+
+/// Maximum UDP datagram size, used for receive buffers.
+pub const MAX_DATAGRAM_SIZE: usize = 65535;
+
+/// Packet handler.
+pub trait Handler {
+    /// Handle a parsed packet received from `peer`.
+    ///
+    /// Returns the acknowledgement type sent back to the unit when the packet is an acknowledged
+    /// request.
+    fn handle(&mut self, packet: &Packet, peer: SocketAddr) -> AcknowledgementType;
+
+    /// Handle a datagram from `peer` that could not be parsed.
+    fn error(&mut self, _error: CalAmpError, _peer: SocketAddr) {
+    }
+
+    /// Handle a packet from `peer` that failed authentication against the credential store. The
+    /// packet is not passed along to `handle`.
+    fn rejected(&mut self, _packet: &Packet, _error: AuthenticationError, _peer: SocketAddr) {
+    }
+
+    /// Handle an ACK/NAK message for a packet from `peer` that could not be sent to `address`.
+    fn send_failed(&mut self, _error: io::Error, _peer: SocketAddr, _address: SocketAddr) {
+    }
+}
+
+impl<F> Handler for F where F: FnMut(&Packet, SocketAddr) -> AcknowledgementType {
+    fn handle(&mut self, packet: &Packet, peer: SocketAddr) -> AcknowledgementType {
+        self(packet, peer)
+    }
+}
+
+/// LMU UDP server.
+///
+/// Each datagram is parsed into a `Packet` and passed along to the handler. Acknowledged requests
+/// are answered with an ACK/NAK message. Encrypted message bodies are decrypted when a cipher is
+/// set.
+///
+/// When a credential store is set, packets failing authentication are passed to
+/// `Handler::rejected` instead and answered with `AcknowledgementType::FailedAuthentication` at
+/// the sending address, ignoring any redirection. ACK/NAK messages for authenticated packets carry
+/// the authentication field for their mobile ID.
+pub struct Server<H: Handler> {
+    /// Application version reported in ACK/NAK messages.
+    application_version: [u8; 3],
+
+    /// Receive buffer.
+    buffer: Vec<u8>,
+
+    /// Message body cipher.
+    cipher: Option<Box<dyn Cipher + Send>>,
+
+    /// Credential store.
+    credentials: Option<Box<dyn CredentialStore + Send>>,
+
+    /// Packet handler.
+    handler: H,
+
+    /// Parser behavior.
+    parse_options: ParseOptions,
+
+    /// Bound socket.
+    socket: UdpSocket
+}
+
+impl<H: Handler> Server<H> {
+    /// Create a new Server listening on all interfaces at `DEFAULT_PORT`.
+    pub fn new(handler: H) -> io::Result<Server<H>> {
+        Server::bind(("0.0.0.0", DEFAULT_PORT), handler)
+    }
+
+    /// Create a new Server listening on `address`.
+    pub fn bind<A: ToSocketAddrs>(address: A, handler: H) -> io::Result<Server<H>> {
+        Ok(Server{
+            application_version: [0; 3],
+            buffer:              vec![0; MAX_DATAGRAM_SIZE],
+            cipher:              None,
+            credentials:         None,
+            handler,
+            parse_options:       ParseOptions::strict(),
+            socket:              UdpSocket::bind(address)?
+        })
+    }
+
+    /// Receive and process a single datagram.
+    ///
+    /// Only a failure to receive is returned. ACK/NAK messages that fail to send are passed to
+    /// `Handler::send_failed`.
+    pub fn serve_once(&mut self) -> io::Result<()> {
+        let (length, peer) = self.socket.recv_from(&mut self.buffer)?;
+
+        let data   = &self.buffer[..length];
+        let parsed = match self.cipher {
+            Some(ref cipher) => {
+                Packet::parse_with_cipher(data, cipher.as_ref(), &self.parse_options)
+            },
+            None => Packet::parse_with_options(data, &self.parse_options)
+        };
+
+        let packet = match parsed {
+            Ok((packet, _)) => packet,
+            Err(error) => {
+                self.handler.error(error, peer);
+
+                return Ok(());
+            }
+        };
+
+        let verified = match self.credentials {
+            Some(ref credentials) => credentials.verify(packet.options_header()),
+            None => Ok(())
+        };
+
+        let trusted = verified.is_ok();
+        let ack     = match verified {
+            Ok(()) => {
+                self.handler.handle(&packet, peer)
+            },
+            Err(error) => {
+                let ack = error.ack();
+
+                self.handler.rejected(&packet, error, peer);
+
+                ack
+            }
+        };
+
+        if *packet.message_header().service_type() == ServiceType::AcknowledgedRequest {
+            let address = if trusted { reply_address(packet.options_header(), peer) } else { peer };
+
+            if let Err(error) = self.acknowledge(&packet, ack, address, trusted) {
+                self.handler.send_failed(error, peer, address);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Receive and process datagrams until receiving fails. ACK/NAK messages that fail to send
+    /// are passed to `Handler::send_failed` without stopping the server.
+    pub fn serve(&mut self) -> io::Result<()> {
+        loop {
+            self.serve_once()?;
+        }
+    }
+
+    /// Send an ACK/NAK message for `packet` to `address`.
+    ///
+    /// Packets that failed authentication are answered without an authentication field, and at
+    /// the sending address chosen by the caller, so a forged packet can neither learn the
+    /// credential nor redirect the reply elsewhere.
+    fn acknowledge(&self, packet: &Packet, ack: AcknowledgementType, address: SocketAddr,
+                   trusted: bool)
+    -> io::Result<()> {
+        let mut options_header = OptionsHeader::new();
+
+        options_header.set_mobile_id(packet.options_header().mobile_id().clone());
+
+        if let Some(ref credentials) = self.credentials {
+            if trusted {
+                // units without a credential are answered without authentication
+                let _ = credentials.authenticate(&mut options_header);
+            }
+        }
+
+        let response = Packet::new(options_header,
+                                   MessageHeader::new(ServiceType::Response,
+                                                      MessageType::AckNak,
+                                                      packet.message_header().sequence_number()),
+                                   Message::AckNak(AcknowledgementMessage::new(
+                                       *packet.message_header().message_type(),
+                                       ack,
+                                       self.application_version)));
+
+        let mut buffer = Vec::new();
+
+        response.encode(&mut buffer);
+
+        self.socket.send_to(&buffer, address)?;
+
+        Ok(())
+    }
+
+    /// Retrieve the handler.
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+
+    /// Retrieve the mutable handler.
+    pub fn handler_mut(&mut self) -> &mut H {
+        &mut self.handler
+    }
+
+    /// Retrieve the local address.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Set the cipher that encrypted message bodies are decrypted with. Without a cipher,
+    /// encrypted message bodies are passed to the handler as `Message::Raw`.
+    pub fn set_cipher(&mut self, cipher: Option<Box<dyn Cipher + Send>>) {
+        self.cipher = cipher;
+    }
+
+    /// Set the credential store that inbound packets are verified against.
+    pub fn set_credential_store(&mut self, credentials: Option<Box<dyn CredentialStore + Send>>) {
+        self.credentials = credentials;
+    }
+
+    /// Set the parser behavior. Lenient parsing passes packets with anomalies along to the
+    /// handler, with the anomalies recorded in `Packet::warnings`.
+    pub fn set_parse_options(&mut self, parse_options: ParseOptions) {
+        self.parse_options = parse_options;
+    }
+
+    /// Set the application version reported in ACK/NAK messages.
+    pub fn set_application_version(&mut self, application_version: [u8; 3]) {
+        self.application_version = application_version;
+    }
+
+    /// Retrieve the bound socket.
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+}
+
+/// Retrieve the address that responses to a packet from `peer` are sent to.
+///
+/// The redirection address replaces the peer address when it is supplied. A zero IP address or
+/// port within the redirection falls back to the peer IP address or port.
+pub fn reply_address(options_header: &OptionsHeader, peer: SocketAddr) -> SocketAddr {
+    match *options_header.redirection() {
+        Some((ref ip, port)) => {
+            let ip = match ip.parse::<Ipv4Addr>() {
+                Ok(ip) if !ip.is_unspecified() => IpAddr::V4(ip),
+                _ => peer.ip()
+            };
+
+            SocketAddr::new(ip, if port == 0 { peer.port() } else { port })
+        },
+        None => peer
+    }
+}
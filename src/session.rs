@@ -0,0 +1,245 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+use message::Message;
+use message::id_report::IdReportMessage;
+use options_header::MobileId;
+use packet::Packet;
+
+use std::collections::{BTreeMap, HashMap};
+use std::collections::hash_map;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// Default maximum number of sessions held at once.
+pub const DEFAULT_CAPACITY: usize = 65536;
+
+/// Per-device session.
+#[derive(Clone,Debug)]
+pub struct Session {
+    /// Last ID report.
+    id_report: Option<IdReportMessage>,
+
+    /// Time the last packet was received.
+    last_seen: Instant,
+
+    /// Mobile ID.
+    mobile_id: MobileId,
+
+    /// Address the last packet was received from.
+    peer: SocketAddr,
+
+    /// Sequence number of the last packet.
+    sequence_number: u16,
+
+    /// Update serial, ordering sessions last seen at the same time.
+    serial: u64
+}
+
+impl Session {
+    /// Retrieve the last ID report.
+    pub fn id_report(&self) -> &Option<IdReportMessage> {
+        &self.id_report
+    }
+
+    /// Retrieve the time the last packet was received.
+    pub fn last_seen(&self) -> Instant {
+        self.last_seen
+    }
+
+    /// Retrieve the mobile ID.
+    pub fn mobile_id(&self) -> &MobileId {
+        &self.mobile_id
+    }
+
+    /// Retrieve the address the last packet was received from.
+    pub fn peer(&self) -> SocketAddr {
+        self.peer
+    }
+
+    /// Retrieve the sequence number of the last packet.
+    pub fn sequence_number(&self) -> u16 {
+        self.sequence_number
+    }
+}
+
+/// Session table keyed by mobile ID.
+///
+/// Sessions are created and refreshed from parsed packets, and removed by `expire()` once they
+/// have been idle for longer than the idle timeout. When the table is full, the least recently
+/// seen session makes room for a new unit.
+#[derive(Clone,Debug)]
+pub struct SessionTable {
+    /// Maximum number of sessions.
+    capacity: usize,
+
+    /// Idle timeout.
+    idle_timeout: Duration,
+
+    /// Mobile IDs ordered by last seen time and update serial.
+    order: BTreeMap<(Instant, u64), MobileId>,
+
+    /// Next update serial.
+    serial: u64,
+
+    /// Sessions.
+    sessions: HashMap<MobileId, Session>
+}
+
+impl SessionTable {
+    /// Create a new SessionTable that expires sessions idle for longer than `idle_timeout`.
+    pub fn new(idle_timeout: Duration) -> SessionTable {
+        SessionTable{
+            capacity: DEFAULT_CAPACITY,
+            idle_timeout,
+            order:    BTreeMap::new(),
+            serial:   0,
+            sessions: HashMap::new()
+        }
+    }
+
+    /// Update the session for the unit that sent `packet` from `peer` at time `now`.
+    ///
+    /// Returns the updated session, or `None` when the packet does not carry a mobile ID.
+    pub fn update(&mut self, packet: &Packet, peer: SocketAddr, now: Instant) -> Option<&Session> {
+        let mobile_id = match *packet.options_header().mobile_id() {
+            Some(ref mobile_id) => mobile_id,
+            None => return None
+        };
+
+        let sequence_number = packet.message_header().sequence_number();
+        let serial          = self.serial;
+
+        match self.sessions.get(mobile_id) {
+            Some(session) => {
+                self.order.remove(&(session.last_seen, session.serial));
+            },
+            None => {
+                while self.sessions.len() >= self.capacity && self.evict() {}
+            }
+        }
+
+        self.serial += 1;
+        self.order.insert((now, serial), mobile_id.clone());
+
+        let session = self.sessions.entry(mobile_id.clone()).or_insert_with(|| {
+            Session{
+                id_report: None,
+                last_seen: now,
+                mobile_id: mobile_id.clone(),
+                peer,
+                sequence_number,
+                serial
+            }
+        });
+
+        session.last_seen       = now;
+        session.peer            = peer;
+        session.sequence_number = sequence_number;
+        session.serial          = serial;
+
+        if let Message::IdReport(ref id_report) = *packet.message() {
+            session.id_report = Some(id_report.clone());
+        }
+
+        Some(session)
+    }
+
+    /// Remove sessions that have been idle for longer than the idle timeout at time `now`.
+    ///
+    /// Returns the expired sessions.
+    pub fn expire(&mut self, now: Instant) -> Vec<Session> {
+        let mut expired = Vec::new();
+
+        while let Some(entry) = self.order.first_entry() {
+            if now.saturating_duration_since(entry.key().0) <= self.idle_timeout {
+                break;
+            }
+
+            let mobile_id = entry.remove();
+
+            expired.extend(self.sessions.remove(&mobile_id));
+        }
+
+        expired
+    }
+
+    /// Remove the least recently seen session.
+    ///
+    /// Returns `false` when the table is empty.
+    fn evict(&mut self) -> bool {
+        match self.order.pop_first() {
+            Some((_, mobile_id)) => {
+                self.sessions.remove(&mobile_id);
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Retrieve the session for `mobile_id`.
+    pub fn get(&self, mobile_id: &MobileId) -> Option<&Session> {
+        self.sessions.get(mobile_id)
+    }
+
+    /// Retrieve the last known address of `mobile_id`.
+    pub fn peer(&self, mobile_id: &MobileId) -> Option<SocketAddr> {
+        self.sessions.get(mobile_id).map(|session| session.peer)
+    }
+
+    /// Remove the session for `mobile_id`.
+    pub fn remove(&mut self, mobile_id: &MobileId) -> Option<Session> {
+        let session = self.sessions.remove(mobile_id)?;
+
+        self.order.remove(&(session.last_seen, session.serial));
+
+        Some(session)
+    }
+
+    /// Retrieve an iterator over all sessions.
+    pub fn iter(&self) -> hash_map::Values<'_, MobileId, Session> {
+        self.sessions.values()
+    }
+
+    /// Retrieve the session count.
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Indicates that the table holds no sessions.
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// Retrieve the maximum number of sessions.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Set the maximum number of sessions, evicting the least recently seen sessions beyond it.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+
+        while self.sessions.len() > self.capacity && self.evict() {}
+    }
+
+    /// Retrieve the idle timeout.
+    pub fn idle_timeout(&self) -> Duration {
+        self.idle_timeout
+    }
+}
@@ -0,0 +1,217 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+#[cfg(any(feature = "toml", feature = "json"))]
+use ConfigError;
+#[cfg(any(feature = "toml", feature = "json"))]
+use config;
+
+use std::collections::HashMap;
+use std::fmt;
+
+#[cfg(any(feature = "toml", feature = "json"))]
+use std::path::Path;
+
+/// Received signal strength.
+#[derive(Clone,Copy,Debug,Eq,Hash,Ord,PartialEq,PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct Rssi(i16);
+
+/// Signal quality classification.
+#[derive(Clone,Copy,Debug,Eq,Hash,PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum SignalQuality {
+    /// -70 dBm and above.
+    Excellent,
+
+    /// -100 to -86 dBm.
+    Fair,
+
+    /// -85 to -71 dBm.
+    Good,
+
+    /// -113 dBm and below, the floor reported by the modem.
+    NoSignal,
+
+    /// -112 to -101 dBm.
+    Poor,
+
+    /// Zero or positive, which the modem reports when it has no reading.
+    Unknown
+}
+
+impl Rssi {
+    /// Create a new Rssi from its wire value in dBm.
+    pub fn new(dbm: i16) -> Rssi {
+        Rssi(dbm)
+    }
+
+    /// Retrieve the signal strength in dBm.
+    pub fn dbm(&self) -> i16 {
+        self.0
+    }
+
+    /// Classify the signal quality.
+    pub fn quality(&self) -> SignalQuality {
+        match self.0 {
+            n if n >= 0 => SignalQuality::Unknown,
+            n if n >= -70 => SignalQuality::Excellent,
+            n if n >= -85 => SignalQuality::Good,
+            n if n >= -100 => SignalQuality::Fair,
+            n if n > -113 => SignalQuality::Poor,
+            _ => SignalQuality::NoSignal
+        }
+    }
+}
+
+impl fmt::Display for Rssi {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{} dBm", self.0)
+    }
+}
+
+impl fmt::Display for SignalQuality {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SignalQuality::Excellent => {
+                write!(formatter, "Excellent")
+            },
+            SignalQuality::Fair => {
+                write!(formatter, "Fair")
+            },
+            SignalQuality::Good => {
+                write!(formatter, "Good")
+            },
+            SignalQuality::NoSignal => {
+                write!(formatter, "NoSignal")
+            },
+            SignalQuality::Poor => {
+                write!(formatter, "Poor")
+            },
+            SignalQuality::Unknown => {
+                write!(formatter, "Unknown")
+            }
+        }
+    }
+}
+
+/// Wireless carrier ID.
+#[derive(Clone,Copy,Debug,Eq,Hash,Ord,PartialEq,PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct CarrierId(u16);
+
+impl CarrierId {
+    /// Create a new CarrierId from its wire value.
+    pub fn new(id: u16) -> CarrierId {
+        CarrierId(id)
+    }
+
+    /// Retrieve the wire value.
+    pub fn value(&self) -> u16 {
+        self.0
+    }
+}
+
+impl fmt::Display for CarrierId {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{}", self.0)
+    }
+}
+
+/// Table entry within a TOML or JSON file.
+#[cfg(any(feature = "toml", feature = "json"))]
+#[derive(Deserialize)]
+struct Entry {
+    /// Carrier ID.
+    id: u16,
+
+    /// Network name.
+    name: String
+}
+
+/// Layout of a TOML or JSON table file.
+#[cfg(any(feature = "toml", feature = "json"))]
+#[derive(Deserialize)]
+struct File {
+    /// Table entries.
+    #[serde(default)]
+    carrier: Vec<Entry>
+}
+
+/// Lookup table of human-readable carrier network names.
+#[derive(Clone,Debug,Default)]
+pub struct CarrierTable {
+    /// Names keyed by carrier ID.
+    names: HashMap<CarrierId, String>
+}
+
+impl CarrierTable {
+    /// Create a new empty CarrierTable.
+    pub fn new() -> CarrierTable {
+        CarrierTable::default()
+    }
+
+    /// Load a table file, choosing the format from the `.toml` or `.json` extension.
+    ///
+    /// The file lists one `carrier` entry per network:
+    ///
+    /// ```toml
+    /// [[carrier]]
+    /// id   = 410
+    /// name = "AT&T Mobility"
+    /// ```
+    #[cfg(any(feature = "toml", feature = "json"))]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<CarrierTable, ConfigError> {
+        config::load(path.as_ref()).map(CarrierTable::from_file)
+    }
+
+    /// Parse a table from TOML text.
+    #[cfg(feature = "toml")]
+    pub fn from_toml(text: &str) -> Result<CarrierTable, ConfigError> {
+        config::from_toml(text).map(CarrierTable::from_file)
+    }
+
+    /// Parse a table from JSON text.
+    #[cfg(feature = "json")]
+    pub fn from_json(text: &str) -> Result<CarrierTable, ConfigError> {
+        config::from_json(text).map(CarrierTable::from_file)
+    }
+
+    /// Build a table from a parsed file.
+    #[cfg(any(feature = "toml", feature = "json"))]
+    fn from_file(file: File) -> CarrierTable {
+        let mut table = CarrierTable::new();
+
+        for entry in file.carrier {
+            table.insert(CarrierId(entry.id), &entry.name);
+        }
+
+        table
+    }
+
+    /// Set the name of `carrier`.
+    pub fn insert(&mut self, carrier: CarrierId, name: &str) {
+        self.names.insert(carrier, name.to_string());
+    }
+
+    /// Retrieve the name of `carrier`.
+    pub fn name(&self, carrier: CarrierId) -> Option<&str> {
+        self.names.get(&carrier).map(|name| name.as_str())
+    }
+}
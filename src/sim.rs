@@ -0,0 +1,1043 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+use ConfigError;
+#[cfg(any(feature = "toml", feature = "json"))]
+use config;
+use accumulators::Accumulators;
+use flags::{CommState, Inputs, UnitStatus};
+use gps_fix::GpsFix;
+use lmu_time::{LmuTime, UpdateTime};
+use message::Message;
+use message::acknowledgement::{AcknowledgementMessage, AcknowledgementType};
+use message::event_report::EventReportMessage;
+use message::id_report::IdReportMessage;
+use message::locate_report::LocateReportMessage;
+use message::null::NullMessage;
+use message::report_header::ReportHeader;
+use message::user::UserMessage;
+use message_header::{MessageHeader, MessageType, ServiceType};
+use options_header::{MobileId, OptionsHeader};
+use packet::Packet;
+use server::MAX_DATAGRAM_SIZE;
+use signal::Rssi;
+
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Unit request action code asking for a locate report.
+pub const LOCATE_REQUEST: u8 = 0;
+
+/// Position along a route.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Waypoint {
+    /// Altitude in meters.
+    altitude: f64,
+
+    /// Latitude in degrees.
+    latitude: f64,
+
+    /// Longitude in degrees.
+    longitude: f64,
+
+    /// Speed in kilometers per hour, or the profile speed when not supplied.
+    speed: Option<f64>
+}
+
+impl Waypoint {
+    /// Create a new Waypoint at sea level.
+    pub fn new(latitude: f64, longitude: f64) -> Waypoint {
+        Waypoint{
+            altitude: 0.0,
+            latitude,
+            longitude,
+            speed:    None
+        }
+    }
+
+    /// Retrieve the altitude in meters.
+    pub fn altitude(&self) -> f64 {
+        self.altitude
+    }
+
+    /// Retrieve the latitude in degrees.
+    pub fn latitude(&self) -> f64 {
+        self.latitude
+    }
+
+    /// Retrieve the longitude in degrees.
+    pub fn longitude(&self) -> f64 {
+        self.longitude
+    }
+
+    /// Retrieve the speed in kilometers per hour.
+    pub fn speed(&self) -> Option<f64> {
+        self.speed
+    }
+
+    /// Set the altitude in meters.
+    pub fn set_altitude(&mut self, altitude: f64) {
+        self.altitude = altitude;
+    }
+
+    /// Set the speed in kilometers per hour.
+    pub fn set_speed(&mut self, speed: Option<f64>) {
+        self.speed = speed;
+    }
+
+    /// Retrieve the initial bearing towards `other` in whole degrees.
+    fn heading_to(&self, other: &Waypoint) -> u16 {
+        let (latitude1, latitude2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let delta                  = (other.longitude - self.longitude).to_radians();
+
+        let y = delta.sin() * latitude2.cos();
+        let x = latitude1.cos() * latitude2.sin() - latitude1.sin() * latitude2.cos() * delta.cos();
+
+        (y.atan2(x).to_degrees().round() as i32).rem_euclid(360) as u16
+    }
+}
+
+/// Waypoint entry within a TOML or JSON file.
+#[cfg(any(feature = "toml", feature = "json"))]
+#[derive(Deserialize)]
+struct Entry {
+    /// Altitude in meters.
+    altitude: Option<f64>,
+
+    /// Latitude in degrees.
+    latitude: f64,
+
+    /// Longitude in degrees.
+    longitude: f64,
+
+    /// Speed in kilometers per hour.
+    speed: Option<f64>
+}
+
+/// Layout of a TOML or JSON route file.
+#[cfg(any(feature = "toml", feature = "json"))]
+#[derive(Deserialize)]
+struct File {
+    /// Waypoint entries in travel order.
+    #[serde(default)]
+    waypoint: Vec<Entry>
+}
+
+/// Closed loop of waypoints followed by simulated units.
+#[derive(Clone,Debug)]
+pub struct Route {
+    /// Waypoints in travel order.
+    waypoints: Vec<Waypoint>
+}
+
+impl Route {
+    /// Create a new Route.
+    ///
+    /// A route without waypoints is replaced by a single waypoint at 0, 0. Route files without
+    /// waypoints are rejected instead.
+    pub fn new(mut waypoints: Vec<Waypoint>) -> Route {
+        if waypoints.is_empty() {
+            waypoints.push(Waypoint::new(0.0, 0.0));
+        }
+
+        Route{
+            waypoints
+        }
+    }
+
+    /// Load a route file, choosing the format from the `.csv`, `.gpx`, `.toml` or `.json`
+    /// extension.
+    ///
+    /// TOML and JSON files list one `waypoint` entry per position, with a latitude and longitude
+    /// in degrees, and an optional altitude in meters and speed in kilometers per hour:
+    ///
+    /// ```toml
+    /// [[waypoint]]
+    /// latitude  = 33.1031058
+    /// longitude = -117.2898431
+    /// altitude  = 12
+    /// speed     = 40
+    ///
+    /// [[waypoint]]
+    /// latitude  = 33.1042113
+    /// longitude = -117.2876020
+    /// ```
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Route, ConfigError> {
+        let path = path.as_ref();
+
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("csv") => Route::from_csv(&fs::read_to_string(path)?),
+            Some("gpx") => Route::from_gpx(&fs::read_to_string(path)?),
+            #[cfg(any(feature = "toml", feature = "json"))]
+            _ => config::load(path).and_then(Route::from_file),
+            #[cfg(not(any(feature = "toml", feature = "json")))]
+            _ => Err(ConfigError::Format(path.to_path_buf()))
+        }
+    }
+
+    /// Parse a route from CSV text.
+    ///
+    /// Each line holds a latitude and longitude in degrees, optionally followed by an altitude in
+    /// meters and a speed in kilometers per hour:
+    ///
+    /// ```text
+    /// latitude,longitude,altitude,speed
+    /// 33.1031058,-117.2898431,12,40
+    /// 33.1042113,-117.2876020
+    /// ```
+    ///
+    /// The header line, blank lines, and lines starting with `#` are skipped.
+    pub fn from_csv(csv: &str) -> Result<Route, ConfigError> {
+        let mut waypoints = Vec::new();
+
+        for (n, line) in csv.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') || line.starts_with("latitude") {
+                continue;
+            }
+
+            let error   = |message: String| ConfigError::Line(n + 1, message);
+            let columns = line.split(',').map(|column| column.trim()).collect::<Vec<&str>>();
+
+            if columns.len() < 2 || columns.len() > 4 {
+                return Err(error("expected latitude, longitude, altitude and speed columns"
+                                 .to_string()));
+            }
+
+            let number = |index: usize| -> Result<Option<f64>, ConfigError> {
+                match columns.get(index) {
+                    Some(column) if !column.is_empty() => {
+                        column.parse().map(Some).map_err(|_| {
+                            error(format!("invalid number '{}'", column))
+                        })
+                    },
+                    _ => Ok(None)
+                }
+            };
+
+            let (latitude, longitude) = match (number(0)?, number(1)?) {
+                (Some(latitude), Some(longitude)) => (latitude, longitude),
+                _ => return Err(error("latitude and longitude are required".to_string()))
+            };
+
+            let mut waypoint = Waypoint::new(latitude, longitude);
+
+            waypoint.set_altitude(number(2)?.unwrap_or(0.0));
+            waypoint.set_speed(number(3)?);
+
+            waypoints.push(waypoint);
+        }
+
+        Route::from_waypoints(waypoints)
+    }
+
+    /// Parse a route from TOML text.
+    #[cfg(feature = "toml")]
+    pub fn from_toml(text: &str) -> Result<Route, ConfigError> {
+        config::from_toml(text).and_then(Route::from_file)
+    }
+
+    /// Parse a route from JSON text.
+    #[cfg(feature = "json")]
+    pub fn from_json(text: &str) -> Result<Route, ConfigError> {
+        config::from_json(text).and_then(Route::from_file)
+    }
+
+    /// Build a route from a parsed file.
+    #[cfg(any(feature = "toml", feature = "json"))]
+    fn from_file(file: File) -> Result<Route, ConfigError> {
+        Route::from_waypoints(file.waypoint.into_iter().map(|entry| {
+            let mut waypoint = Waypoint::new(entry.latitude, entry.longitude);
+
+            waypoint.set_altitude(entry.altitude.unwrap_or(0.0));
+            waypoint.set_speed(entry.speed);
+
+            waypoint
+        }).collect())
+    }
+
+    /// Parse a route from GPX text.
+    ///
+    /// Track points, route points and waypoints are read in document order, with the altitude
+    /// taken from their `ele` element.
+    pub fn from_gpx(gpx: &str) -> Result<Route, ConfigError> {
+        let mut waypoints = Vec::new();
+        let mut rest      = gpx;
+
+        while let Some(start) = ["<trkpt", "<rtept", "<wpt"].iter()
+                                                             .filter_map(|tag| rest.find(tag))
+                                                             .min() {
+            let line  = gpx[..gpx.len() - rest.len() + start].matches('\n').count() + 1;
+            let error = |message: &str| ConfigError::Line(line, message.to_string());
+
+            rest = &rest[start..];
+
+            let tag_end = rest.find('>').ok_or_else(|| error("unterminated point"))?;
+            let tag     = &rest[..tag_end];
+
+            // self-closing points have no child elements
+            let body = if tag.ends_with('/') {
+                ""
+            } else {
+                let end = rest.find("</trkpt>")
+                              .into_iter()
+                              .chain(rest.find("</rtept>"))
+                              .chain(rest.find("</wpt>"))
+                              .min()
+                              .ok_or_else(|| error("unterminated point"))?;
+
+                &rest[tag_end..end]
+            };
+
+            let latitude  = attribute(tag, "lat").ok_or_else(|| error("invalid lat attribute"))?;
+            let longitude = attribute(tag, "lon").ok_or_else(|| error("invalid lon attribute"))?;
+
+            let mut waypoint = Waypoint::new(latitude, longitude);
+
+            if let Some(start) = body.find("<ele>") {
+                let value = &body[start + 5..];
+                let end   = value.find("</ele>").ok_or_else(|| error("unterminated ele"))?;
+
+                waypoint.set_altitude(value[..end].trim().parse().map_err(|_| {
+                    error("invalid ele value")
+                })?);
+            }
+
+            waypoints.push(waypoint);
+
+            rest = &rest[tag_end..];
+        }
+
+        Route::from_waypoints(waypoints)
+    }
+
+    /// Build a route from the waypoints of a route file, which must have at least one.
+    fn from_waypoints(waypoints: Vec<Waypoint>) -> Result<Route, ConfigError> {
+        if waypoints.is_empty() {
+            return Err(ConfigError::Invalid("route has no waypoints".to_string()));
+        }
+
+        Ok(Route::new(waypoints))
+    }
+
+    /// Retrieve the waypoints.
+    pub fn waypoints(&self) -> &[Waypoint] {
+        &self.waypoints
+    }
+
+    /// Retrieve the GPS fix at waypoint `index`, heading towards the next waypoint at `speed`
+    /// kilometers per hour unless the waypoint supplies a speed. Single waypoint routes are
+    /// stationary.
+    fn fix(&self, index: usize, speed: f64) -> GpsFix {
+        let waypoint = &self.waypoints[index % self.waypoints.len()];
+        let next     = &self.waypoints[(index + 1) % self.waypoints.len()];
+
+        let (heading, speed) = if self.waypoints.len() > 1 {
+            (waypoint.heading_to(next), waypoint.speed.unwrap_or(speed))
+        } else {
+            (0, 0.0)
+        };
+
+        GpsFix::new((waypoint.latitude * 10_000_000.0).round() as i32,
+                    (waypoint.longitude * 10_000_000.0).round() as i32,
+                    (waypoint.altitude * 100.0).round() as i32,
+                    (speed * 100_000.0 / 3600.0).round() as u32,
+                    heading,
+                    9,
+                    Default::default(),
+                    9)
+    }
+}
+
+/// Behavior shared by simulated units.
+#[derive(Clone,Debug)]
+pub struct Profile {
+    /// Application version reported in ID reports and ACK/NAK messages.
+    application_version: [u8; 3],
+
+    /// Event code of periodic event reports.
+    event_code: u8,
+
+    /// Idle time after which a null keep-alive message is sent.
+    keep_alive_interval: Option<Duration>,
+
+    /// Interval between event reports.
+    report_interval: Duration,
+
+    /// Delay after each attempt before an unacknowledged message is sent again, or dropped after
+    /// the last one.
+    retry_schedule: Vec<Duration>,
+
+    /// Script version reported in ID reports.
+    script_version: u8,
+
+    /// Speed in kilometers per hour between waypoints that do not supply one.
+    speed: f64,
+
+    /// Interval between user messages.
+    user_interval: Option<Duration>
+}
+
+impl Profile {
+    /// Create a new Profile reporting every 60 seconds, with a keep-alive after 5 minutes of idle
+    /// time, retries after 5, 10 and 20 seconds, and no user messages.
+    pub fn new() -> Profile {
+        Profile{
+            application_version: [0; 3],
+            event_code:          0,
+            keep_alive_interval: Some(Duration::from_secs(300)),
+            report_interval:     Duration::from_secs(60),
+            retry_schedule:      vec![Duration::from_secs(5),
+                                      Duration::from_secs(10),
+                                      Duration::from_secs(20)],
+            script_version:      0,
+            speed:               50.0,
+            user_interval:       None
+        }
+    }
+
+    /// Retrieve the application version.
+    pub fn application_version(&self) -> &[u8; 3] {
+        &self.application_version
+    }
+
+    /// Retrieve the event code of periodic event reports.
+    pub fn event_code(&self) -> u8 {
+        self.event_code
+    }
+
+    /// Retrieve the keep-alive interval.
+    pub fn keep_alive_interval(&self) -> Option<Duration> {
+        self.keep_alive_interval
+    }
+
+    /// Retrieve the interval between event reports.
+    pub fn report_interval(&self) -> Duration {
+        self.report_interval
+    }
+
+    /// Retrieve the retry schedule.
+    pub fn retry_schedule(&self) -> &[Duration] {
+        &self.retry_schedule
+    }
+
+    /// Retrieve the script version.
+    pub fn script_version(&self) -> u8 {
+        self.script_version
+    }
+
+    /// Retrieve the default speed in kilometers per hour.
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// Retrieve the interval between user messages.
+    pub fn user_interval(&self) -> Option<Duration> {
+        self.user_interval
+    }
+
+    /// Set the application version.
+    pub fn set_application_version(&mut self, application_version: [u8; 3]) {
+        self.application_version = application_version;
+    }
+
+    /// Set the event code of periodic event reports.
+    pub fn set_event_code(&mut self, event_code: u8) {
+        self.event_code = event_code;
+    }
+
+    /// Set the keep-alive interval, or disable keep-alive messages with `None`.
+    pub fn set_keep_alive_interval(&mut self, keep_alive_interval: Option<Duration>) {
+        self.keep_alive_interval = keep_alive_interval;
+    }
+
+    /// Set the interval between event reports.
+    pub fn set_report_interval(&mut self, report_interval: Duration) {
+        self.report_interval = report_interval;
+    }
+
+    /// Set the retry schedule. The schedule length is also the attempt count.
+    pub fn set_retry_schedule(&mut self, retry_schedule: Vec<Duration>) {
+        self.retry_schedule = retry_schedule;
+    }
+
+    /// Set the script version.
+    pub fn set_script_version(&mut self, script_version: u8) {
+        self.script_version = script_version;
+    }
+
+    /// Set the default speed in kilometers per hour.
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed;
+    }
+
+    /// Set the interval between user messages, or disable user messages with `None`.
+    pub fn set_user_interval(&mut self, user_interval: Option<Duration>) {
+        self.user_interval = user_interval;
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Profile {
+        Profile::new()
+    }
+}
+
+/// Message counts for simulated units.
+#[derive(Clone,Copy,Debug,Default,Eq,PartialEq)]
+pub struct UnitStats {
+    /// Acknowledged requests answered with a successful ACK.
+    acknowledged: u64,
+
+    /// Acknowledged requests dropped after the retry schedule ran out.
+    expired: u64,
+
+    /// Acknowledged requests answered with a NAK.
+    rejected: u64,
+
+    /// Packets received from the server.
+    received: u64,
+
+    /// Acknowledged requests sent again.
+    retries: u64,
+
+    /// Packets sent, excluding retries.
+    sent: u64
+}
+
+impl UnitStats {
+    /// Retrieve the count of requests answered with a successful ACK.
+    pub fn acknowledged(&self) -> u64 {
+        self.acknowledged
+    }
+
+    /// Retrieve the count of requests dropped after the retry schedule ran out.
+    pub fn expired(&self) -> u64 {
+        self.expired
+    }
+
+    /// Retrieve the count of requests answered with a NAK.
+    pub fn rejected(&self) -> u64 {
+        self.rejected
+    }
+
+    /// Retrieve the count of packets received from the server.
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    /// Retrieve the count of requests sent again.
+    pub fn retries(&self) -> u64 {
+        self.retries
+    }
+
+    /// Retrieve the count of packets sent, excluding retries.
+    pub fn sent(&self) -> u64 {
+        self.sent
+    }
+
+    /// Add the counts of `other`.
+    fn add(&mut self, other: &UnitStats) {
+        self.acknowledged += other.acknowledged;
+        self.expired      += other.expired;
+        self.rejected     += other.rejected;
+        self.received     += other.received;
+        self.retries      += other.retries;
+        self.sent         += other.sent;
+    }
+}
+
+/// Acknowledged request awaiting an ACK/NAK message.
+#[derive(Clone,Debug)]
+struct InFlight {
+    /// Number of times the request has been sent.
+    attempts: usize,
+
+    /// Encoded packet.
+    data: Vec<u8>,
+
+    /// Message type.
+    message_type: MessageType,
+
+    /// Time of the next retry.
+    next_attempt: Instant,
+
+    /// Sequence number.
+    sequence_number: u16
+}
+
+/// Simulated LMU.
+///
+/// On its first poll the unit sends an ID report, then sends an event report every report
+/// interval while moving to the next waypoint of its route, a user message every user interval,
+/// and a null keep-alive message after the keep-alive interval passes without other traffic.
+///
+/// Reports are acknowledged requests sent one at a time in order, like the LMU log: the next
+/// report waits until the previous one is acknowledged, or is dropped after the retry schedule
+/// runs out. Acknowledged requests from the server are answered with an ACK, configuration
+/// parameter messages are recorded, and unit requests for `LOCATE_REQUEST` are answered with a
+/// locate report.
+#[derive(Debug)]
+pub struct Unit {
+    /// Indicates the ID report was sent.
+    booted: bool,
+
+    /// Request awaiting acknowledgement.
+    in_flight: Option<InFlight>,
+
+    /// Time of the last sent packet.
+    last_sent: Option<Instant>,
+
+    /// Requests waiting to be sent, as message type and body.
+    log: VecDeque<(MessageType, Message)>,
+
+    /// Mobile ID.
+    mobile_id: MobileId,
+
+    /// Time of the next event report.
+    next_report: Option<Instant>,
+
+    /// Time of the next user message.
+    next_user: Option<Instant>,
+
+    /// Configuration parameter message bodies received from the server.
+    parameters: Vec<Vec<u8>>,
+
+    /// Behavior.
+    profile: Profile,
+
+    /// Route.
+    route: Arc<Route>,
+
+    /// Current waypoint index.
+    route_index: usize,
+
+    /// Last assigned sequence number.
+    sequence_number: u16,
+
+    /// Server address.
+    server: SocketAddr,
+
+    /// Bound socket.
+    socket: UdpSocket,
+
+    /// Message counts.
+    stats: UnitStats
+}
+
+impl Unit {
+    /// Create a new Unit sending to `server` from an ephemeral local port.
+    pub fn new(mobile_id: MobileId, server: SocketAddr, route: Arc<Route>, profile: Profile)
+    -> io::Result<Unit> {
+        let socket = if server.is_ipv4() {
+            UdpSocket::bind("0.0.0.0:0")?
+        } else {
+            UdpSocket::bind("[::]:0")?
+        };
+
+        socket.set_nonblocking(true)?;
+
+        Ok(Unit{
+            booted:          false,
+            in_flight:       None,
+            last_sent:       None,
+            log:             VecDeque::new(),
+            mobile_id,
+            next_report:     None,
+            next_user:       None,
+            parameters:      Vec::new(),
+            profile,
+            route,
+            route_index:     0,
+            sequence_number: 0,
+            server,
+            socket,
+            stats:           UnitStats::default()
+        })
+    }
+
+    /// Receive packets from the server, and send the messages due at time `now`.
+    pub fn poll(&mut self, now: Instant) -> io::Result<()> {
+        self.receive(now)?;
+
+        if !self.booted {
+            self.booted      = true;
+            self.next_report = Some(now + self.profile.report_interval);
+            self.next_user   = self.profile.user_interval.map(|interval| now + interval);
+
+            let report = self.id_report();
+
+            self.log.push_back((MessageType::IdReport, Message::IdReport(report)));
+        }
+
+        if self.next_report.is_some_and(|next_report| next_report <= now) {
+            let report = EventReportMessage::new(self.report_header(), 0, self.profile.event_code,
+                                                 Accumulators::new(Vec::new()));
+
+            self.log.push_back((MessageType::EventReport, Message::EventReport(report)));
+            self.next_report  = Some(now + self.profile.report_interval);
+            self.route_index += 1;
+        }
+
+        if let (Some(next_user), Some(interval)) = (self.next_user, self.profile.user_interval) {
+            if next_user <= now {
+                let message = UserMessage::new(self.report_header(), 0, 0,
+                                               format!("{} {}", self.mobile_id,
+                                                       self.stats.sent).into_bytes());
+
+                self.log.push_back((MessageType::UserData, Message::UserData(message)));
+                self.next_user = Some(now + interval);
+            }
+        }
+
+        self.send_log(now)?;
+
+        let idle = match (self.profile.keep_alive_interval, self.last_sent) {
+            (Some(interval), Some(last_sent)) => now.duration_since(last_sent) >= interval,
+            _ => false
+        };
+
+        if idle {
+            let sequence_number = self.next_sequence_number();
+
+            self.send(ServiceType::UnacknowledgedRequest, MessageType::Null, sequence_number,
+                      Message::Null(NullMessage::new()), now)?;
+        }
+
+        Ok(())
+    }
+
+    /// Retrieve the local address.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Retrieve the mobile ID.
+    pub fn mobile_id(&self) -> &MobileId {
+        &self.mobile_id
+    }
+
+    /// Retrieve the configuration parameter message bodies received from the server.
+    pub fn parameters(&self) -> &[Vec<u8>] {
+        &self.parameters
+    }
+
+    /// Retrieve the count of requests waiting to be sent or awaiting acknowledgement.
+    pub fn pending(&self) -> usize {
+        self.log.len() + self.in_flight.iter().count()
+    }
+
+    /// Retrieve the behavior.
+    pub fn profile(&self) -> &Profile {
+        &self.profile
+    }
+
+    /// Set the current waypoint index.
+    pub fn set_route_index(&mut self, route_index: usize) {
+        self.route_index = route_index;
+    }
+
+    /// Retrieve the message counts.
+    pub fn stats(&self) -> &UnitStats {
+        &self.stats
+    }
+
+    /// Build the ID report sent on boot.
+    fn id_report(&self) -> IdReportMessage {
+        let mut report = IdReportMessage::new(self.profile.script_version, [0; 3],
+                                              self.profile.application_version);
+
+        match self.mobile_id {
+            MobileId::Esn(ref esn) => report.set_esn(esn),
+            MobileId::ImeiEid(ref imei) => report.set_imei(imei),
+            MobileId::Imsi(ref imsi) => report.set_imsi(imsi),
+            _ => {}
+        }
+
+        report.set_mobile_id_type(self.mobile_id.type_value());
+        report.set_unit_status(UnitStatus::GPS_ANTENNA_OK | UnitStatus::GPS_RECEIVER_OK |
+                               UnitStatus::GPS_TRACKING);
+
+        report
+    }
+
+    /// Build a report header for the current waypoint.
+    fn report_header(&self) -> ReportHeader {
+        let now = UpdateTime::from_system_time(SystemTime::now()).map_or(0, |time| time.as_secs());
+
+        let mut header = ReportHeader::new(LmuTime::from_secs(now), LmuTime::from_secs(now),
+                                           self.route.fix(self.route_index, self.profile.speed));
+
+        header.set_comm_state(CommState::AVAILABLE | CommState::NETWORK_SERVICE |
+                              CommState::DATA_SERVICE | CommState::CONNECTED);
+        header.set_inputs(Inputs::IGNITION);
+        header.set_rssi(Rssi::new(-75));
+        header.set_unit_status(UnitStatus::GPS_ANTENNA_OK | UnitStatus::GPS_RECEIVER_OK |
+                               UnitStatus::GPS_TRACKING);
+
+        header
+    }
+
+    /// Receive and handle the packets waiting on the socket.
+    fn receive(&mut self, now: Instant) -> io::Result<()> {
+        let mut buffer = vec![0; MAX_DATAGRAM_SIZE];
+
+        loop {
+            let length = match self.socket.recv_from(&mut buffer) {
+                Ok((length, _)) => length,
+                Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(error) => return Err(error)
+            };
+
+            if let Ok((packet, _)) = Packet::parse(&buffer[..length]) {
+                self.stats.received += 1;
+
+                self.handle(&packet, now)?;
+            }
+        }
+    }
+
+    /// Handle a packet from the server.
+    fn handle(&mut self, packet: &Packet, now: Instant) -> io::Result<()> {
+        let header = packet.message_header();
+
+        match *packet.message() {
+            Message::AckNak(ref ack) => {
+                let matched = self.in_flight.as_ref().is_some_and(|in_flight| {
+                    in_flight.sequence_number == header.sequence_number() &&
+                    in_flight.message_type == *ack.message_type()
+                });
+
+                if matched {
+                    self.in_flight = None;
+
+                    if *ack.ack() == AcknowledgementType::Successful {
+                        self.stats.acknowledged += 1;
+                    } else {
+                        self.stats.rejected += 1;
+                    }
+
+                    self.send_log(now)?;
+                }
+
+                return Ok(());
+            },
+            Message::Raw(ref data) if *header.message_type() == MessageType::UnitRequest &&
+                                      data.first() == Some(&LOCATE_REQUEST) => {
+                let report = LocateReportMessage::new(self.report_header(), 0, 0,
+                                                      Accumulators::new(Vec::new()));
+
+                self.log.push_front((MessageType::LocateReport, Message::LocateReport(report)));
+            },
+            Message::Raw(ref data) if *header.message_type() ==
+                                      MessageType::ConfigurationParameter => {
+                self.parameters.push(data.clone());
+            },
+            _ => {}
+        }
+
+        if *header.service_type() == ServiceType::AcknowledgedRequest {
+            let ack = AcknowledgementMessage::new(*header.message_type(),
+                                                  AcknowledgementType::Successful,
+                                                  self.profile.application_version);
+
+            self.send(ServiceType::Response, MessageType::AckNak, header.sequence_number(),
+                      Message::AckNak(ack), now)?;
+        }
+
+        self.send_log(now)
+    }
+
+    /// Send the next logged request when none is awaiting acknowledgement, and resend or drop
+    /// the request awaiting acknowledgement when its retry delay has passed at time `now`.
+    fn send_log(&mut self, now: Instant) -> io::Result<()> {
+        if let Some(ref mut in_flight) = self.in_flight {
+            if in_flight.next_attempt > now {
+                return Ok(());
+            }
+
+            if in_flight.attempts < self.profile.retry_schedule.len() {
+                self.socket.send_to(&in_flight.data, self.server)?;
+
+                in_flight.next_attempt  = now + self.profile.retry_schedule[in_flight.attempts];
+                in_flight.attempts     += 1;
+                self.stats.retries     += 1;
+                self.last_sent          = Some(now);
+
+                return Ok(());
+            }
+
+            self.stats.expired += 1;
+        }
+
+        self.in_flight = None;
+
+        if let Some((message_type, message)) = self.log.pop_front() {
+            let sequence_number = self.next_sequence_number();
+            let data            = self.send(ServiceType::AcknowledgedRequest, message_type,
+                                            sequence_number, message, now)?;
+
+            self.in_flight = Some(InFlight{
+                attempts:        1,
+                data,
+                message_type,
+                next_attempt:    now + self.profile.retry_schedule.first()
+                                                                  .cloned()
+                                                                  .unwrap_or_default(),
+                sequence_number
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Build and send a packet.
+    ///
+    /// Returns the encoded packet.
+    fn send(&mut self, service_type: ServiceType, message_type: MessageType,
+            sequence_number: u16, message: Message, now: Instant) -> io::Result<Vec<u8>> {
+        let mut options_header = OptionsHeader::new();
+
+        options_header.set_mobile_id(Some(self.mobile_id.clone()));
+
+        let mut data = Vec::new();
+
+        Packet::new(options_header,
+                    MessageHeader::new(service_type, message_type, sequence_number),
+                    message).encode(&mut data);
+
+        self.socket.send_to(&data, self.server)?;
+
+        self.last_sent   = Some(now);
+        self.stats.sent += 1;
+
+        Ok(data)
+    }
+
+    /// Assign the next sequence number.
+    fn next_sequence_number(&mut self) -> u16 {
+        self.sequence_number = self.sequence_number.wrapping_add(1);
+
+        self.sequence_number
+    }
+}
+
+/// Group of simulated units.
+#[derive(Debug,Default)]
+pub struct Simulator {
+    /// Units.
+    units: Vec<Unit>
+}
+
+impl Simulator {
+    /// Create a new empty Simulator.
+    pub fn new() -> Simulator {
+        Simulator::default()
+    }
+
+    /// Create a new Simulator with `count` units sending to `server`, identified by consecutive
+    /// ESNs starting at `first_esn`.
+    ///
+    /// Units are spread along the route so they do not all report the same position.
+    pub fn spawn(count: usize, first_esn: u64, server: SocketAddr, route: Arc<Route>,
+                 profile: Profile) -> io::Result<Simulator> {
+        let mut simulator = Simulator::new();
+        let waypoints     = route.waypoints().len();
+
+        for n in 0..count {
+            let mut unit = Unit::new(MobileId::Esn((first_esn + n as u64).to_string()), server,
+                                     route.clone(), profile.clone())?;
+
+            unit.set_route_index(n * waypoints / count.max(1));
+
+            simulator.push(unit);
+        }
+
+        Ok(simulator)
+    }
+
+    /// Add a unit.
+    pub fn push(&mut self, unit: Unit) {
+        self.units.push(unit);
+    }
+
+    /// Poll every unit at time `now`.
+    pub fn poll(&mut self, now: Instant) -> io::Result<()> {
+        for unit in &mut self.units {
+            unit.poll(now)?;
+        }
+
+        Ok(())
+    }
+
+    /// Poll every unit every `tick` until `duration` passes.
+    pub fn run(&mut self, duration: Duration, tick: Duration) -> io::Result<()> {
+        let end = Instant::now() + duration;
+
+        loop {
+            let now = Instant::now();
+
+            if now >= end {
+                return Ok(());
+            }
+
+            self.poll(now)?;
+
+            thread::sleep(tick.min(end - now));
+        }
+    }
+
+    /// Retrieve the message counts summed across all units.
+    pub fn stats(&self) -> UnitStats {
+        let mut stats = UnitStats::default();
+
+        for unit in &self.units {
+            stats.add(unit.stats());
+        }
+
+        stats
+    }
+
+    /// Retrieve the units.
+    pub fn units(&self) -> &[Unit] {
+        &self.units
+    }
+
+    /// Retrieve the mutable units.
+    pub fn units_mut(&mut self) -> &mut [Unit] {
+        &mut self.units
+    }
+}
+
+/// Retrieve a numeric attribute value from within an element tag.
+fn attribute(tag: &str, name: &str) -> Option<f64> {
+    [format!(" {}=\"", name), format!(" {}='", name)].iter().find_map(|prefix| {
+        let start = tag.find(prefix.as_str())? + prefix.len();
+        let quote = prefix.chars().last()?;
+        let end   = tag[start..].find(quote)?;
+
+        tag[start..start + end].trim().parse().ok()
+    })
+}
@@ -0,0 +1,120 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+extern crate calamp;
+
+#[cfg(feature = "toml")]
+use calamp::ConfigError;
+use calamp::accumulators::*;
+
+#[test]
+fn accumulators_round_trip() {
+    let data = [0x42, 0x00, 0x00, 0x00, 0x32, 0x3B, 0x00, 0x00, 0x0E, 0x10];
+
+    let (accumulators, byte_count) = Accumulators::parse(&data).unwrap();
+
+    assert_eq!(byte_count, data.len());
+    assert_eq!(accumulators.accumulator_type(), 1);
+    assert_eq!(accumulators.values(), &[12859, 3600]);
+
+    let mut encoded = Vec::new();
+
+    accumulators.encode(&mut encoded);
+
+    assert_eq!(encoded, data);
+}
+
+#[test]
+fn accumulators_count_limit() {
+    let accumulators = Accumulators::new((0..70).collect());
+    let mut encoded  = Vec::new();
+
+    assert_eq!(accumulators.len(), MAX_ACCUMULATORS);
+
+    accumulators.encode(&mut encoded);
+
+    let (parsed, byte_count) = Accumulators::parse(&encoded).unwrap();
+
+    assert_eq!(byte_count, encoded.len());
+    assert_eq!(parsed, accumulators);
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn accumulators_resolve() {
+    let schema = AccumulatorSchema::from_toml("# odometer in meters, shown in km\n\
+                                               [[accumulator]]\n\
+                                               index = 0\n\
+                                               name  = \"odometer\"\n\
+                                               kind  = \"distance\"\n\
+                                               unit  = \"km\"\n\
+                                               scale = 0.001\n\
+                                               \n\
+                                               [[accumulator]]\n\
+                                               index = 2\n\
+                                               name  = \"engine hours\"\n\
+                                               kind  = \"timer\"\n\
+                                               unit  = \"h\"\n\
+                                               scale = 0.000277777777777778\n\
+                                               \n\
+                                               [[accumulator]]\n\
+                                               index = 3\n\
+                                               name  = \"ignitions\"\n\
+                                               kind  = \"counter\"\n").unwrap();
+
+    let accumulators = Accumulators::new(vec![12859, 42, 3600, 7]);
+    let values       = accumulators.resolve(&schema);
+
+    assert_eq!(values.len(), 3);
+    assert_eq!(values[0].name(), "odometer");
+    assert_eq!(values[0].definition().kind(), AccumulatorKind::Distance);
+    assert!((values[0].value() - 12.859).abs() < 1e-9);
+    assert_eq!(values[1].index(), 2);
+    assert!((values[1].value() - 1.0).abs() < 1e-9);
+    assert_eq!(values[2].raw(), 7);
+    assert_eq!(values[2].to_string(), "ignitions: 7");
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn accumulator_schema_json() {
+    let schema = AccumulatorSchema::from_json(r#"{"accumulator": [
+        {"index": 1, "name": "idle", "kind": "timer", "unit": "s"}
+    ]}"#).unwrap();
+
+    assert_eq!(schema.get(1).unwrap().name(), "idle");
+    assert_eq!(schema.get(1).unwrap().unit(), "s");
+    assert!((schema.get(1).unwrap().scale() - 1.0).abs() < 1e-9);
+    assert!(schema.get(0).is_none());
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn accumulator_schema_errors() {
+    match AccumulatorSchema::from_toml("[[accumulator]]\nindex = 0\nname = \"fuel\"\n\
+                                        kind = \"liters\"\n") {
+        Err(ConfigError::Line(line, _)) => assert_eq!(line, 4),
+        _ => panic!("Unknown accumulator kind was accepted")
+    }
+
+    match AccumulatorSchema::from_toml("[[accumulator]]\nindex = \"x\"\nname = \"odometer\"\n\
+                                        kind = \"distance\"\n") {
+        Err(ConfigError::Line(line, _)) => assert_eq!(line, 2),
+        _ => panic!("Invalid index was accepted")
+    }
+}
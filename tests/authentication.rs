@@ -0,0 +1,267 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+extern crate calamp;
+
+use std::net::{SocketAddr, UdpSocket};
+
+#[cfg(feature = "toml")]
+use std::env;
+#[cfg(feature = "toml")]
+use std::fs;
+
+#[cfg(feature = "toml")]
+use calamp::ConfigError;
+use calamp::authentication::*;
+use calamp::message::Message;
+use calamp::message::acknowledgement::AcknowledgementType;
+use calamp::message::null::NullMessage;
+use calamp::message_header::*;
+use calamp::options_header::*;
+use calamp::packet::Packet;
+use calamp::server::{Handler, Server};
+
+fn esn() -> MobileId {
+    MobileId::Esn("4641143898".to_string())
+}
+
+fn options(mobile_id: Option<MobileId>, authentication: Option<Vec<u8>>) -> OptionsHeader {
+    let mut options = OptionsHeader::new();
+
+    options.set_mobile_id(mobile_id);
+    options.set_authentication(authentication);
+    options
+}
+
+#[test]
+fn authentication_verify() {
+    let mut store = MemoryCredentialStore::new();
+
+    store.insert(esn(), vec![1, 2, 3, 4]);
+
+    assert_eq!(store.verify(&options(Some(esn()), Some(vec![1, 2, 3, 4]))), Ok(()));
+    assert_eq!(store.verify(&options(Some(esn()), Some(vec![1, 2, 3, 5]))),
+               Err(AuthenticationError::Mismatch));
+    assert_eq!(store.verify(&options(Some(esn()), Some(vec![1, 2, 3]))),
+               Err(AuthenticationError::Mismatch));
+    assert_eq!(store.verify(&options(Some(esn()), None)),
+               Err(AuthenticationError::MissingAuthentication));
+    assert_eq!(store.verify(&options(None, Some(vec![1, 2, 3, 4]))),
+               Err(AuthenticationError::MissingMobileId));
+
+    let other = MobileId::Esn("1".to_string());
+
+    assert_eq!(store.verify(&options(Some(other.clone()), Some(vec![1, 2, 3, 4]))),
+               Err(AuthenticationError::UnknownMobileId(other)));
+    assert_eq!(AuthenticationError::Mismatch.ack(), AcknowledgementType::FailedAuthentication);
+}
+
+#[test]
+fn authentication_authenticate() {
+    let mut store = MemoryCredentialStore::new();
+
+    store.insert(esn(), vec![9, 8, 7]);
+
+    let mut outbound = options(Some(esn()), None);
+
+    store.authenticate(&mut outbound).unwrap();
+
+    assert_eq!(*outbound.authentication(), Some(vec![9, 8, 7]));
+    assert_eq!(store.verify(&outbound), Ok(()));
+
+    // the authentication field survives encoding
+    let mut buffer = Vec::new();
+
+    outbound.encode(&mut buffer);
+
+    assert_eq!(store.verify(&OptionsHeader::parse(&buffer).unwrap().0), Ok(()));
+
+    let mut outbound = options(None, None);
+
+    assert_eq!(store.authenticate(&mut outbound), Err(AuthenticationError::MissingMobileId));
+
+    assert_eq!(store.remove(&esn()), Some(vec![9, 8, 7]));
+    assert!(store.is_empty());
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn authentication_toml() {
+    let store = MemoryCredentialStore::from_toml("# fleet units\n\
+                                                  [[credential]]\n\
+                                                  mobile_id      = \"esn:4641143898\"\n\
+                                                  authentication = \"0a1B2c3d\"\n\
+                                                  \n\
+                                                  [[credential]]\n\
+                                                  mobile_id      = \"ip:10.0.0.1\"\n\
+                                                  authentication = \"ff\"\n\
+                                                  \n\
+                                                  [[credential]]\n\
+                                                  mobile_id      = \"user:0102\"\n\
+                                                  authentication = \"00\"\n").unwrap();
+
+    assert_eq!(store.len(), 3);
+    assert_eq!(store.credential(&esn()), Some(vec![0x0A, 0x1B, 0x2C, 0x3D]));
+    assert_eq!(store.credential(&MobileId::IpAddress("10.0.0.1".to_string())), Some(vec![0xFF]));
+    assert_eq!(store.credential(&MobileId::User(vec![1, 2])), Some(vec![0]));
+
+    for (mobile_id, authentication) in &[("esn:1", "zz"), ("esn:x", "00"), ("serial:1", "00"),
+                                         ("esn:1", "000")] {
+        let text = format!("[[credential]]\nmobile_id = \"{}\"\nauthentication = \"{}\"\n",
+                           mobile_id, authentication);
+
+        match MemoryCredentialStore::from_toml(&text) {
+            Err(ConfigError::Invalid(_)) => {},
+            x => panic!("unexpected result for {:?}: {:?}", text, x)
+        }
+    }
+
+    match MemoryCredentialStore::from_toml("[[credential]]\nmobile_id = \"esn:1\"\n") {
+        Err(ConfigError::Line(n, _)) => assert_eq!(n, 1),
+        x => panic!("unexpected result: {:?}", x)
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn authentication_json() {
+    let store = MemoryCredentialStore::from_json(r#"{"credential": [
+        {"mobile_id": "esn:4641143898", "authentication": "01"}
+    ]}"#).unwrap();
+
+    assert_eq!(store.credential(&esn()), Some(vec![1]));
+}
+
+#[test]
+fn authentication_mobile_id_from_str() {
+    assert_eq!("esn:4641143898".parse::<MobileId>(), Ok(esn()));
+    assert_eq!("imei:352099001761481".parse::<MobileId>(),
+               Ok(MobileId::ImeiEid("352099001761481".to_string())));
+    assert_eq!("imsi:310150123456789".parse::<MobileId>(),
+               Ok(MobileId::Imsi("310150123456789".to_string())));
+    assert_eq!("phone:5551234".parse::<MobileId>(), Ok(MobileId::Phone("5551234".to_string())));
+    assert!("esn".parse::<MobileId>().is_err());
+    assert!("esn:12a".parse::<MobileId>().is_err());
+    assert!("ip:10.0.0".parse::<MobileId>().is_err());
+    assert!("user:1".parse::<MobileId>().is_err());
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn authentication_file_store() {
+    let path = env::temp_dir().join(format!("calamp-credentials-{}.toml", std::process::id()));
+    let entry = |authentication: &str| {
+        format!("[[credential]]\nmobile_id = \"esn:4641143898\"\nauthentication = \"{}\"\n",
+                authentication)
+    };
+
+    fs::write(&path, entry("01")).unwrap();
+
+    let mut store = FileCredentialStore::open(&path).unwrap();
+
+    assert_eq!(store.path(), path.as_path());
+    assert_eq!(store.credential(&esn()), Some(vec![1]));
+
+    fs::write(&path, entry("02")).unwrap();
+    store.reload().unwrap();
+
+    assert_eq!(store.credential(&esn()), Some(vec![2]));
+
+    // a failed reload keeps the current credentials
+    fs::write(&path, entry("nothex")).unwrap();
+
+    assert!(store.reload().is_err());
+    assert_eq!(store.credential(&esn()), Some(vec![2]));
+
+    fs::remove_file(&path).unwrap();
+
+    match FileCredentialStore::open(&path) {
+        Err(ConfigError::Io(_)) => {},
+        x => panic!("unexpected result: {:?}", x)
+    }
+}
+
+struct Recorder {
+    handled: usize,
+    rejected: Vec<AuthenticationError>
+}
+
+impl Handler for Recorder {
+    fn handle(&mut self, _packet: &Packet, _peer: SocketAddr) -> AcknowledgementType {
+        self.handled += 1;
+
+        AcknowledgementType::Successful
+    }
+
+    fn rejected(&mut self, _packet: &Packet, error: AuthenticationError, _peer: SocketAddr) {
+        self.rejected.push(error);
+    }
+}
+
+#[test]
+fn authentication_server() {
+    let mut store = MemoryCredentialStore::new();
+
+    store.insert(esn(), vec![1, 2, 3, 4]);
+
+    let mut server = Server::bind("127.0.0.1:0", Recorder{ handled: 0, rejected: Vec::new() })
+                            .unwrap();
+
+    server.set_credential_store(Some(Box::new(store)));
+
+    let unit = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+    for authentication in &[vec![1, 2, 3, 4], vec![6, 6, 6, 6]] {
+        let verified    = authentication[0] == 1;
+        let mut buffer  = Vec::new();
+        let mut options = options(Some(esn()), Some(authentication.clone()));
+
+        if !verified {
+            // forged packets are answered at the sender, not at the redirection
+            options.set_redirection(Some(("127.0.0.1".to_string(), 9)));
+        }
+
+        Packet::new(options,
+                    MessageHeader::new(ServiceType::AcknowledgedRequest, MessageType::Null, 1),
+                    Message::Null(NullMessage::new())).encode(&mut buffer);
+
+        unit.send_to(&buffer, server.local_addr().unwrap()).unwrap();
+        server.serve_once().unwrap();
+
+        let mut buffer  = [0; 512];
+        let (length, _) = unit.recv_from(&mut buffer).unwrap();
+        let ack         = Packet::parse(&buffer[..length]).unwrap().0;
+
+        let (authentication, expected) = if verified {
+            (Some(vec![1, 2, 3, 4]), AcknowledgementType::Successful)
+        } else {
+            (None, AcknowledgementType::FailedAuthentication)
+        };
+
+        // only responses to verified packets carry the unit's authentication field
+        assert_eq!(*ack.options_header().authentication(), authentication);
+
+        match *ack.message() {
+            Message::AckNak(ref message) => assert_eq!(*message.ack(), expected),
+            ref x => panic!("unexpected message: {:?}", x)
+        }
+    }
+
+    assert_eq!(server.handler().handled, 1);
+    assert_eq!(server.handler().rejected, [AuthenticationError::Mismatch]);
+}
@@ -0,0 +1,241 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+#![cfg(feature = "pcap")]
+
+extern crate calamp;
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::net::SocketAddr;
+use std::time::{Duration, UNIX_EPOCH};
+
+use calamp::CalAmpError;
+use calamp::capture::{CaptureError, CaptureReader, Transport};
+use calamp::message_header::MessageType;
+
+fn sample() -> Vec<u8> {
+    let mut v = Vec::new();
+
+    File::open("tests/sample/message1.bin").unwrap()
+                                           .read_to_end(&mut v)
+                                           .unwrap();
+
+    v
+}
+
+fn ipv4(protocol: u8, source: [u8; 4], destination: [u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut v = vec![0x45, 0];
+
+    v.extend_from_slice(&(20 + payload.len() as u16).to_be_bytes());
+    v.extend_from_slice(&[0, 0, 0x40, 0, 64, protocol, 0, 0]);
+    v.extend_from_slice(&source);
+    v.extend_from_slice(&destination);
+    v.extend_from_slice(payload);
+    v
+}
+
+fn udp(source: u16, destination: u16, payload: &[u8]) -> Vec<u8> {
+    let mut v = Vec::new();
+
+    v.extend_from_slice(&source.to_be_bytes());
+    v.extend_from_slice(&destination.to_be_bytes());
+    v.extend_from_slice(&(8 + payload.len() as u16).to_be_bytes());
+    v.extend_from_slice(&[0, 0]);
+    v.extend_from_slice(payload);
+    v
+}
+
+fn tcp(source: u16, destination: u16, sequence: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+    let mut v = Vec::new();
+
+    v.extend_from_slice(&source.to_be_bytes());
+    v.extend_from_slice(&destination.to_be_bytes());
+    v.extend_from_slice(&sequence.to_be_bytes());
+    v.extend_from_slice(&[0, 0, 0, 0, 0x50, flags, 0xFF, 0xFF, 0, 0, 0, 0]);
+    v.extend_from_slice(payload);
+    v
+}
+
+fn ethernet(ip: &[u8]) -> Vec<u8> {
+    let mut v = vec![0; 12];
+
+    v.extend_from_slice(&[0x81, 0x00, 0x00, 0x01, 0x08, 0x00]);
+    v.extend_from_slice(ip);
+    v
+}
+
+fn pcap(link_type: u32, frames: &[(u32, u32, Vec<u8>)]) -> Vec<u8> {
+    let mut v = Vec::new();
+
+    v.extend_from_slice(&0xA1B2_C3D4u32.to_le_bytes());
+    v.extend_from_slice(&[2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xFF, 0xFF, 0, 0]);
+    v.extend_from_slice(&link_type.to_le_bytes());
+
+    for &(seconds, micros, ref data) in frames {
+        v.extend_from_slice(&seconds.to_le_bytes());
+        v.extend_from_slice(&micros.to_le_bytes());
+        v.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        v.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        v.extend_from_slice(data);
+    }
+
+    v
+}
+
+fn block(block_type: u32, body: &[u8]) -> Vec<u8> {
+    let length = 12 + ((body.len() + 3) & !3) as u32;
+    let mut v  = Vec::new();
+
+    v.extend_from_slice(&block_type.to_be_bytes());
+    v.extend_from_slice(&length.to_be_bytes());
+    v.extend_from_slice(body);
+    v.resize(length as usize - 4, 0);
+    v.extend_from_slice(&length.to_be_bytes());
+    v
+}
+
+fn sll(ip: &[u8]) -> Vec<u8> {
+    let mut v = vec![0; 14];
+
+    v.extend_from_slice(&[0x08, 0x00]);
+    v.extend_from_slice(ip);
+    v
+}
+
+#[test]
+fn capture_pcap_udp() {
+    let data    = sample();
+    let lmu     = udp(20500, 20500, &data);
+    let other   = udp(5000, 53, &data);
+    let capture = pcap(1, &[(1_449_602_011, 250_000, ethernet(&ipv4(17, [10, 0, 0, 1],
+                                                                      [192, 168, 1, 2],
+                                                                      &lmu))),
+                            (1_449_602_012, 0, ethernet(&ipv4(17, [10, 0, 0, 1],
+                                                              [192, 168, 1, 2], &other)))]);
+
+    let packets = CaptureReader::new().read(&capture).unwrap();
+
+    assert_eq!(packets.len(), 1);
+    assert_eq!(packets[0].transport(), Transport::Udp);
+    assert_eq!(packets[0].source(), "10.0.0.1:20500".parse::<SocketAddr>().unwrap());
+    assert_eq!(packets[0].destination(), "192.168.1.2:20500".parse::<SocketAddr>().unwrap());
+    assert_eq!(packets[0].timestamp(), UNIX_EPOCH + Duration::from_millis(1_449_602_011_250));
+    assert_eq!(packets[0].payload(), &data[..]);
+    assert_eq!(*packets[0].packet().unwrap().message_header().message_type(),
+               MessageType::EventReport);
+
+    let packets = CaptureReader::with_ports(vec![53]).read(&capture).unwrap();
+
+    assert_eq!(packets.len(), 1);
+    assert_eq!(packets[0].destination().port(), 53);
+}
+
+#[test]
+fn capture_pcap_tcp_reassembly() {
+    let data   = sample();
+    let mut stream = data.clone();
+
+    stream.extend_from_slice(&data);
+
+    // SYN, then the two back to back packets split into three out of order segments
+    let segments = [(1000, 0x02, Vec::new()),
+                    (1001 + 50, 0x18, stream[50..100].to_vec()),
+                    (1001, 0x18, stream[..50].to_vec()),
+                    (1001 + 40, 0x18, stream[40..60].to_vec()),
+                    (1001 + 100, 0x18, stream[100..].to_vec())];
+
+    let frames: Vec<(u32, u32, Vec<u8>)> = segments.iter().enumerate().map(|(n, segment)| {
+        (100 + n as u32, 0, ipv4(6, [10, 0, 0, 1], [10, 0, 0, 2],
+                                 &tcp(40000, 20500, segment.0, segment.1, &segment.2)))
+    }).collect();
+
+    let packets = CaptureReader::new().read(&pcap(101, &frames)).unwrap();
+
+    assert_eq!(packets.len(), 2);
+
+    for packet in &packets {
+        assert_eq!(packet.transport(), Transport::Tcp);
+        assert_eq!(packet.payload(), &data[..]);
+        assert!(packet.packet().is_some());
+    }
+
+    assert_eq!(packets[0].timestamp(), UNIX_EPOCH + Duration::from_secs(102));
+    assert_eq!(packets[1].timestamp(), UNIX_EPOCH + Duration::from_secs(104));
+}
+
+#[test]
+fn capture_pcap_tcp_incomplete() {
+    let data   = sample();
+    let frames = vec![(1, 0, ipv4(6, [10, 0, 0, 1], [10, 0, 0, 2],
+                                  &tcp(40000, 20500, 7, 0x18, &data[..30])))];
+
+    let packets = CaptureReader::new().read(&pcap(101, &frames)).unwrap();
+
+    assert_eq!(packets.len(), 1);
+    assert_eq!(packets[0].payload(), &data[..30]);
+
+    match packets[0].error() {
+        Some(&CalAmpError::Eos) => {},
+        x => panic!("unexpected error: {:?}", x)
+    }
+}
+
+#[test]
+fn capture_pcapng() {
+    let data = sample();
+
+    let mut capture = block(0x0A0D_0D0A, &[0x1A, 0x2B, 0x3C, 0x4D, 0, 1, 0, 0, 0xFF, 0xFF, 0xFF,
+                                           0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+
+    // Linux cooked capture interface with nanosecond timestamps
+    capture.extend(block(1, &[0, 113, 0, 0, 0, 0, 0xFF, 0xFF, 0, 9, 0, 1, 9, 0, 0, 0, 0, 0, 0,
+                              0]));
+
+    let frame     = sll(&ipv4(17, [10, 0, 0, 1], [10, 0, 0, 2], &udp(20500, 20500, &data)));
+    let timestamp = 1_449_602_011_123_456_789u64;
+
+    let mut body = vec![0, 0, 0, 0];
+
+    body.extend_from_slice(&((timestamp >> 32) as u32).to_be_bytes());
+    body.extend_from_slice(&(timestamp as u32).to_be_bytes());
+    body.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+    body.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+    body.extend_from_slice(&frame);
+
+    capture.extend(block(6, &body));
+
+    let packets = CaptureReader::new().read(&capture).unwrap();
+
+    assert_eq!(packets.len(), 1);
+    assert_eq!(packets[0].payload(), &data[..]);
+    assert_eq!(packets[0].timestamp(), UNIX_EPOCH + Duration::from_nanos(timestamp));
+}
+
+#[test]
+fn capture_invalid() {
+    match CaptureReader::new().read(b"not a capture") {
+        Err(CaptureError::Format(_)) => {},
+        x => panic!("unexpected result: {:?}", x)
+    }
+
+    match CaptureReader::new().load("tests/sample/missing.pcap") {
+        Err(CaptureError::Io(_)) => {},
+        x => panic!("unexpected result: {:?}", x)
+    }
+}
@@ -0,0 +1,171 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+extern crate calamp;
+
+mod common;
+
+use calamp::{CalAmpError, ParseOptions};
+use calamp::cipher::{Cipher, CipherKey, Rc4Cipher};
+use calamp::message::Message;
+use calamp::options_header::*;
+use calamp::packet::Packet;
+
+fn sample() -> Packet {
+    Packet::parse(&common::message1()).unwrap().0
+}
+
+fn encrypted(encryption_type: EncryptionType) -> Packet {
+    let packet        = sample();
+    let mut options   = packet.options_header().clone();
+    let mut extension = OptionExtension::new();
+
+    extension.set_encryption_service(Some((encryption_type, [1, 2, 3, 4])));
+    options.set_extension(Some(extension));
+
+    Packet::new(options, packet.message_header().clone(), packet.message().clone())
+}
+
+#[test]
+fn cipher_encryption_type() {
+    for value in 0..4 {
+        assert_eq!(EncryptionType::from_u8(value).unwrap().as_u8(), value);
+    }
+
+    match EncryptionType::from_u8(4) {
+        Err(CalAmpError::EncryptionType(4)) => {},
+        x => panic!("unexpected result: {:?}", x)
+    }
+}
+
+/// Assert that `Rc4Cipher` turns `plain` into `encrypted` and back.
+fn keystream(identifier: &[u8], random_key: [u8; 4], plain: &[u8], encrypted: &[u8]) {
+    let key      = CipherKey::new(EncryptionType::Esn, identifier.to_vec(), random_key);
+    let mut data = plain.to_vec();
+
+    Rc4Cipher.encrypt(&key, &mut data);
+
+    assert_eq!(data, encrypted);
+
+    Rc4Cipher.decrypt(&key, &mut data);
+
+    assert_eq!(data, plain);
+}
+
+#[test]
+fn cipher_rc4_keystream() {
+    // RC4 test vectors, keyed by the identifier followed by the random key
+    keystream(b"", *b"Wiki", b"pedia", &[0x10, 0x21, 0xBF, 0x04, 0x20]);
+    keystream(b"Se", *b"cret", b"Attack at dawn",
+              &[0x45, 0xA0, 0x1F, 0x64, 0x5F, 0xC3, 0x5B, 0x38, 0x35, 0x52, 0x54, 0x4B, 0x9B,
+                0xF5]);
+}
+
+#[test]
+fn cipher_key() {
+    let key = CipherKey::from_options_header(encrypted(EncryptionType::Esn).options_header())
+                        .unwrap()
+                        .unwrap();
+
+    assert_eq!(key.encryption_type(), EncryptionType::Esn);
+    assert_eq!(key.identifier(), &[0x46, 0x41, 0x14, 0x38, 0x98]);
+    assert_eq!(key.random_key(), [1, 2, 3, 4]);
+
+    let key = CipherKey::from_options_header(encrypted(EncryptionType::MobileId).options_header())
+                        .unwrap()
+                        .unwrap();
+
+    assert_eq!(key.identifier(), &[0x46, 0x41, 0x14, 0x38, 0x98]);
+
+    // the options extension ESN takes precedence over the mobile ID
+    let mut options   = OptionsHeader::new();
+    let mut extension = OptionExtension::new();
+
+    extension.set_esn(Some("123456".to_string()));
+    extension.set_encryption_service(Some((EncryptionType::Esn, [0; 4])));
+    options.set_mobile_id(Some(MobileId::Esn("4641143898".to_string())));
+    options.set_extension(Some(extension));
+
+    let key = CipherKey::from_options_header(&options).unwrap().unwrap();
+
+    assert_eq!(key.identifier(), &[0x12, 0x34, 0x56]);
+
+    // no IMEI is available
+    match CipherKey::from_options_header(encrypted(EncryptionType::ImeiMeid).options_header()) {
+        Err(CalAmpError::EncryptionIdentifier(EncryptionType::ImeiMeid)) => {},
+        x => panic!("unexpected result: {:?}", x)
+    }
+
+    assert!(CipherKey::from_options_header(sample().options_header()).unwrap().is_none());
+    assert!(CipherKey::from_options_header(encrypted(EncryptionType::Unencrypted)
+                                               .options_header()).unwrap().is_none());
+}
+
+#[test]
+fn cipher_round_trip() {
+    let packet    = encrypted(EncryptionType::Esn);
+    let mut plain = Vec::new();
+    let mut data  = Vec::new();
+
+    packet.encode(&mut plain);
+    packet.encode_with_cipher(&Rc4Cipher, &mut data).unwrap();
+
+    assert_eq!(plain.len(), data.len());
+    assert_ne!(plain, data);
+
+    // headers are sent in the clear
+    assert_eq!(plain[..21], data[..21]);
+
+    let (parsed, byte_count) = Packet::parse(&data).unwrap();
+
+    assert!(parsed.options_header().is_encrypted());
+    assert_eq!(byte_count, data.len());
+
+    match *parsed.message() {
+        Message::Raw(ref body) => assert_eq!(&body[..], &data[21..]),
+        ref x => panic!("unexpected message: {:?}", x)
+    }
+
+    let (parsed, byte_count) = Packet::parse_with_cipher(&data, &Rc4Cipher, &ParseOptions::strict())
+                                   .unwrap();
+
+    assert_eq!(byte_count, data.len());
+    assert_eq!(parsed.message().report_header().unwrap().gps_fix().latitude_raw(), 331031058);
+
+    let mut encoded = Vec::new();
+
+    parsed.encode(&mut encoded);
+
+    assert_eq!(encoded, plain);
+}
+
+#[test]
+fn cipher_unencrypted() {
+    let packet   = encrypted(EncryptionType::Unencrypted);
+    let mut data = Vec::new();
+
+    packet.encode_with_cipher(&Rc4Cipher, &mut data).unwrap();
+
+    let mut plain = Vec::new();
+
+    packet.encode(&mut plain);
+
+    assert_eq!(data, plain);
+    assert!(!packet.options_header().is_encrypted());
+    assert!(Packet::parse(&data).unwrap().0.message().report_header().is_some());
+}
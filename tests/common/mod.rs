@@ -0,0 +1,49 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+#![allow(dead_code)]
+
+use std::fs::File;
+use std::io::prelude::*;
+
+use calamp::util::decode_hex;
+
+/// Read the packets of `tests/sample/corpus.hex`, one hex packet per line.
+pub fn corpus() -> Vec<Vec<u8>> {
+    let mut text = String::new();
+
+    File::open("tests/sample/corpus.hex").unwrap()
+                                         .read_to_string(&mut text)
+                                         .unwrap();
+
+    text.lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| decode_hex(line).unwrap())
+        .collect()
+}
+
+/// Read the event report of `tests/sample/message1.bin`.
+pub fn message1() -> Vec<u8> {
+    let mut v = Vec::new();
+
+    File::open("tests/sample/message1.bin").unwrap()
+                                           .read_to_end(&mut v)
+                                           .unwrap();
+
+    v
+}
@@ -0,0 +1,152 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::process::{Command, Stdio};
+
+fn sample() -> Vec<u8> {
+    let mut v = Vec::new();
+
+    File::open("tests/sample/message1.bin").unwrap()
+                                           .read_to_end(&mut v)
+                                           .unwrap();
+
+    v
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn decode(args: &[&str], stdin: &[u8]) -> (i32, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_calamp-decode")).args(args)
+                                                                   .stdin(Stdio::piped())
+                                                                   .stdout(Stdio::piped())
+                                                                   .stderr(Stdio::null())
+                                                                   .spawn()
+                                                                   .unwrap();
+
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+
+    let output = child.wait_with_output().unwrap();
+
+    (output.status.code().unwrap(), String::from_utf8(output.stdout).unwrap())
+}
+
+#[test]
+fn decode_file() {
+    let (code, stdout) = decode(&["tests/sample/message1.bin"], &[]);
+
+    assert_eq!(code, 0);
+    assert!(stdout.contains("4641143898"));
+    assert!(stdout.contains("EventReport"));
+}
+
+#[test]
+fn decode_stdin() {
+    let (code, stdout) = decode(&[], &sample());
+
+    assert_eq!(code, 0);
+    assert!(stdout.contains("AcknowledgedRequest"));
+}
+
+#[test]
+fn decode_hex() {
+    let hex = to_hex(&sample());
+
+    let (code, stdout) = decode(&["--hex", &hex], &[]);
+
+    assert_eq!(code, 0);
+    assert!(stdout.contains("EventReport"));
+
+    let lines = format!("{}\n\n0x{}\n", hex, hex.to_uppercase());
+
+    let (code, stdout) = decode(&["--format", "hex", "-"], lines.as_bytes());
+
+    assert_eq!(code, 0);
+    assert!(stdout.contains("Packet -#1") && stdout.contains("Packet -#2"));
+}
+
+#[test]
+fn decode_base64() {
+    let packet = "gwVGQRQ4mAEBAQIAAQAAAAVWZyvbE7siEronOkMAAAAAAAAAAAAAAGQAAP+PAAAfCAANAwAAADI7AAAAAAAAAAA=";
+
+    let (code, stdout) = decode(&["--base64", packet], &[]);
+
+    assert_eq!(code, 0);
+    assert!(stdout.contains("EventReport"));
+
+    let (code, stdout) = decode(&["-b", "gwVGQRQ4mAEBAQIAAQ"], &[]);
+
+    assert_eq!(code, 1);
+    assert!(stdout.is_empty());
+}
+
+#[test]
+fn decode_errors() {
+    assert_eq!(decode(&["--hex", "830"], &[]).0, 1);
+    assert_eq!(decode(&["--hex", "zz"], &[]).0, 1);
+    assert_eq!(decode(&["--hex", "83"], &[]).0, 1);
+    assert_eq!(decode(&["tests/sample/missing.bin"], &[]).0, 2);
+    assert_eq!(decode(&["--bogus"], &[]).0, 2);
+
+    let (code, stdout) = decode(&["-x", &to_hex(&sample()), "-x", "83"], &[]);
+
+    assert_eq!(code, 1);
+    assert!(stdout.contains("EventReport"));
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn decode_json() {
+    let (code, stdout) = decode(&["--json", "tests/sample/message1.bin"], &[]);
+
+    assert_eq!(code, 0);
+    assert!(stdout.contains("\"esn\": \"4641143898\""));
+}
+
+#[test]
+fn decode_dissect() {
+    let (code, stdout) = decode(&["--dissect", "tests/sample/message1.bin"], &[]);
+
+    assert_eq!(code, 0);
+    assert!(stdout.contains("0002..0007  46 41 14 38 98             Mobile id"));
+
+    let (code, stdout) = decode(&["-d", "-x", &to_hex(&sample()[..23])], &[]);
+
+    assert_eq!(code, 1);
+    assert!(stdout.contains("Time of fix"));
+    assert!(stdout.contains("error at 0015: premature end of stream"));
+}
+
+#[cfg(feature = "pcap")]
+#[test]
+fn decode_pcap() {
+    let (code, stdout) = decode(&["--format", "pcap", "tests/sample/message1.pcap"], &[]);
+
+    assert_eq!(code, 0);
+    assert!(stdout.contains("Packet tests/sample/message1.pcap#1 Udp 10.0.0.1:20500 -> \
+                             10.0.0.2:20500"));
+    assert!(stdout.contains("EventReport"));
+
+    let (code, stdout) = decode(&["-f", "pcap", "-p", "20501", "tests/sample/message1.pcap"], &[]);
+
+    assert_eq!(code, 0);
+    assert!(stdout.is_empty());
+}
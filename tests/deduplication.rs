@@ -0,0 +1,170 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+extern crate calamp;
+
+use calamp::deduplication::{Deduplicator, Verdict};
+use calamp::message::Message;
+use calamp::message_header::*;
+use calamp::options_header::{MobileId, OptionsHeader};
+use calamp::packet::Packet;
+
+use std::time::{Duration, Instant};
+
+fn packet(esn: &str, message_type: MessageType, sequence_number: u16, body: &[u8]) -> Packet {
+    let mut options = OptionsHeader::new();
+
+    options.set_mobile_id(Some(MobileId::Esn(esn.to_string())));
+
+    Packet::new(options,
+                MessageHeader::new(ServiceType::AcknowledgedRequest, message_type, sequence_number),
+                Message::Raw(body.to_vec()))
+}
+
+fn event(sequence_number: u16) -> Packet {
+    packet("4641143898", MessageType::EventReport, sequence_number, &[1, 2, 3])
+}
+
+#[test]
+fn deduplicator_detects_retransmissions() {
+    let now    = Instant::now();
+    let mut dd = Deduplicator::new(8, Duration::from_secs(600));
+
+    assert_eq!(dd.check(&event(100), now), Verdict::New);
+    assert_eq!(dd.check(&event(101), now), Verdict::New);
+    assert_eq!(dd.check(&event(100), now), Verdict::Duplicate);
+    assert_eq!(dd.check(&packet("4641143898", MessageType::IdReport, 100, &[1, 2, 3]), now),
+               Verdict::New);
+    assert_eq!(dd.check(&packet("4641143899", MessageType::EventReport, 100, &[1, 2, 3]), now),
+               Verdict::New);
+
+    // late, but within the window
+    assert_eq!(dd.check(&event(96), now), Verdict::New);
+
+    // too far behind
+    assert_eq!(dd.check(&event(50), now), Verdict::OutOfWindow);
+    assert_eq!(dd.check(&event(51), now), Verdict::New);
+
+    // no mobile ID
+    let anonymous = Packet::new(OptionsHeader::new(), event(51).message_header().clone(),
+                                Message::Raw(vec![1, 2, 3]));
+
+    assert_eq!(dd.check(&anonymous, now), Verdict::New);
+    assert_eq!(dd.check(&anonymous, now), Verdict::New);
+}
+
+#[test]
+fn deduplicator_handles_wraparound_and_reboot() {
+    let now    = Instant::now();
+    let mut dd = Deduplicator::new(8, Duration::from_secs(600));
+
+    assert_eq!(dd.check(&event(65534), now), Verdict::New);
+    assert_eq!(dd.check(&event(65535), now), Verdict::New);
+    assert_eq!(dd.check(&event(0), now), Verdict::New);
+    assert_eq!(dd.check(&event(65535), now), Verdict::Duplicate);
+    assert_eq!(dd.check(&event(1), now), Verdict::New);
+
+    for n in 2..1000 {
+        dd.check(&event(n), now);
+    }
+
+    // reboot resets the sequence numbers
+    assert_eq!(dd.check(&event(1), now), Verdict::New);
+    assert_eq!(dd.check(&event(1), now), Verdict::Duplicate);
+    assert_eq!(dd.check(&event(2), now), Verdict::New);
+}
+
+#[test]
+fn deduplicator_detects_reboot_within_window() {
+    let unit   = "4641143898";
+    let now    = Instant::now();
+    let mut dd = Deduplicator::new(8, Duration::from_secs(600));
+
+    assert_eq!(dd.check(&packet(unit, MessageType::EventReport, 1, &[1]), now), Verdict::New);
+    assert_eq!(dd.check(&packet(unit, MessageType::EventReport, 2, &[2]), now), Verdict::New);
+
+    // a reboot reuses sequence number 1 for a different message
+    assert_eq!(dd.check(&packet(unit, MessageType::EventReport, 1, &[3]), now), Verdict::New);
+    assert_eq!(dd.check(&packet(unit, MessageType::EventReport, 1, &[3]), now),
+               Verdict::Duplicate);
+    assert_eq!(dd.check(&packet(unit, MessageType::EventReport, 2, &[4]), now), Verdict::New);
+}
+
+#[test]
+fn deduplicator_expires_idle_units() {
+    let now    = Instant::now();
+    let later  = now + Duration::from_secs(601);
+    let mut dd = Deduplicator::new(8, Duration::from_secs(600));
+
+    assert_eq!(dd.check(&event(1), now), Verdict::New);
+    assert_eq!(dd.check(&event(1), later), Verdict::New);
+    assert_eq!(dd.check(&packet("4641143899", MessageType::EventReport, 1, &[]), later),
+               Verdict::New);
+    assert_eq!(dd.len(), 2);
+    assert_eq!(dd.expire(later + Duration::from_secs(601)).len(), 2);
+    assert!(dd.is_empty());
+}
+
+#[test]
+fn deduplicator_capacity() {
+    let now    = Instant::now();
+    let mut dd = Deduplicator::new(8, Duration::from_secs(600));
+
+    dd.set_capacity(2);
+
+    for (n, esn) in ["1", "2", "3"].iter().enumerate() {
+        let at = now + Duration::from_secs(n as u64);
+
+        assert_eq!(dd.check(&packet(esn, MessageType::EventReport, 1, &[]), at), Verdict::New);
+    }
+
+    assert_eq!(dd.len(), 2);
+
+    // the least recently seen unit was forgotten
+    let at = now + Duration::from_secs(3);
+
+    assert_eq!(dd.check(&packet("3", MessageType::EventReport, 1, &[]), at), Verdict::Duplicate);
+    assert_eq!(dd.check(&packet("1", MessageType::EventReport, 1, &[]), at), Verdict::New);
+}
+
+#[test]
+fn deduplicator_capacity_follows_last_seen() {
+    let now    = Instant::now();
+    let mut dd = Deduplicator::new(8, Duration::from_secs(600));
+
+    dd.set_capacity(3);
+
+    for esn in ["1", "2", "3"].iter() {
+        assert_eq!(dd.check(&packet(esn, MessageType::EventReport, 1, &[]), now), Verdict::New);
+    }
+
+    // hearing from the first unit again makes the second one the least recently seen
+    assert_eq!(dd.check(&packet("1", MessageType::EventReport, 2, &[]), now), Verdict::New);
+
+    dd.set_capacity(2);
+
+    assert_eq!(dd.len(), 2);
+    assert_eq!(dd.check(&packet("1", MessageType::EventReport, 2, &[]), now), Verdict::Duplicate);
+    assert_eq!(dd.check(&packet("3", MessageType::EventReport, 1, &[]), now), Verdict::Duplicate);
+
+    dd.remove(&MobileId::Esn("3".to_string()));
+
+    assert_eq!(dd.len(), 1);
+    assert_eq!(dd.expire(now + Duration::from_secs(601)).len(), 1);
+    assert!(dd.is_empty());
+}
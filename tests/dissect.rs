@@ -0,0 +1,169 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+extern crate calamp;
+
+use std::fs::File;
+use std::io::prelude::*;
+
+use calamp::CalAmpError;
+use calamp::dissect::{self, Trace};
+use calamp::options_header::OptionsHeader;
+
+fn sample() -> Vec<u8> {
+    let mut v = Vec::new();
+
+    File::open("tests/sample/message1.bin").unwrap()
+                                           .read_to_end(&mut v)
+                                           .unwrap();
+
+    v
+}
+
+#[test]
+fn dissect_spans() {
+    let data       = sample();
+    let dissection = dissect::dissect(&data);
+
+    assert!(dissection.packet().is_some());
+    assert!(dissection.error().is_none());
+    assert_eq!(dissection.stop(), data.len());
+
+    let names: Vec<&str> = dissection.spans().iter().map(|span| span.name()).collect();
+
+    assert_eq!(names, ["Options header", "Message header", "Message"]);
+    assert_eq!(dissection.spans()[0].range(), 0..9);
+    assert_eq!(dissection.spans()[1].range(), 9..13);
+    assert_eq!(dissection.spans()[2].range(), 13..data.len());
+
+    let bits = dissection.find("Option bits").unwrap();
+
+    assert_eq!(bits.range(), 0..1);
+    assert_eq!(bits.value(), "0x83 (mobile id, mobile id type, options header)");
+
+    let mobile_id = dissection.find("Mobile id").unwrap();
+
+    assert_eq!(mobile_id.range(), 2..7);
+    assert_eq!(mobile_id.value(), "MobileId::Esn(4641143898)");
+
+    assert_eq!(dissection.find("Mobile id type").unwrap().range(), 8..9);
+    assert_eq!(dissection.find("Message type").unwrap().value(), "EventReport");
+    assert_eq!(dissection.find("Latitude").unwrap().range(), 21..25);
+    assert_eq!(dissection.find("Latitude").unwrap().value(), "33.1031058 (331031058)");
+    assert_eq!(dissection.find("Event code").unwrap().value(), "13");
+    assert_eq!(dissection.find("Accumulator 0").unwrap().value(), "12859");
+
+    // leaf spans cover every byte exactly once
+    fn leaves(spans: &[dissect::Span], ranges: &mut Vec<std::ops::Range<usize>>) {
+        for span in spans {
+            if span.children().is_empty() {
+                ranges.push(span.range());
+            } else {
+                leaves(span.children(), ranges);
+            }
+        }
+    }
+
+    let mut ranges = Vec::new();
+
+    leaves(dissection.spans(), &mut ranges);
+
+    assert_eq!(ranges.first().unwrap().start, 0);
+    assert_eq!(ranges.last().unwrap().end, data.len());
+
+    for pair in ranges.windows(2) {
+        assert_eq!(pair[0].end, pair[1].start);
+    }
+}
+
+#[test]
+fn dissect_render() {
+    let rendered = dissect::dissect(&sample()).to_string();
+    let lines: Vec<&str> = rendered.lines().collect();
+
+    assert_eq!(lines[0].trim_end(), "0000..0009                           Options header");
+    assert_eq!(lines[1], "0000..0001  83                         Option bits: 0x83 (mobile id, \
+                          mobile id type, options header)");
+    assert_eq!(lines[3], "0002..0007  46 41 14 38 98             Mobile id: \
+                          MobileId::Esn(4641143898)");
+    assert!(lines.contains(&"0015..0019  13 bb 22 12                  Latitude: 33.1031058 \
+                             (331031058)"));
+}
+
+#[test]
+fn dissect_truncated() {
+    let data       = sample();
+    let dissection = dissect::dissect(&data[..23]);
+
+    match dissection.error() {
+        Some(&CalAmpError::Eos) => {},
+        x => panic!("unexpected error: {:?}", x)
+    }
+
+    assert!(dissection.packet().is_none());
+    assert_eq!(dissection.stop(), 21);
+    assert_eq!(dissection.find("Message").unwrap().range(), 13..21);
+    assert_eq!(dissection.find("Time of fix").unwrap().range(), 17..21);
+    assert!(dissection.find("Latitude").is_none());
+    assert!(dissection.to_string().ends_with("error at 0015: premature end of stream\n"));
+}
+
+#[test]
+fn dissect_trailing_data() {
+    let mut data = sample();
+
+    data.extend_from_slice(&[1, 2, 3]);
+
+    let dissection = dissect::dissect(&data);
+
+    assert!(dissection.packet().is_some());
+
+    let trailing = dissection.find("Trailing data").unwrap();
+
+    assert_eq!(trailing.range(), data.len() - 3..data.len());
+    assert_eq!(trailing.value(), "3 bytes");
+}
+
+#[test]
+fn dissect_options_absent() {
+    let dissection = dissect::dissect(&[0x01, 0x00, 0x00, 0x07]);
+
+    assert!(dissection.packet().is_some());
+    assert_eq!(dissection.find("Options header").unwrap().range(), 0..0);
+    assert!(dissection.find("Option bits").is_none());
+    assert_eq!(dissection.find("Service type").unwrap().range(), 0..1);
+    assert_eq!(dissection.find("Sequence number").unwrap().value(), "7");
+}
+
+#[test]
+fn dissect_parse_traced() {
+    let data = sample();
+    let mut trace = Trace::new();
+
+    let (options, index) = OptionsHeader::parse_traced(&data, &mut trace).unwrap();
+
+    assert_eq!(index, 9);
+    assert_eq!(trace.furthest(), 9);
+    assert_eq!(format!("{:?}", options),
+               format!("{:?}", OptionsHeader::parse(&data).unwrap().0));
+
+    let spans = trace.into_spans();
+
+    assert_eq!(spans.len(), 5);
+    assert_eq!(spans[0].name(), "Option bits");
+}
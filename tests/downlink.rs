@@ -0,0 +1,299 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+extern crate calamp;
+
+use std::io;
+use std::net::UdpSocket;
+use std::time::{Duration, Instant};
+
+use calamp::downlink::{Downlink, Outcome, Status};
+use calamp::message::Message;
+use calamp::message::null::NullMessage;
+use calamp::message::acknowledgement::{AcknowledgementMessage, AcknowledgementType};
+use calamp::message_header::*;
+use calamp::options_header::*;
+use calamp::packet::Packet;
+use calamp::session::SessionTable;
+
+fn receive(socket: &UdpSocket) -> Packet {
+    let mut buffer = [0; 512];
+    let (length, _) = socket.recv_from(&mut buffer).unwrap();
+
+    Packet::parse(&buffer[..length]).unwrap().0
+}
+
+fn ack(mobile_id: &MobileId, request: &Packet, ack: AcknowledgementType) -> Packet {
+    let mut options_header = OptionsHeader::new();
+
+    options_header.set_mobile_id(Some(mobile_id.clone()));
+
+    Packet::new(options_header,
+                MessageHeader::new(ServiceType::Response, MessageType::AckNak,
+                                   request.message_header().sequence_number()),
+                Message::AckNak(AcknowledgementMessage::new(*request.message_header()
+                                                                    .message_type(),
+                                                            ack, [0; 3])))
+}
+
+fn sessions(mobile_id: &MobileId, unit: &UdpSocket, now: Instant) -> SessionTable {
+    let mut sessions       = SessionTable::new(Duration::from_secs(600));
+    let mut options_header = OptionsHeader::new();
+
+    options_header.set_mobile_id(Some(mobile_id.clone()));
+
+    sessions.update(&Packet::new(options_header,
+                                 MessageHeader::new(ServiceType::UnacknowledgedRequest,
+                                                    MessageType::Null, 1),
+                                 Message::Null(NullMessage::new())),
+                    unit.local_addr().unwrap(), now);
+
+    sessions
+}
+
+#[test]
+fn downlink_matches_ack() {
+    let server    = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let unit      = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mobile_id = MobileId::Esn("4641143898".to_string());
+    let now       = Instant::now();
+    let sessions  = sessions(&mobile_id, &unit, now);
+    let peer      = unit.local_addr().unwrap();
+
+    let mut downlink = Downlink::new();
+
+    let first = downlink.send(&server, &sessions, &mobile_id, MessageType::UnitRequest,
+                              Message::Raw(vec![1, 0, 0]), now).unwrap();
+    let second = downlink.send(&server, &sessions, &mobile_id, MessageType::ConfigurationParameter,
+                               Message::Raw(vec![0; 4]), now).unwrap();
+
+    let first_request  = receive(&unit);
+    let second_request = receive(&unit);
+
+    assert_eq!(*first_request.message_header().service_type(), ServiceType::AcknowledgedRequest);
+    assert_eq!(first_request.message_header().sequence_number(), 1);
+    assert_eq!(second_request.message_header().sequence_number(), 2);
+
+    // wrong message type for the sequence number
+    let mut wrong = ack(&mobile_id, &first_request, AcknowledgementType::Successful);
+
+    wrong = Packet::new(wrong.options_header().clone(),
+                        second_request.message_header().clone(),
+                        wrong.message().clone());
+
+    assert_eq!(downlink.acknowledge(&wrong, peer, now), None);
+
+    assert_eq!(downlink.acknowledge(&ack(&mobile_id, &second_request,
+                                         AcknowledgementType::FailedOperation), peer, now),
+               Some((second, Outcome::Acknowledged(AcknowledgementType::FailedOperation))));
+    assert_eq!(downlink.acknowledge(&ack(&mobile_id, &first_request,
+                                         AcknowledgementType::Successful), peer, now),
+               Some((first, Outcome::Acknowledged(AcknowledgementType::Successful))));
+    assert!(downlink.is_empty());
+
+    // completed commands remain queryable for the retention period
+    assert_eq!(downlink.status(first),
+               Some(Status::Completed(Outcome::Acknowledged(AcknowledgementType::Successful))));
+    assert!(downlink.poll(&server, now + downlink.retention()).unwrap().is_empty());
+    assert!(downlink.status(second).is_some());
+    assert!(downlink.poll(&server, now + downlink.retention() * 2).unwrap().is_empty());
+    assert_eq!(downlink.status(second), None);
+}
+
+#[test]
+fn downlink_matches_ack_to_unit() {
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let first  = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let second = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let esn    = MobileId::Esn("4641143898".to_string());
+    let imei   = MobileId::ImeiEid("359101400000123".to_string());
+    let now    = Instant::now();
+
+    let mut sessions       = sessions(&esn, &first, now);
+    let mut options_header = OptionsHeader::new();
+
+    options_header.set_mobile_id(Some(imei.clone()));
+
+    sessions.update(&Packet::new(options_header,
+                                 MessageHeader::new(ServiceType::UnacknowledgedRequest,
+                                                    MessageType::Null, 1),
+                                 Message::Null(NullMessage::new())),
+                    second.local_addr().unwrap(), now);
+
+    let mut downlink = Downlink::new();
+
+    let to_first = downlink.send(&server, &sessions, &esn, MessageType::UnitRequest,
+                                 Message::Raw(vec![1, 0, 0]), now).unwrap();
+    let to_second = downlink.send(&server, &sessions, &imei, MessageType::UnitRequest,
+                                  Message::Raw(vec![1, 0, 0]), now).unwrap();
+
+    let first_request  = receive(&first);
+    let second_request = receive(&second);
+
+    assert_eq!(first_request.message_header().sequence_number(), 1);
+    assert_eq!(second_request.message_header().sequence_number(), 1);
+
+    // an ACK without a mobile ID only completes the command sent to its address
+    let mut anonymous = ack(&esn, &first_request, AcknowledgementType::Successful);
+
+    anonymous = Packet::new(OptionsHeader::new(),
+                            anonymous.message_header().clone(),
+                            anonymous.message().clone());
+
+    assert_eq!(downlink.acknowledge(&anonymous, server.local_addr().unwrap(), now), None);
+    assert_eq!(downlink.acknowledge(&anonymous, second.local_addr().unwrap(), now),
+               Some((to_second, Outcome::Acknowledged(AcknowledgementType::Successful))));
+    assert!(downlink.is_pending(to_first));
+
+    // an ACK with a mobile ID only completes the command sent to that unit
+    assert_eq!(downlink.acknowledge(&ack(&imei, &first_request, AcknowledgementType::Successful),
+                                    first.local_addr().unwrap(), now),
+               None);
+    assert_eq!(downlink.acknowledge(&ack(&esn, &first_request, AcknowledgementType::Successful),
+                                    second.local_addr().unwrap(), now),
+               Some((to_first, Outcome::Acknowledged(AcknowledgementType::Successful))));
+    assert!(downlink.is_empty());
+}
+
+#[test]
+fn downlink_retries_and_expires() {
+    let server    = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let unit      = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mobile_id = MobileId::Esn("4641143898".to_string());
+    let now       = Instant::now();
+    let sessions  = sessions(&mobile_id, &unit, now);
+
+    let mut downlink = Downlink::with_retry_schedule(vec![Duration::from_secs(1),
+                                                          Duration::from_secs(2)]);
+
+    let id = downlink.send(&server, &sessions, &mobile_id, MessageType::UnitRequest,
+                           Message::Raw(vec![1, 0, 0]), now).unwrap();
+
+    receive(&unit);
+
+    assert!(downlink.poll(&server, now).unwrap().is_empty());
+    assert!(downlink.poll(&server, now + Duration::from_secs(1)).unwrap().is_empty());
+    assert_eq!(receive(&unit).message_header().sequence_number(), 1);
+    assert!(downlink.is_pending(id));
+
+    assert_eq!(downlink.poll(&server, now + Duration::from_secs(3)).unwrap(),
+               vec![(id, Outcome::Expired)]);
+    assert!(!downlink.is_pending(id));
+    assert_eq!(downlink.status(id), Some(Status::Completed(Outcome::Expired)));
+}
+
+#[test]
+fn downlink_requires_known_unit() {
+    let server    = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let unit      = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mobile_id = MobileId::Esn("4641143898".to_string());
+    let other     = MobileId::Esn("4641143899".to_string());
+    let now       = Instant::now();
+    let sessions  = sessions(&mobile_id, &unit, now);
+
+    let mut downlink = Downlink::new();
+
+    let error = downlink.send(&server, &sessions, &other, MessageType::UnitRequest,
+                              Message::Raw(vec![1, 0, 0]), now).unwrap_err();
+
+    assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    assert!(downlink.is_empty());
+}
+
+#[test]
+fn downlink_holds_until_check_in() {
+    let server    = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let unit      = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mobile_id = MobileId::Esn("4641143898".to_string());
+    let other     = MobileId::Esn("4641143899".to_string());
+    let now       = Instant::now();
+    let expires   = now + Duration::from_secs(60);
+
+    let mut downlink = Downlink::new();
+
+    let held    = downlink.hold(&mobile_id, MessageType::UnitRequest, Message::Raw(vec![1, 0, 0]),
+                                expires);
+    let stale   = downlink.hold(&mobile_id, MessageType::UnitRequest, Message::Raw(vec![2, 0, 0]),
+                                now + Duration::from_secs(1));
+    let waiting = downlink.hold(&other, MessageType::ConfigurationParameter,
+                                Message::Raw(vec![0; 4]), expires);
+
+    assert_eq!(downlink.status(held), Some(Status::Held(expires)));
+    assert_eq!(downlink.poll(&server, now + Duration::from_secs(1)).unwrap(),
+               vec![(stale, Outcome::Expired)]);
+    assert_eq!(downlink.status(stale), Some(Status::Completed(Outcome::Expired)));
+
+    // null keep-alive from the unit
+    let mut options_header = OptionsHeader::new();
+
+    options_header.set_mobile_id(Some(mobile_id.clone()));
+
+    let keep_alive = Packet::new(options_header,
+                                 MessageHeader::new(ServiceType::UnacknowledgedRequest,
+                                                    MessageType::Null, 7),
+                                 Message::Null(NullMessage::new()));
+
+    assert_eq!(downlink.check_in(&server, &keep_alive, unit.local_addr().unwrap(),
+                                 now + Duration::from_secs(2)).unwrap(),
+               vec![held]);
+    assert_eq!(downlink.status(held), Some(Status::Pending(1)));
+    assert_eq!(downlink.status(waiting), Some(Status::Held(expires)));
+
+    let request = receive(&unit);
+
+    match *request.message() {
+        Message::Raw(ref body) => assert_eq!(*body, vec![1, 0, 0]),
+        _ => panic!("Unexpected message body")
+    }
+
+    assert_eq!(downlink.acknowledge(&ack(&mobile_id, &request, AcknowledgementType::Successful),
+                                    unit.local_addr().unwrap(), now + Duration::from_secs(3)),
+               Some((held, Outcome::Acknowledged(AcknowledgementType::Successful))));
+    assert_eq!(downlink.len(), 1);
+}
+
+#[test]
+fn downlink_keeps_held_command_on_send_failure() {
+    let server    = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let unit      = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mobile_id = MobileId::Esn("4641143898".to_string());
+    let now       = Instant::now();
+    let expires   = now + Duration::from_secs(60);
+
+    let mut downlink = Downlink::new();
+
+    let held = downlink.hold(&mobile_id, MessageType::UnitRequest, Message::Raw(vec![1, 0, 0]),
+                             expires);
+
+    let mut options_header = OptionsHeader::new();
+
+    options_header.set_mobile_id(Some(mobile_id.clone()));
+
+    let keep_alive = Packet::new(options_header,
+                                 MessageHeader::new(ServiceType::UnacknowledgedRequest,
+                                                    MessageType::Null, 7),
+                                 Message::Null(NullMessage::new()));
+
+    // an IPv4 socket cannot send to an IPv6 address
+    assert!(downlink.check_in(&server, &keep_alive, "[::1]:20500".parse().unwrap(), now).is_err());
+    assert_eq!(downlink.status(held), Some(Status::Held(expires)));
+
+    assert_eq!(downlink.check_in(&server, &keep_alive, unit.local_addr().unwrap(), now).unwrap(),
+               vec![held]);
+    assert_eq!(downlink.status(held), Some(Status::Pending(1)));
+}
@@ -0,0 +1,124 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+extern crate calamp;
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use calamp::event_catalog::*;
+use calamp::message::Message;
+use calamp::message_header::*;
+use calamp::options_header::*;
+use calamp::packet::Packet;
+use calamp::session::SessionTable;
+
+fn catalog() -> EventCatalog {
+    let mut catalog = EventCatalog::new();
+
+    catalog.insert(None, 13, EventDefinition::new("Ignition on", "ignition"));
+    catalog.insert(Some(33), 13, EventDefinition::new("Harsh brake", "driving"));
+
+    catalog
+}
+
+fn message1() -> Packet {
+    let mut v = Vec::new();
+
+    File::open("tests/sample/message1.bin").unwrap()
+                                           .read_to_end(&mut v)
+                                           .unwrap();
+
+    Packet::parse(&v).unwrap().0
+}
+
+#[test]
+fn event_catalog_overrides() {
+    let catalog = catalog();
+
+    assert_eq!(catalog.get(None, 13).unwrap().name(), "Ignition on");
+    assert_eq!(catalog.get(Some(32), 13).unwrap().name(), "Ignition on");
+    assert_eq!(catalog.get(Some(33), 13).unwrap().category(), "driving");
+    assert!(catalog.get(Some(33), 14).is_none());
+}
+
+#[test]
+fn event_catalog_labels_by_script_version() {
+    let catalog = catalog();
+    let packet  = message1();
+    let peer: SocketAddr = "10.0.0.1:4000".parse().unwrap();
+
+    let mut sessions = SessionTable::new(Duration::from_secs(60));
+
+    assert_eq!(catalog.label_packet(&packet, &sessions).unwrap().name(), "Ignition on");
+
+    // ID report from the same unit running script version 33
+    let mut body = vec![33, 0, 0, 7, 0x38, 0x31, 0x62, 0, 0, 0, 0, 1, 0, 0, 0, 0];
+
+    body.extend_from_slice(&[0xFF; 42]);
+
+    let mut options_header = OptionsHeader::new();
+
+    options_header.set_mobile_id(packet.options_header().mobile_id().clone());
+
+    let mut buffer = Vec::new();
+
+    Packet::new(options_header,
+                MessageHeader::new(ServiceType::AcknowledgedRequest, MessageType::IdReport, 1),
+                Message::Raw(body)).encode(&mut buffer);
+
+    sessions.update(&Packet::parse(&buffer).unwrap().0, peer, Instant::now());
+
+    assert_eq!(catalog.label_packet(&packet, &sessions).unwrap().name(), "Harsh brake");
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn event_catalog_toml() {
+    let catalog = EventCatalog::from_toml("[[event]]\n\
+                                           code = 13\n\
+                                           name = \"Ignition on\"\n\
+                                           category = \"ignition\"\n\
+                                           \n\
+                                           [[event]]\n\
+                                           script = 33\n\
+                                           code = 13\n\
+                                           name = \"Harsh brake\"\n").unwrap();
+
+    assert_eq!(catalog.get(Some(1), 13).unwrap().category(), "ignition");
+    assert_eq!(catalog.get(Some(33), 13).unwrap().name(), "Harsh brake");
+
+    match EventCatalog::from_toml("[[event]]\ncode = 13\nname = \"a\"\n\n[[event]]\ncode = \"x\"\n") {
+        Err(calamp::ConfigError::Line(line, _)) => assert_eq!(line, 6),
+        _ => panic!("Invalid catalog was accepted")
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn event_catalog_json() {
+    let catalog = EventCatalog::from_json(r#"{"event": [
+        {"code": 13, "name": "Ignition on", "category": "ignition"},
+        {"script": 33, "code": 13, "name": "Harsh brake", "category": "driving"}
+    ]}"#).unwrap();
+
+    assert_eq!(catalog.get(None, 13).unwrap().name(), "Ignition on");
+    assert_eq!(catalog.get(Some(33), 13).unwrap().category(), "driving");
+}
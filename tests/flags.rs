@@ -0,0 +1,63 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+extern crate calamp;
+
+use calamp::flags::*;
+
+#[test]
+fn flags_round_trip() {
+    for bits in 0..256 {
+        assert_eq!(FixStatus::from_bits(bits as u8).bits(), bits as u8);
+        assert_eq!(CommState::from_bits(bits as u8).bits(), bits as u8);
+        assert_eq!(UnitStatus::from_bits(bits as u8).bits(), bits as u8);
+        assert_eq!(Inputs::from_bits(bits as u8).bits(), bits as u8);
+    }
+}
+
+#[test]
+fn flags_accessors() {
+    let fix_status = FixStatus::from_bits(0x64);
+
+    assert!(fix_status.last_known());
+    assert!(fix_status.historic());
+    assert!(fix_status.invalid_time());
+    assert!(!fix_status.invalid_fix());
+    assert!(fix_status.contains(FixStatus::HISTORIC | FixStatus::LAST_KNOWN));
+
+    let comm_state = CommState::CONNECTED | CommState::ROAMING;
+
+    assert!(comm_state.roaming());
+    assert!(!comm_state.voice_call());
+    assert_eq!(comm_state.bits(), 0x28);
+
+    let inputs = Inputs::from_bits(0x05);
+
+    assert!(inputs.ignition());
+    assert!(inputs.input(2));
+    assert!(!inputs.input(1));
+    assert!(!inputs.input(8));
+}
+
+#[test]
+fn flags_debug() {
+    assert_eq!(format!("{:?}", FixStatus::from_bits(0x64)),
+               "FixStatus(LastKnown | Historic | InvalidTime)");
+    assert_eq!(format!("{:?}", CommState::from_bits(0xC1)), "CommState(Available | 0xc0)");
+    assert_eq!(format!("{:?}", UnitStatus::default()), "UnitStatus()");
+}
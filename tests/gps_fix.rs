@@ -0,0 +1,124 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+extern crate calamp;
+
+mod common;
+
+use calamp::flags::FixStatus;
+use calamp::gps_fix::GpsFix;
+use calamp::lmu_time::UpdateTime;
+use calamp::message::Message;
+use calamp::message::mini_report_header::MiniReportHeader;
+use calamp::message_header::MessageType;
+use calamp::packet::Packet;
+use calamp::signal::SignalQuality;
+
+#[test]
+fn gps_fix_conversions() {
+    let fix = GpsFix::new(-337_000_000, 1_512_000_000, 12_345, 2_778, 270, 9,
+                          FixStatus::DIFFERENTIAL, 13);
+
+    assert!((fix.latitude() + 33.7).abs() < 1e-9);
+    assert!((fix.longitude() - 151.2).abs() < 1e-9);
+    assert!((fix.altitude() - 123.45).abs() < 1e-9);
+    assert_eq!(fix.speed_cm_s(), 2_778);
+    assert!((fix.speed_kmh() - 100.008).abs() < 1e-9);
+    assert!((fix.speed_mph() - 62.142).abs() < 1e-3);
+    assert_eq!(fix.heading(), 270);
+    assert_eq!(fix.satellites(), 9);
+    assert!((fix.hdop() - 1.3).abs() < 1e-9);
+    assert!(fix.is_valid());
+}
+
+#[test]
+fn gps_fix_validity() {
+    assert!(GpsFix::new(0, 0, 0, 0, 0, 0, FixStatus::default(), 0).is_zero_position());
+    assert!(!GpsFix::new(0, 0, 0, 0, 0, 0, FixStatus::default(), 0).is_valid());
+    assert!(!GpsFix::new(900_000_001, 0, 0, 0, 0, 0, FixStatus::default(), 0).is_in_range());
+    assert!(!GpsFix::new(0, -1_800_000_001, 0, 0, 0, 0, FixStatus::default(), 0).is_in_range());
+    assert!(!GpsFix::new(i32::MIN, 10, 0, 0, 0, 0, FixStatus::default(), 0).is_valid());
+    assert!(!GpsFix::new(10, i32::MIN, 0, 0, 0, 0, FixStatus::default(), 0).is_valid());
+    assert!(GpsFix::new(10, 10, 0, 0, 0, 0, FixStatus::INVALID_FIX, 0).is_invalid_fix());
+    assert!(!GpsFix::new(10, 10, 0, 0, 0, 0, FixStatus::INVALID_FIX, 0).is_valid());
+}
+
+#[test]
+fn gps_fix_mini_messages() {
+    let fix = GpsFix::new(-337_000_000, 1_512_000_000, 12_345, 2_778, 270, 9,
+                          FixStatus::DIFFERENTIAL, 13);
+    let time = UpdateTime::from_secs(1_500_000_000);
+    let mut data = Vec::new();
+
+    MiniReportHeader::new(time, fix).encode(&mut data);
+    data.extend_from_slice(&[1, 2, 0, 0]);
+
+    let (message, length) = Message::parse(&MessageType::MiniUser, &data).unwrap();
+    let mini_fix = message.gps_fix().unwrap();
+
+    assert_eq!(length, data.len());
+    assert_eq!(mini_fix.latitude_raw(), -337_000_000);
+    assert_eq!(mini_fix.longitude_raw(), 1_512_000_000);
+    assert_eq!(mini_fix.heading(), 270);
+    assert_eq!(mini_fix.satellites(), 9);
+    assert_eq!(mini_fix.fix_status(), FixStatus::DIFFERENTIAL);
+    assert_eq!(mini_fix.speed_cm_s(), 2_778);
+    assert_eq!(message.mini_report_header().unwrap().update_time(time), Some(time));
+}
+
+#[test]
+fn gps_fix_message1() {
+    let v = common::message1();
+
+    let (packet, byte_count) = Packet::parse(&v).unwrap();
+
+    assert_eq!(byte_count, v.len());
+
+    let report = match *packet.message() {
+        Message::EventReport(ref report) => report,
+        _ => panic!("Message is not an event report")
+    };
+
+    assert_eq!(report.event_code(), 13);
+    assert_eq!(report.accumulators().values(), &[12859, 0, 0]);
+
+    let fix = packet.message().gps_fix().unwrap();
+
+    assert_eq!(fix.latitude_raw(), 331031058);
+    assert!((fix.latitude() - 33.1031058).abs() < 1e-9);
+    assert!((fix.longitude() + 117.1834301).abs() < 1e-9);
+    assert_eq!(fix.altitude(), 0.0);
+    assert_eq!(fix.speed_kmh(), 0.0);
+    assert!(fix.is_in_range());
+    assert!(!fix.is_zero_position());
+    assert!(fix.is_valid());
+    assert!(fix.fix_status().historic());
+    assert!(fix.fix_status().invalid_time());
+    assert!(report.report_header().update_time().is_invalid());
+    assert_eq!(report.report_header().time_of_fix().to_rfc3339(), "2015-12-08T19:13:31Z");
+    assert!(report.report_header().unit_status().gps_tracking());
+    assert!(report.report_header().inputs().ignition());
+    assert_eq!(report.report_header().rssi().dbm(), -113);
+    assert_eq!(report.report_header().rssi().quality(), SignalQuality::NoSignal);
+
+    let mut encoded = Vec::new();
+
+    packet.encode(&mut encoded);
+
+    assert_eq!(encoded, v);
+}
@@ -0,0 +1,113 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+extern crate calamp;
+
+#[cfg(feature = "chrono")]
+extern crate chrono;
+
+#[cfg(feature = "time")]
+extern crate time;
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use calamp::lmu_time::{FixTime, GPS_EPOCH, SECONDS_PER_WEEK, UpdateTime};
+
+#[test]
+fn lmu_time_conversions() {
+    let time = FixTime::from_secs(1_449_602_011);
+
+    assert_eq!(time.to_rfc3339(), "2015-12-08T19:13:31Z");
+    assert_eq!(format!("{}", time), "2015-12-08T19:13:31Z");
+    assert_eq!(SystemTime::from(time), UNIX_EPOCH + Duration::from_secs(1_449_602_011));
+    assert_eq!(FixTime::from_system_time(time.to_system_time()), Some(time));
+    assert_eq!(UpdateTime::from_secs(951_782_400).to_rfc3339(), "2000-02-29T00:00:00Z");
+    assert_eq!(UpdateTime::from_secs(u32::MAX).to_rfc3339(), "2106-02-07T06:28:15Z");
+    assert_eq!(UpdateTime::from_system_time(UNIX_EPOCH + Duration::from_secs(1 << 32)), None);
+}
+
+#[test]
+fn lmu_time_invalid() {
+    assert!(UpdateTime::from_secs(5).is_invalid());
+    assert!(UpdateTime::from_secs(315_964_800 + 1_000).is_invalid());
+    assert!(!UpdateTime::from_secs(1_449_602_011).is_invalid());
+}
+
+#[test]
+fn lmu_time_gps() {
+    let time = UpdateTime::from_secs(1_449_602_011);
+
+    assert_eq!(UpdateTime::from_gps_secs(0), Some(UpdateTime::from_secs(GPS_EPOCH)));
+    assert_eq!(UpdateTime::from_gps_secs(u32::MAX), None);
+    assert_eq!(time.as_gps_secs(), Some(1_449_602_011 - GPS_EPOCH));
+    assert_eq!(UpdateTime::from_secs(5).as_gps_secs(), None);
+
+    // GPS week 1874 began on 2015-12-06
+    assert_eq!(time.gps_week(), Some(1874));
+    assert_eq!(UpdateTime::from_gps_week(1874, time.to_short()), Some(time));
+    assert_eq!(UpdateTime::from_gps_week(1874, SECONDS_PER_WEEK), None);
+}
+
+#[test]
+fn lmu_time_short() {
+    let time  = UpdateTime::from_secs(1_449_602_011);
+    let short = time.to_short();
+
+    assert_eq!(short, 2 * 86_400 + 19 * 3_600 + 13 * 60 + 31);
+    assert_eq!(UpdateTime::from_short(short, time), Some(time));
+
+    // the week nearest to the reference is chosen, across week boundaries
+    for offset in &[-300_000i64, -60, 0, 60, 300_000] {
+        let reference = FixTime::from_secs((1_449_602_011 + offset) as u32);
+
+        assert_eq!(UpdateTime::from_short(short, reference), Some(time));
+    }
+
+    assert_eq!(UpdateTime::from_short(SECONDS_PER_WEEK, time), None);
+
+    // invalid units count from the Unix epoch
+    let invalid = UpdateTime::from_secs(1_000);
+
+    assert_eq!(UpdateTime::from_short(invalid.to_short(), invalid), Some(invalid));
+
+    // the nearest time falls before the Unix epoch
+    assert_eq!(UpdateTime::from_short(UpdateTime::from_secs(0).to_short() - 1,
+                                      UpdateTime::from_secs(0)),
+               None);
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn lmu_time_chrono() {
+    let time: chrono::DateTime<chrono::Utc> = FixTime::from_secs(1_449_602_011).into();
+
+    assert_eq!(time.to_rfc3339(), "2015-12-08T19:13:31+00:00");
+    assert_eq!(FixTime::from_chrono(&time), Some(FixTime::from_secs(1_449_602_011)));
+    assert_eq!(FixTime::from_chrono(&chrono::DateTime::from_timestamp(-1, 0).unwrap()), None);
+}
+
+#[cfg(feature = "time")]
+#[test]
+fn lmu_time_time() {
+    let time: time::OffsetDateTime = FixTime::from_secs(1_449_602_011).into();
+
+    assert_eq!(time.unix_timestamp(), 1_449_602_011);
+    assert_eq!(FixTime::from_offset_date_time(&time), Some(FixTime::from_secs(1_449_602_011)));
+    assert_eq!(FixTime::from_offset_date_time(&time::OffsetDateTime::UNIX_EPOCH),
+               Some(FixTime::from_secs(0)));
+}
@@ -0,0 +1,82 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+extern crate calamp;
+
+use std::net::SocketAddr;
+use std::process::Command;
+use std::thread;
+
+use calamp::message::acknowledgement::AcknowledgementType;
+use calamp::message_header::MessageType;
+use calamp::packet::Packet;
+use calamp::server::Server;
+
+fn load(args: &[&str]) -> (i32, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_calamp-load")).args(args).output().unwrap();
+
+    (output.status.code().unwrap(), String::from_utf8(output.stdout).unwrap())
+}
+
+#[test]
+fn load_reports_latency() {
+    // NAK null messages to check that rejections are counted apart
+    let mut server = Server::bind("127.0.0.1:0", |packet: &Packet, _: SocketAddr| {
+        match *packet.message_header().message_type() {
+            MessageType::Null => AcknowledgementType::FailedOperation,
+            _ => AcknowledgementType::Successful
+        }
+    }).unwrap();
+
+    let address = server.local_addr().unwrap().to_string();
+
+    thread::spawn(move || server.serve());
+
+    let (code, stdout) = load(&["-r", "200", "-d", "0.5", "-n", "4", &address]);
+
+    assert_eq!(code, 0);
+    assert!(stdout.contains("sent 100 "), "{}", stdout);
+    assert!(stdout.contains("acknowledged 100  rejected 0  lost 0"), "{}", stdout);
+    assert!(stdout.contains("latency  p50 "), "{}", stdout);
+    assert!(stdout.contains("max "), "{}", stdout);
+
+    let (code, stdout) = load(&["-r", "100", "-d", "0.2", "-m", "null", &address]);
+
+    assert_eq!(code, 0);
+    assert!(stdout.contains("acknowledged 0  rejected 20  lost 0"), "{}", stdout);
+}
+
+#[test]
+fn load_counts_lost_requests() {
+    // nothing answers on a bound but unread socket
+    let silent  = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let address = silent.local_addr().unwrap().to_string();
+
+    let (code, stdout) = load(&["-r", "100", "-d", "0.1", "-w", "0.1", &address]);
+
+    assert_eq!(code, 0);
+    assert!(stdout.contains("acknowledged 0  rejected 0  lost 10"), "{}", stdout);
+    assert!(!stdout.contains("latency"), "{}", stdout);
+}
+
+#[test]
+fn load_usage_errors() {
+    assert_eq!(load(&[]).0, 2);
+    assert_eq!(load(&["-r", "0", "127.0.0.1:1"]).0, 2);
+    assert_eq!(load(&["-m", "bogus", "127.0.0.1:1"]).0, 2);
+}
@@ -0,0 +1,136 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+extern crate calamp;
+
+use calamp::options_header::*;
+
+fn round_trip(data: &[u8]) -> OptionsHeader {
+    let (options_header, length) = OptionsHeader::parse(data).unwrap();
+
+    assert_eq!(length, data.len());
+
+    let mut encoded = Vec::new();
+
+    options_header.encode(&mut encoded);
+
+    assert_eq!(encoded, data);
+
+    options_header
+}
+
+#[test]
+fn options_header_extension_unknown_fields() {
+    // 2 byte bitmap holding the ESN bit and unknown bits 3 and 9
+    let options_header = round_trip(&[0xC0, 2, 0x09, 0x02,
+                                      5, 0x46, 0x41, 0x14, 0x38, 0x98,
+                                      1, 0x33,
+                                      2, 0xAA, 0xBB]);
+
+    let extension = options_header.extension().clone().unwrap();
+
+    assert_eq!(*extension.esn(), Some("4641143898".to_string()));
+    assert_eq!(extension.unknown_fields(), &[(3, vec![0x33]), (9, vec![0xAA, 0xBB])]);
+}
+
+#[test]
+fn options_header_extension_unknown_fields_length_prefixed() {
+    // unknown bit 3 with a field of 2 bytes after its length byte, followed by the message header
+    let data = [0xC0, 1, 0x08, 2, 0xAA, 0xBB, 0x01, 0x02];
+
+    let (options_header, length) = OptionsHeader::parse(&data).unwrap();
+
+    assert_eq!(length, 6);
+    assert_eq!(options_header.extension().clone().unwrap().unknown_fields(),
+               &[(3, vec![0xAA, 0xBB])]);
+
+    // a length byte running past the data is a premature end of stream
+    match OptionsHeader::parse(&[0xC0, 1, 0x08, 3, 0xAA, 0xBB]) {
+        Err(calamp::CalAmpError::Eos) => {},
+        x => panic!("unexpected result: {:?}", x)
+    }
+}
+
+#[test]
+fn options_header_extension_padded_bitmap() {
+    // 3 byte bitmap holding only the encryption bit
+    let options_header = round_trip(&[0xC0, 3, 0x04, 0x00, 0x00, 5, 0, 1, 2, 3, 4]);
+
+    let extension = options_header.extension().clone().unwrap();
+
+    assert_eq!(extension.encryption_type(), Some(EncryptionType::Unencrypted));
+    assert!(extension.unknown_fields().is_empty());
+}
+
+#[test]
+fn options_header_extension_set_unknown_fields() {
+    let mut extension      = OptionExtension::new();
+    let mut options_header = OptionsHeader::new();
+
+    extension.set_vin(Some("1FTFW1ET5DFC10312".to_string()));
+    extension.set_unknown_fields(vec![(17, vec![7]), (1, vec![1]), (4, vec![])]);
+    options_header.set_extension(Some(extension));
+
+    let mut data = Vec::new();
+
+    options_header.encode(&mut data);
+
+    assert_eq!(&data[..5], &[0xC0, 3, 0x12, 0x00, 0x02]);
+
+    let extension = round_trip(&data).extension().clone().unwrap();
+
+    assert_eq!(*extension.vin(), Some("1FTFW1ET5DFC10312".to_string()));
+    assert_eq!(extension.unknown_fields(), &[(4, vec![]), (17, vec![7])]);
+}
+
+#[test]
+#[allow(deprecated)]
+fn options_header_extension_bit_length_error() {
+    // kept for compatibility, though bitmaps of any length are now parsed
+    assert_eq!(calamp::CalAmpError::OptionExtensionBitLength(2).to_string(),
+               "unsupported option extension bit length: 2");
+}
+
+#[test]
+fn options_header_absent() {
+    // a first byte without bit 7 belongs to the message header
+    let (options_header, length) = OptionsHeader::parse(&[0x01, 0x02, 0x00, 0x07]).unwrap();
+
+    assert_eq!(length, 0);
+    assert!(options_header.mobile_id().is_none());
+}
+
+#[test]
+fn options_header_ends_at_slice_end() {
+    // the last field ends on the last byte of the slice
+    let options_header = round_trip(&[0x83, 2, 0x46, 0x41, 1, 1]);
+
+    assert_eq!(*options_header.mobile_id(), Some(MobileId::Esn("4641".to_string())));
+    assert!(OptionsHeader::parse(&[0x83, 2, 0x46, 0x41, 1]).is_err());
+}
+
+#[test]
+fn options_header_encryption_service() {
+    // encryption type followed by the random key, and then an unknown field for bit 3
+    let options_header = round_trip(&[0xC0, 1, 0x0C, 5, 1, 0xDE, 0xAD, 0xBE, 0xEF, 1, 0x2A]);
+    let extension      = options_header.extension().as_ref().unwrap();
+
+    assert_eq!(extension.encryption_type(), Some(EncryptionType::Esn));
+    assert_eq!(*extension.encryption_service(), Some([0xDE, 0xAD, 0xBE, 0xEF]));
+    assert_eq!(extension.unknown_fields(), &[(3, vec![0x2A])]);
+}
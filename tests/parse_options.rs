@@ -0,0 +1,144 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+extern crate calamp;
+
+use std::fs::File;
+use std::io::prelude::*;
+
+use calamp::{CalAmpError, ParseOptions, ParseWarning};
+use calamp::message::Message;
+use calamp::message::null::NullMessage;
+use calamp::message_header::*;
+use calamp::options_header::*;
+use calamp::packet::Packet;
+
+fn message1() -> Packet {
+    let mut v = Vec::new();
+
+    File::open("tests/sample/message1.bin").unwrap()
+                                           .read_to_end(&mut v)
+                                           .unwrap();
+
+    Packet::parse(&v).unwrap().0
+}
+
+/// Encode `packet` with `options_header` bytes in place of its options header.
+fn with_options_header(packet: &Packet, options_header: &[u8]) -> Vec<u8> {
+    let mut data = options_header.to_vec();
+
+    packet.message_header().encode(&mut data);
+    packet.message().encode(&mut data);
+
+    data
+}
+
+#[test]
+fn parse_options_default_is_strict() {
+    assert!(ParseOptions::default().strict);
+    assert!(!ParseOptions::lenient().strict);
+}
+
+#[test]
+fn parse_options_aftermarket_vin() {
+    let packet = message1();
+
+    let mut extension      = OptionExtension::new();
+    let mut options_header = packet.options_header().clone();
+
+    extension.set_vin(Some("AFTERMARKET1".to_string()));
+    options_header.set_extension(Some(extension));
+
+    let mut data = Vec::new();
+
+    Packet::new(options_header, packet.message_header().clone(), packet.message().clone())
+           .encode(&mut data);
+
+    match Packet::parse(&data) {
+        Err(CalAmpError::VinLength) => {},
+        other => panic!("unexpected result: {:?}", other.map(|_| ()))
+    }
+
+    let (lenient, length) = Packet::parse_with_options(&data, &ParseOptions::lenient()).unwrap();
+
+    assert_eq!(length, data.len());
+    assert_eq!(lenient.warnings(), &[ParseWarning::VinLength(12)]);
+    assert_eq!(*lenient.options_header().extension().as_ref().unwrap().vin(),
+               Some("AFTERMARKET1".to_string()));
+    assert_eq!(lenient.message().gps_fix(), packet.message().gps_fix());
+
+    // valid packets record no warnings
+    assert!(packet.warnings().is_empty());
+}
+
+#[test]
+fn parse_options_long_extension_bitmap() {
+    let packet = Packet::new(OptionsHeader::new(),
+                             MessageHeader::new(ServiceType::UnacknowledgedRequest,
+                                                MessageType::Null, 1),
+                             Message::Null(NullMessage::new()));
+
+    // extension with a 2 byte bitmap holding the ESN bit and unknown bit 9
+    let data = with_options_header(&packet, &[0xC0, 2, 0x01, 0x02,
+                                              5, 0x46, 0x41, 0x14, 0x38, 0x98,
+                                              2, 0xAA, 0xBB]);
+
+    let mut warnings = Vec::new();
+
+    let (options_header, length) = OptionsHeader::parse_with_options(&data,
+                                                                     &ParseOptions::strict(),
+                                                                     &mut warnings).unwrap();
+
+    assert_eq!(length, 13);
+    assert!(warnings.is_empty());
+    assert_eq!(*options_header.extension().as_ref().unwrap().esn(),
+               Some("4641143898".to_string()));
+
+    let (lenient, _) = Packet::parse_with_options(&data, &ParseOptions::lenient()).unwrap();
+
+    assert_eq!(*lenient.message_header().message_type(), MessageType::Null);
+    assert!(lenient.warnings().is_empty());
+}
+
+#[test]
+fn parse_options_unknown_encryption_type() {
+    let packet = message1();
+
+    let data = with_options_header(&packet, &[0xC0, 1, 0x04, 5, 9, 1, 2, 3, 4]);
+
+    match Packet::parse(&data) {
+        Err(CalAmpError::EncryptionType(9)) => {},
+        other => panic!("unexpected result: {:?}", other.map(|_| ()))
+    }
+
+    let (lenient, _) = Packet::parse_with_options(&data, &ParseOptions::lenient()).unwrap();
+    let extension    = lenient.options_header().extension().clone().unwrap();
+
+    assert_eq!(lenient.warnings(), &[ParseWarning::EncryptionType(9)]);
+    assert_eq!(extension.encryption_type(), None);
+    assert_eq!(*extension.encryption_service(), Some([1, 2, 3, 4]));
+    assert!(lenient.options_header().is_encrypted());
+
+    // the body cannot be decrypted, so it is kept as is
+    match *lenient.message() {
+        Message::Raw(ref body) => assert_eq!(body.len(), data.len() - 9 - 4),
+        ref other => panic!("unexpected message: {:?}", other)
+    }
+
+    assert_eq!(ParseWarning::EncryptionType(9).to_string(), "unsupported encryption type: 9");
+}
@@ -0,0 +1,437 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+extern crate calamp;
+extern crate proptest;
+
+mod common;
+
+use calamp::ParseOptions;
+use calamp::accumulators::Accumulators;
+use calamp::cipher::Rc4Cipher;
+use calamp::dissect;
+use calamp::flags::{CommState, FixStatus, Inputs, UnitStatus};
+use calamp::gps_fix::GpsFix;
+use calamp::lmu_time::{FixTime, UpdateTime};
+use calamp::message::Message;
+use calamp::message::accumulator::AccumulatorMessage;
+use calamp::message::acknowledgement::AcknowledgementMessage;
+use calamp::message::application::ApplicationMessage;
+use calamp::message::event_report::EventReportMessage;
+use calamp::message::id_report::IdReportMessage;
+use calamp::message::locate_report::LocateReportMessage;
+use calamp::message::mini_event_report::MiniEventReportMessage;
+use calamp::message::mini_report_header::MiniReportHeader;
+use calamp::message::mini_user::MiniUserMessage;
+use calamp::message::null::NullMessage;
+use calamp::message::report_header::ReportHeader;
+use calamp::message::user::UserMessage;
+use calamp::message_header::*;
+use calamp::options_header::*;
+use calamp::packet::Packet;
+use calamp::signal::{CarrierId, Rssi};
+use proptest::collection::{btree_map, vec};
+use proptest::prelude::*;
+use proptest::sample::Index;
+
+/// Encode a string of decimal digits as `length` bytes of packed BCD, filled with 0xF nibbles.
+fn bcd_fixed(digits: &str, length: usize) -> Vec<u8> {
+    let mut nibbles: Vec<u8> = digits.bytes().map(|digit| digit - b'0').collect();
+
+    nibbles.resize(length * 2, 0xF);
+    nibbles.chunks(2).map(|pair| (pair[0] << 4) | pair[1]).collect()
+}
+
+fn digits(max: usize) -> impl Strategy<Value = String> {
+    vec(0..10u8, 0..=max).prop_map(|digits| {
+        digits.into_iter().map(|digit| (b'0' + digit) as char).collect()
+    })
+}
+
+fn ip() -> impl Strategy<Value = String> {
+    any::<[u8; 4]>().prop_map(|ip| format!("{}.{}.{}.{}", ip[0], ip[1], ip[2], ip[3]))
+}
+
+fn mobile_id() -> impl Strategy<Value = MobileId> {
+    prop_oneof![
+        digits(20).prop_map(MobileId::Esn),
+        digits(20).prop_map(MobileId::ImeiEid),
+        digits(20).prop_map(MobileId::Imsi),
+        digits(20).prop_map(MobileId::Phone),
+        ip().prop_map(MobileId::IpAddress),
+        vec(any::<u8>(), 0..16).prop_map(MobileId::User),
+        (prop_oneof![Just(0u8), 7..=255u8], vec(any::<u8>(), 0..16))
+            .prop_map(|(kind, bytes)| MobileId::Unknown(kind, bytes))
+    ]
+}
+
+fn option_extension() -> impl Strategy<Value = OptionExtension> {
+    (proptest::option::of(digits(20)),
+     proptest::option::of("[A-HJ-NPR-Z0-9]{17}"),
+     proptest::option::of((0..4u8, any::<[u8; 4]>())),
+     btree_map(3..64u16, vec(any::<u8>(), 0..8), 0..4))
+        .prop_map(|(esn, vin, encryption, unknown_fields)| {
+            let mut extension = OptionExtension::new();
+
+            extension.set_esn(esn);
+            extension.set_vin(vin);
+            extension.set_encryption_service(encryption.map(|(encryption_type, random_key)| {
+                (EncryptionType::from_u8(encryption_type).unwrap(), random_key)
+            }));
+            extension.set_unknown_fields(unknown_fields.into_iter().collect());
+
+            extension
+        })
+}
+
+fn options_header() -> impl Strategy<Value = OptionsHeader> {
+    (proptest::option::of(mobile_id()),
+     proptest::option::of(vec(any::<u8>(), 0..16)),
+     proptest::option::of(vec(any::<u8>(), 0..16)),
+     proptest::option::of((ip(), any::<u16>(), any::<u8>(), any::<u8>())),
+     proptest::option::of((ip(), any::<u16>())),
+     proptest::option::of(option_extension()))
+        .prop_map(|(mobile_id, authentication, routing, forwarding, redirection, extension)| {
+            let mut options_header = OptionsHeader::new();
+
+            options_header.set_mobile_id(mobile_id);
+            options_header.set_authentication(authentication);
+            options_header.set_routing(routing);
+            options_header.set_forwarding(forwarding.map(|(ip, port, protocol, operation)| {
+                (ip, port, ForwardingProtocol::from_u8(protocol),
+                 ForwardingOperationType::from_u8(operation))
+            }));
+            options_header.set_redirection(redirection);
+            options_header.set_extension(extension);
+
+            options_header
+        })
+}
+
+fn message_type() -> impl Strategy<Value = MessageType> {
+    (0..12u8).prop_map(|value| MessageType::from_u8(value).unwrap())
+}
+
+fn message_header(message_type: MessageType) -> impl Strategy<Value = MessageHeader> {
+    (0..3u8, any::<u16>()).prop_map(move |(service_type, sequence_number)| {
+        MessageHeader::new(ServiceType::from_u8(service_type).unwrap(), message_type,
+                           sequence_number)
+    })
+}
+
+fn gps_fix() -> impl Strategy<Value = GpsFix> {
+    (any::<i32>(), any::<i32>(), any::<i32>(), any::<u32>(), any::<u16>(), any::<u8>(),
+     any::<u8>(), any::<u8>())
+        .prop_map(|(latitude, longitude, altitude, speed, heading, satellites, fix_status,
+                    hdop)| {
+            GpsFix::new(latitude, longitude, altitude, speed, heading, satellites,
+                        FixStatus::from_bits(fix_status), hdop)
+        })
+}
+
+fn report_header() -> impl Strategy<Value = ReportHeader> {
+    (any::<u32>(), any::<u32>(), gps_fix(), any::<u16>(), any::<i16>(), any::<[u8; 3]>())
+        .prop_map(|(update_time, time_of_fix, gps_fix, carrier, rssi, bits)| {
+            let mut report_header = ReportHeader::new(UpdateTime::from_secs(update_time),
+                                                      FixTime::from_secs(time_of_fix), gps_fix);
+
+            report_header.set_carrier(CarrierId::new(carrier));
+            report_header.set_rssi(Rssi::new(rssi));
+            report_header.set_comm_state(CommState::from_bits(bits[0]));
+            report_header.set_inputs(Inputs::from_bits(bits[1]));
+            report_header.set_unit_status(UnitStatus::from_bits(bits[2]));
+
+            report_header
+        })
+}
+
+/// Mini report header, built from its wire layout since the fix is reduced on the wire.
+fn mini_report_header() -> impl Strategy<Value = MiniReportHeader> {
+    any::<[u8; 17]>().prop_map(|data| MiniReportHeader::parse(&data).unwrap().0)
+}
+
+/// Accumulators, built from their wire layout to cover the list type and spare byte.
+fn accumulators() -> impl Strategy<Value = Accumulators> {
+    (0..4u8, any::<u8>(), vec(any::<u32>(), 0..64)).prop_map(|(list_type, spare, values)| {
+        let mut data = vec![(list_type << 6) | values.len() as u8, spare];
+
+        for value in values {
+            data.extend_from_slice(&value.to_be_bytes());
+        }
+
+        Accumulators::parse(&data).unwrap().0
+    })
+}
+
+/// ACK/NAK message, built from its wire layout to cover the spare byte.
+fn acknowledgement() -> impl Strategy<Value = AcknowledgementMessage> {
+    (message_type(), 0..8u8, any::<u8>(), any::<[u8; 3]>())
+        .prop_map(|(message_type, ack, spare, application_version)| {
+            let mut data = vec![message_type.as_u8(), ack, spare];
+
+            data.extend_from_slice(&application_version);
+
+            AcknowledgementMessage::parse(&data).unwrap().0
+        })
+}
+
+/// ID report message, built from its wire layout to cover every field.
+fn id_report() -> impl Strategy<Value = IdReportMessage> {
+    (any::<[u8; 12]>(), any::<u32>(), digits(16), digits(16), digits(16), digits(16), digits(20),
+     vec(any::<u8>(), 0..32))
+        .prop_map(|(fields, query_id, esn, imei, imsi, min, iccid, extension)| {
+            let mut data = fields.to_vec();
+
+            data.extend_from_slice(&query_id.to_be_bytes());
+            data.extend(bcd_fixed(&esn, 8));
+            data.extend(bcd_fixed(&imei, 8));
+            data.extend(bcd_fixed(&imsi, 8));
+            data.extend(bcd_fixed(&min, 8));
+            data.extend(bcd_fixed(&iccid, 10));
+            data.extend(extension);
+
+            IdReportMessage::parse(&data).unwrap().0
+        })
+}
+
+/// Message body of `message_type`.
+fn message(message_type: MessageType) -> BoxedStrategy<Message> {
+    match message_type {
+        MessageType::AckNak => {
+            acknowledgement().prop_map(Message::AckNak).boxed()
+        },
+        MessageType::ApplicationData => {
+            (report_header(), any::<u16>(), vec(any::<u8>(), 0..64))
+                .prop_map(|(report_header, message_type, data)| {
+                    Message::ApplicationData(ApplicationMessage::new(report_header, message_type,
+                                                                     data))
+                }).boxed()
+        },
+        MessageType::EventReport => {
+            (report_header(), any::<u8>(), any::<u8>(), accumulators())
+                .prop_map(|(report_header, event_index, event_code, accumulators)| {
+                    Message::EventReport(EventReportMessage::new(report_header, event_index,
+                                                                 event_code, accumulators))
+                }).boxed()
+        },
+        MessageType::IdReport => {
+            id_report().prop_map(Message::IdReport).boxed()
+        },
+        MessageType::LocateReport => {
+            (report_header(), any::<u8>(), any::<u8>(), accumulators())
+                .prop_map(|(report_header, event_index, event_code, accumulators)| {
+                    Message::LocateReport(LocateReportMessage::new(report_header, event_index,
+                                                                   event_code, accumulators))
+                }).boxed()
+        },
+        MessageType::MiniEventReport => {
+            (mini_report_header(), any::<u8>(), accumulators())
+                .prop_map(|(report_header, event_code, accumulators)| {
+                    Message::MiniEventReport(MiniEventReportMessage::new(report_header,
+                                                                         event_code,
+                                                                         accumulators))
+                }).boxed()
+        },
+        MessageType::MiniUser => {
+            (mini_report_header(), any::<u8>(), any::<u8>(), vec(any::<u8>(), 0..64))
+                .prop_map(|(report_header, route, id, data)| {
+                    Message::MiniUser(MiniUserMessage::new(report_header, route, id, data))
+                }).boxed()
+        },
+        MessageType::Null => {
+            Just(Message::Null(NullMessage::new())).boxed()
+        },
+        MessageType::UserData => {
+            (report_header(), any::<u8>(), any::<u8>(), vec(any::<u8>(), 0..64))
+                .prop_map(|(report_header, route, id, data)| {
+                    Message::UserData(UserMessage::new(report_header, route, id, data))
+                }).boxed()
+        },
+        MessageType::UserDataAccumulators => {
+            (report_header(), any::<u8>(), any::<u8>(), accumulators(), vec(any::<u8>(), 0..64))
+                .prop_map(|(report_header, route, id, accumulators, data)| {
+                    Message::UserDataAccumulators(AccumulatorMessage::new(report_header, route,
+                                                                          id, accumulators,
+                                                                          data))
+                }).boxed()
+        },
+        _ => {
+            vec(any::<u8>(), 0..64).prop_map(Message::Raw).boxed()
+        }
+    }
+}
+
+fn packet() -> impl Strategy<Value = Packet> {
+    message_type().prop_flat_map(|message_type| {
+        (options_header(), message_header(message_type), message(message_type))
+    }).prop_map(|(options_header, message_header, message)| {
+        Packet::new(options_header, message_header, message)
+    })
+}
+
+/// Arbitrary bytes, and corpus packets with overwritten, truncated or appended bytes.
+fn untrusted_bytes() -> impl Strategy<Value = Vec<u8>> {
+    prop_oneof![
+        vec(any::<u8>(), 0..256),
+        (any::<Index>(), vec((any::<Index>(), any::<u8>()), 0..8), any::<Index>(),
+         vec(any::<u8>(), 0..8))
+            .prop_map(|(packet, writes, cut, tail)| {
+                let mut data = packet.get(&common::corpus()).clone();
+
+                for (offset, value) in writes {
+                    let offset = offset.index(data.len());
+
+                    data[offset] = value;
+                }
+
+                data.truncate(cut.index(data.len() + 1));
+                data.extend(tail);
+
+                data
+            })
+    ]
+}
+
+proptest! {
+    #[test]
+    fn properties_options_header_round_trip(options_header in options_header()) {
+        let mut data = Vec::new();
+
+        options_header.encode(&mut data);
+
+        // a message header follows, as an options header without options is not encoded
+        let (parsed, length) = OptionsHeader::parse(&[&data[..], &[0]].concat()).unwrap();
+
+        prop_assert_eq!(length, data.len());
+        prop_assert_eq!(parsed.mobile_id(), options_header.mobile_id());
+        prop_assert_eq!(parsed.authentication(), options_header.authentication());
+        prop_assert_eq!(parsed.routing(), options_header.routing());
+        prop_assert_eq!(parsed.forwarding(), options_header.forwarding());
+        prop_assert_eq!(parsed.redirection(), options_header.redirection());
+        prop_assert_eq!(parsed.extension().is_some(), options_header.extension().is_some());
+
+        if let (Some(parsed), Some(extension)) = (parsed.extension(), options_header.extension()) {
+            prop_assert_eq!(parsed.esn(), extension.esn());
+            prop_assert_eq!(parsed.vin(), extension.vin());
+            prop_assert_eq!(parsed.encryption_type(), extension.encryption_type());
+            prop_assert_eq!(parsed.encryption_service(), extension.encryption_service());
+            prop_assert_eq!(parsed.unknown_fields(), extension.unknown_fields());
+        }
+
+        let mut encoded = Vec::new();
+
+        parsed.encode(&mut encoded);
+
+        prop_assert_eq!(encoded, data);
+    }
+
+    #[test]
+    fn properties_message_header_round_trip(message_header in message_type()
+                                                .prop_flat_map(message_header)) {
+        let mut data = Vec::new();
+
+        message_header.encode(&mut data);
+
+        let (parsed, length) = MessageHeader::parse(&data).unwrap();
+
+        prop_assert_eq!(length, 4);
+        prop_assert_eq!(format!("{:?}", parsed), format!("{:?}", message_header));
+    }
+
+    #[test]
+    fn properties_report_header_round_trip(report_header in report_header()) {
+        let mut data = Vec::new();
+
+        report_header.encode(&mut data);
+
+        let (parsed, length) = ReportHeader::parse(&data).unwrap();
+
+        prop_assert_eq!(length, data.len());
+        prop_assert_eq!(format!("{:?}", parsed), format!("{:?}", report_header));
+    }
+
+    #[test]
+    fn properties_message_round_trip((message_type, message) in message_type()
+                                         .prop_flat_map(|message_type| {
+                                             (Just(message_type), message(message_type))
+                                         })) {
+        let mut data = Vec::new();
+
+        message.encode(&mut data);
+
+        let (parsed, length) = Message::parse(&message_type, &data).unwrap();
+
+        prop_assert_eq!(length, data.len());
+        prop_assert_eq!(format!("{:?}", parsed), format!("{:?}", message));
+    }
+
+    #[test]
+    fn properties_packet_round_trip(packet in packet()) {
+        let mut data = Vec::new();
+
+        packet.encode(&mut data);
+
+        let (parsed, length) = Packet::parse(&data).unwrap();
+
+        prop_assert_eq!(length, data.len());
+        prop_assert_eq!(format!("{:?}", parsed.message_header()),
+                        format!("{:?}", packet.message_header()));
+
+        // encrypted message bodies are kept raw without a cipher
+        if !packet.options_header().is_encrypted() {
+            prop_assert_eq!(format!("{:?}", parsed.message()), format!("{:?}", packet.message()));
+        }
+
+        let mut encoded = Vec::new();
+
+        parsed.encode(&mut encoded);
+
+        prop_assert_eq!(encoded, data);
+    }
+
+    #[test]
+    fn properties_untrusted_bytes(data in untrusted_bytes()) {
+        // every parse entry point returns rather than panicking
+        let _ = OptionsHeader::parse(&data);
+        let _ = OptionsHeader::parse_with_options(&data, &ParseOptions::lenient(), &mut Vec::new());
+        let _ = MessageHeader::parse(&data);
+        let _ = ReportHeader::parse(&data);
+        let _ = Accumulators::parse(&data);
+
+        for value in 0..12 {
+            let _ = Message::parse(&MessageType::from_u8(value).unwrap(), &data);
+        }
+
+        let _ = Packet::parse_with_options(&data, &ParseOptions::lenient());
+        let _ = Packet::parse_with_cipher(&data, &Rc4Cipher, &ParseOptions::lenient());
+
+        let dissection = dissect::dissect(&data);
+
+        prop_assert!(dissection.stop() <= data.len());
+        prop_assert!(!dissection.to_string().is_empty());
+
+        // packets that parse encode back to the bytes they were parsed from
+        if let Ok((packet, length)) = Packet::parse(&data) {
+            let mut encoded = Vec::new();
+
+            packet.encode(&mut encoded);
+
+            prop_assert_eq!(&encoded[..], &data[..length]);
+        }
+    }
+}
@@ -0,0 +1,460 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+extern crate calamp;
+
+use std::io::{self, prelude::*};
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use calamp::message::Message;
+use calamp::message::acknowledgement::{AcknowledgementMessage, AcknowledgementType};
+use calamp::message::null::NullMessage;
+use calamp::message_header::*;
+use calamp::options_header::*;
+use calamp::packet::Packet;
+use calamp::relay::{Relay, Rewrite};
+
+fn esn() -> MobileId {
+    MobileId::Esn("4641143898".to_string())
+}
+
+fn relay() -> Relay {
+    let mut relay = Relay::new(Duration::from_secs(60));
+
+    relay.set_policy(Some(Box::new(|destination: SocketAddr| destination.ip().is_loopback())));
+
+    relay
+}
+
+fn request(destination: SocketAddr, protocol: ForwardingProtocol,
+           operation: ForwardingOperationType) -> Vec<u8> {
+    let mut options_header = OptionsHeader::new();
+
+    options_header.set_mobile_id(Some(esn()));
+    options_header.set_forwarding(Some((destination.ip().to_string(), destination.port(),
+                                        protocol, operation)));
+
+    let mut buffer = Vec::new();
+
+    Packet::new(options_header,
+                MessageHeader::new(ServiceType::AcknowledgedRequest, MessageType::Null, 7),
+                Message::Null(NullMessage::new())).encode(&mut buffer);
+
+    buffer
+}
+
+fn response() -> Vec<u8> {
+    let mut buffer = Vec::new();
+
+    Packet::new(OptionsHeader::new(),
+                MessageHeader::new(ServiceType::Response, MessageType::AckNak, 7),
+                Message::AckNak(AcknowledgementMessage::new(MessageType::Null,
+                                                            AcknowledgementType::Successful,
+                                                            [1, 2, 3]))).encode(&mut buffer);
+
+    buffer
+}
+
+fn sockets() -> (UdpSocket, UdpSocket, UdpSocket) {
+    let unit        = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server      = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let third_party = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+    for socket in &[&unit, &server, &third_party] {
+        socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    }
+
+    (unit, server, third_party)
+}
+
+fn receive(socket: &UdpSocket) -> (Packet, SocketAddr) {
+    let mut buffer       = [0; 512];
+    let (length, sender) = socket.recv_from(&mut buffer).unwrap();
+
+    (Packet::parse(&buffer[..length]).unwrap().0, sender)
+}
+
+fn read_forwarded(relay: &mut Relay, server: &UdpSocket, stream: &mut TcpStream) -> Vec<u8> {
+    let deadline   = Instant::now() + Duration::from_secs(5);
+    let mut buffer = [0; 512];
+
+    stream.set_read_timeout(Some(Duration::from_millis(5))).unwrap();
+
+    loop {
+        assert!(Instant::now() < deadline, "timed out waiting for the forwarded packet");
+
+        relay.poll(server, Instant::now()).unwrap();
+
+        match stream.read(&mut buffer) {
+            Ok(length) => return buffer[..length].to_vec(),
+            Err(ref error) if error.kind() == io::ErrorKind::WouldBlock ||
+                              error.kind() == io::ErrorKind::TimedOut => {},
+            Err(error) => panic!("{}", error)
+        }
+    }
+}
+
+fn poll_until_relayed(relay: &mut Relay, server: &UdpSocket, count: usize) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut relayed = 0;
+
+    while relayed < count {
+        assert!(Instant::now() < deadline, "timed out waiting for responses");
+
+        relayed += relay.poll(server, Instant::now()).unwrap();
+
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn relay_forward_udp() {
+    let (unit, _, third_party) = sockets();
+    let mut relay = relay();
+    let request   = request(third_party.local_addr().unwrap(), ForwardingProtocol::Udp,
+                            ForwardingOperationType::Forward);
+
+    assert_eq!(relay.forward(&request, unit.local_addr().unwrap(), false, Instant::now()).unwrap(),
+               Some(third_party.local_addr().unwrap()));
+
+    let mut buffer                  = [0; 512];
+    let (length, _)                 = third_party.recv_from(&mut buffer).unwrap();
+    let (forwarded, options_length) = OptionsHeader::parse(&buffer[..length]).unwrap();
+    let (_, request_length)         = OptionsHeader::parse(&request).unwrap();
+
+    assert!(forwarded.forwarding().is_none());
+    assert!(forwarded.redirection().is_none());
+    assert_eq!(*forwarded.mobile_id(), Some(esn()));
+
+    // the message header and message are forwarded as received
+    assert_eq!(&buffer[options_length..length], &request[request_length..]);
+    assert!(relay.is_empty());
+}
+
+#[test]
+fn relay_forward_redirect() {
+    let (unit, _, third_party) = sockets();
+    let mut relay = relay();
+
+    relay.set_rewrite(Rewrite::Redirect);
+
+    relay.forward(&request(third_party.local_addr().unwrap(), ForwardingProtocol::Udp,
+                           ForwardingOperationType::Forward),
+                  unit.local_addr().unwrap(), false, Instant::now()).unwrap();
+
+    let (forwarded, _) = receive(&third_party);
+    let unit_address   = unit.local_addr().unwrap();
+
+    assert!(forwarded.options_header().forwarding().is_none());
+    assert_eq!(*forwarded.options_header().redirection(),
+               Some((unit_address.ip().to_string(), unit_address.port())));
+}
+
+#[test]
+fn relay_forward_lookup() {
+    let (unit, _, third_party) = sockets();
+    let (_, _, fallback)       = sockets();
+    let mut relay              = relay();
+
+    relay.insert_lookup(esn(), third_party.local_addr().unwrap());
+
+    let request = request(fallback.local_addr().unwrap(), ForwardingProtocol::Udp,
+                          ForwardingOperationType::ForwardLookup);
+
+    assert_eq!(relay.forward(&request, unit.local_addr().unwrap(), false, Instant::now()).unwrap(),
+               Some(third_party.local_addr().unwrap()));
+
+    receive(&third_party);
+
+    // without a lookup entry the forwarding address is used
+    relay.remove_lookup(&esn());
+
+    assert_eq!(relay.forward(&request, unit.local_addr().unwrap(), false, Instant::now()).unwrap(),
+               Some(fallback.local_addr().unwrap()));
+
+    receive(&fallback);
+}
+
+#[test]
+fn relay_forward_tcp() {
+    let (unit, _, _) = sockets();
+    let listener     = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut relay    = relay();
+
+    relay.forward(&request(listener.local_addr().unwrap(), ForwardingProtocol::Tcp,
+                           ForwardingOperationType::Forward),
+                  unit.local_addr().unwrap(), false, Instant::now()).unwrap();
+
+    let (mut stream, _) = listener.accept().unwrap();
+    let mut data        = Vec::new();
+
+    stream.read_to_end(&mut data).unwrap();
+
+    let (forwarded, length) = Packet::parse(&data).unwrap();
+
+    assert_eq!(length, data.len());
+    assert!(forwarded.options_header().forwarding().is_none());
+}
+
+#[test]
+fn relay_proxy_udp() {
+    let (unit, server, third_party) = sockets();
+    let mut relay = relay();
+    let request   = request(third_party.local_addr().unwrap(), ForwardingProtocol::Udp,
+                            ForwardingOperationType::Proxy);
+
+    for _ in 0..2 {
+        relay.forward(&request, unit.local_addr().unwrap(), false, Instant::now()).unwrap();
+
+        let (forwarded, sender) = receive(&third_party);
+
+        assert!(forwarded.options_header().forwarding().is_none());
+
+        third_party.send_to(&response(), sender).unwrap();
+
+        poll_until_relayed(&mut relay, &server, 1);
+
+        let (relayed, sender) = receive(&unit);
+
+        assert_eq!(sender, server.local_addr().unwrap());
+        assert_eq!(*relayed.message_header().message_type(), MessageType::AckNak);
+    }
+
+    // the upstream socket is reused for the unit
+    assert_eq!(relay.len(), 1);
+
+    relay.poll(&server, Instant::now() + Duration::from_secs(61)).unwrap();
+
+    assert!(relay.is_empty());
+}
+
+#[test]
+fn relay_proxy_tcp() {
+    let (unit, server, _) = sockets();
+    let listener          = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut relay         = relay();
+
+    relay.forward(&request(listener.local_addr().unwrap(), ForwardingProtocol::Tcp,
+                           ForwardingOperationType::Proxy),
+                  unit.local_addr().unwrap(), false, Instant::now()).unwrap();
+
+    let (mut stream, _) = listener.accept().unwrap();
+    let forwarded       = read_forwarded(&mut relay, &server, &mut stream);
+
+    assert!(Packet::parse(&forwarded).unwrap().0.options_header().forwarding().is_none());
+
+    // two responses, split across writes
+    let mut responses = response();
+
+    responses.extend(response());
+
+    stream.write_all(&responses[..5]).unwrap();
+    stream.flush().unwrap();
+
+    thread::sleep(Duration::from_millis(20));
+
+    assert_eq!(relay.poll(&server, Instant::now()).unwrap(), 0);
+
+    stream.write_all(&responses[5..]).unwrap();
+
+    poll_until_relayed(&mut relay, &server, 2);
+
+    for _ in 0..2 {
+        assert_eq!(*receive(&unit).0.message_header().message_type(), MessageType::AckNak);
+    }
+
+    // closing the stream closes the upstream connection
+    drop(stream);
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+
+    while !relay.is_empty() {
+        assert!(Instant::now() < deadline, "upstream connection was not closed");
+
+        relay.poll(&server, Instant::now()).unwrap();
+
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn relay_no_forwarding() {
+    let (unit, _, _) = sockets();
+    let mut relay    = Relay::new(Duration::from_secs(60));
+    let packet       = Packet::new(OptionsHeader::new(),
+                                   MessageHeader::new(ServiceType::UnacknowledgedRequest,
+                                                      MessageType::Null, 1),
+                                   Message::Null(NullMessage::new()));
+    let mut buffer   = Vec::new();
+
+    packet.encode(&mut buffer);
+
+    assert_eq!(relay.forward(&buffer, unit.local_addr().unwrap(), false, Instant::now()).unwrap(),
+               None);
+}
+
+#[test]
+fn relay_policy() {
+    let (unit, _, third_party) = sockets();
+    let mut relay = Relay::new(Duration::from_secs(60));
+    let request   = request(third_party.local_addr().unwrap(), ForwardingProtocol::Udp,
+                            ForwardingOperationType::Forward);
+
+    // without a policy no forwarding address is used
+    assert_eq!(relay.forward(&request, unit.local_addr().unwrap(), false, Instant::now())
+                    .unwrap_err()
+                    .kind(),
+               io::ErrorKind::PermissionDenied);
+
+    relay.set_policy(Some(Box::new(|destination: SocketAddr| destination.port() == 9)));
+
+    assert_eq!(relay.forward(&request, unit.local_addr().unwrap(), false, Instant::now())
+                    .unwrap_err()
+                    .kind(),
+               io::ErrorKind::PermissionDenied);
+
+    // lookup destinations are used regardless
+    relay.set_policy(None);
+    relay.insert_lookup(esn(), third_party.local_addr().unwrap());
+
+    let lookup = self::request(third_party.local_addr().unwrap(), ForwardingProtocol::Udp,
+                               ForwardingOperationType::ForwardLookup);
+
+    assert_eq!(relay.forward(&lookup, unit.local_addr().unwrap(), false, Instant::now()).unwrap(),
+               Some(third_party.local_addr().unwrap()));
+
+    receive(&third_party);
+}
+
+#[test]
+fn relay_proxy_tcp_invalid_response() {
+    let (unit, server, _) = sockets();
+    let listener          = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut relay         = relay();
+
+    relay.forward(&request(listener.local_addr().unwrap(), ForwardingProtocol::Tcp,
+                           ForwardingOperationType::Proxy),
+                  unit.local_addr().unwrap(), false, Instant::now()).unwrap();
+
+    let (mut stream, _) = listener.accept().unwrap();
+
+    read_forwarded(&mut relay, &server, &mut stream);
+
+    // an unparsable response is dropped rather than relayed, and closes the connection
+    stream.write_all(&[0x07, 0x02, 0x00, 0x01, 0x00, 0x00]).unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+
+    while !relay.is_empty() {
+        assert!(Instant::now() < deadline, "upstream connection was not closed");
+
+        assert_eq!(relay.poll(&server, Instant::now()).unwrap(), 0);
+
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    unit.set_read_timeout(Some(Duration::from_millis(50))).unwrap();
+
+    assert!(unit.recv_from(&mut [0; 512]).is_err());
+}
+
+#[test]
+fn relay_proxy_udp_redirection() {
+    let (unit, server, third_party) = sockets();
+    let elsewhere     = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let elsewhere_at  = elsewhere.local_addr().unwrap();
+    let mut relay     = relay();
+    let mut request   = Packet::parse(&request(third_party.local_addr().unwrap(),
+                                               ForwardingProtocol::Udp,
+                                               ForwardingOperationType::Proxy)).unwrap().0;
+    let mut options   = request.options_header().clone();
+
+    options.set_redirection(Some((elsewhere_at.ip().to_string(), elsewhere_at.port())));
+    request = Packet::new(options, request.message_header().clone(), request.message().clone());
+
+    let mut buffer = Vec::new();
+
+    request.encode(&mut buffer);
+
+    elsewhere.set_read_timeout(Some(Duration::from_millis(50))).unwrap();
+
+    // the redirection of an unauthenticated packet is not followed
+    relay.forward(&buffer, unit.local_addr().unwrap(), false, Instant::now()).unwrap();
+
+    let (_, sender) = receive(&third_party);
+
+    // datagrams that do not parse as a packet are dropped
+    third_party.send_to(&[0x07, 0x02, 0x00, 0x01, 0x00, 0x00], sender).unwrap();
+    third_party.send_to(&response(), sender).unwrap();
+
+    poll_until_relayed(&mut relay, &server, 1);
+
+    assert_eq!(*receive(&unit).0.message_header().message_type(), MessageType::AckNak);
+    assert!(elsewhere.recv_from(&mut [0; 512]).is_err());
+    assert_eq!(relay.len(), 1);
+
+    // the redirection of an authenticated packet is followed
+    relay.forward(&buffer, unit.local_addr().unwrap(), true, Instant::now()).unwrap();
+
+    let (_, sender) = receive(&third_party);
+
+    third_party.send_to(&response(), sender).unwrap();
+
+    poll_until_relayed(&mut relay, &server, 1);
+
+    elsewhere.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    assert_eq!(*receive(&elsewhere).0.message_header().message_type(), MessageType::AckNak);
+}
+
+#[test]
+fn relay_proxy_tcp_queue_limit() {
+    let (unit, _, _) = sockets();
+    let listener     = TcpListener::bind("127.0.0.1:0").unwrap();
+    let destination  = listener.local_addr().unwrap();
+    let mut relay    = relay();
+    let mut options  = OptionsHeader::new();
+
+    options.set_mobile_id(Some(esn()));
+    options.set_forwarding(Some((destination.ip().to_string(), destination.port(),
+                                 ForwardingProtocol::Tcp, ForwardingOperationType::Proxy)));
+
+    let mut buffer = Vec::new();
+
+    Packet::new(options,
+                MessageHeader::new(ServiceType::UnacknowledgedRequest, MessageType::UserData, 1),
+                Message::Raw(vec![0; 4096])).encode(&mut buffer);
+
+    // without polling, the connection is never established and the data stays queued
+    let mut forwarded = 0;
+
+    let error = loop {
+        match relay.forward(&buffer, unit.local_addr().unwrap(), false, Instant::now()) {
+            Ok(_) => forwarded += 1,
+            Err(error) => break error
+        }
+
+        assert!(forwarded <= 16, "queued data was not limited");
+    };
+
+    assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+    assert_eq!(forwarded, 15);
+    assert_eq!(relay.len(), 1);
+}
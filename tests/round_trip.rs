@@ -0,0 +1,181 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+extern crate calamp;
+extern crate proptest;
+
+mod common;
+
+use calamp::ParseOptions;
+use calamp::message::Message;
+use calamp::options_header::*;
+use calamp::packet::Packet;
+use calamp::util::decode_hex;
+use proptest::prelude::*;
+use proptest::sample::Index;
+
+/// Parse `data` and encode the packet, returning the parsed byte count and the encoded bytes.
+fn round_trip(data: &[u8]) -> (usize, Vec<u8>) {
+    let (packet, length) = Packet::parse(data).unwrap();
+
+    let mut encoded = Vec::new();
+
+    packet.encode(&mut encoded);
+
+    (length, encoded)
+}
+
+#[test]
+fn round_trip_corpus() {
+    let corpus = common::corpus();
+
+    assert!(corpus.len() >= 20);
+
+    for data in &corpus {
+        let (length, encoded) = round_trip(data);
+
+        assert_eq!(length, data.len());
+        assert_eq!(encoded, *data);
+    }
+}
+
+#[test]
+fn round_trip_raw_fields() {
+    let corpus = common::corpus();
+
+    // spare byte of the ACK/NAK message
+    let (packet, _) = Packet::parse(&corpus[3]).unwrap();
+
+    let mut data = Vec::new();
+
+    match *packet.message() {
+        Message::AckNak(ref message) => message.encode(&mut data),
+        ref other => panic!("unexpected message: {:?}", other)
+    }
+
+    assert_eq!(data, [2, 0, 0x5A, 1, 2, 3]);
+
+    // unknown mobile id type
+    let (packet, _) = Packet::parse(&corpus[5]).unwrap();
+
+    assert_eq!(*packet.options_header().mobile_id(),
+               Some(MobileId::Unknown(9, vec![0xDE, 0xAD, 0xBE, 0xEF])));
+
+    // ip address mobile id that is not 4 bytes
+    let (packet, _) = Packet::parse(&corpus[8]).unwrap();
+
+    assert_eq!(*packet.options_header().mobile_id(),
+               Some(MobileId::Unknown(6, vec![10, 0, 0, 7, 1, 2])));
+
+    // unknown forwarding protocol and operation type
+    let (packet, _) = Packet::parse(&corpus[9]).unwrap();
+
+    assert_eq!(*packet.options_header().forwarding(),
+               Some(("192.168.1.20".to_string(), 20500, ForwardingProtocol::Unknown(1),
+                     ForwardingOperationType::Unknown(9))));
+}
+
+#[test]
+fn round_trip_received_encodings() {
+    // options headers followed by an unacknowledged null message
+    let packets = [
+        // options header without options
+        "8000000001",
+        // mobile id without a type
+        "8102123400000001",
+        // mobile id type of 2 bytes, and of 0 bytes
+        "8305464114389802010000000001",
+        "830212340000000001",
+        // filler nibbles inside and past the digits of an ESN mobile id
+        "830338f812010100000001",
+        "83041298ffff010100000001",
+        // forwarding of 0 and 10 bytes
+        "900000000001",
+        "900ac0a8011450141100aabb00000001",
+        // ESN extension with a filler nibble inside the digits
+        "c0010103f8123400000001",
+        // VIN that is not UTF-8
+        "c00102113146544657314554ff444531323334353600000001",
+        // encryption service of 6 bytes, and of 0 bytes
+        "c001040600010203049900000001",
+        "c001040000000001",
+        // null message carrying data
+        "000000011234",
+        // ID report with filler before the ESN digits
+        concat!("00030001", "01000000000000000000000100000000",
+                // ESN
+                "ffeaffffffffffff",
+                // IMEI, IMSI, MIN and ICC-ID
+                "ffffffffffffffffffffffffffffffff",
+                "ffffffffffffffffffffffffffffffffffff")
+    ];
+
+    for hex in &packets {
+        let data = decode_hex(hex).unwrap();
+
+        let (length, encoded) = round_trip(&data);
+
+        assert_eq!(length, data.len(), "{}", hex);
+        assert_eq!(encoded, data, "{}", hex);
+    }
+
+    let (packet, _) = Packet::parse(&decode_hex(packets[1]).unwrap()).unwrap();
+
+    assert_eq!(*packet.options_header().mobile_id(), Some(MobileId::Unknown(0, vec![0x12, 0x34])));
+
+    let (packet, _) = Packet::parse(&decode_hex(packets[2]).unwrap()).unwrap();
+
+    assert_eq!(*packet.options_header().mobile_id(), Some(MobileId::Esn("4641143898".to_string())));
+
+    // an unknown encryption type is kept by a lenient parse
+    let data = decode_hex("c0010405070102030400000001").unwrap();
+
+    let (packet, length) = Packet::parse_with_options(&data, &ParseOptions::lenient()).unwrap();
+
+    let mut encoded = Vec::new();
+
+    packet.encode(&mut encoded);
+
+    assert_eq!(length, data.len());
+    assert_eq!(encoded, data);
+}
+
+proptest! {
+    #[test]
+    fn round_trip_mutated_corpus(packet in any::<Index>(),
+                                 mutations in prop::collection::vec((any::<Index>(), any::<u8>()),
+                                                                    1..8)) {
+        let corpus   = common::corpus();
+        let mut data = packet.get(&corpus).clone();
+
+        for (offset, value) in mutations {
+            let offset = offset.index(data.len());
+
+            data[offset] = value;
+        }
+
+        // every mutation that still parses encodes back to the bytes it was parsed from
+        if let Ok((packet, length)) = Packet::parse(&data) {
+            let mut encoded = Vec::new();
+
+            packet.encode(&mut encoded);
+
+            prop_assert_eq!(&encoded[..], &data[..length]);
+        }
+    }
+}
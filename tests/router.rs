@@ -0,0 +1,160 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+extern crate calamp;
+
+#[cfg(feature = "toml")]
+use std::path::Path;
+
+#[cfg(feature = "toml")]
+use calamp::ConfigError;
+use calamp::message::Message;
+use calamp::message::null::NullMessage;
+use calamp::message_header::*;
+use calamp::options_header::*;
+use calamp::packet::Packet;
+use calamp::router::*;
+
+fn packet(routing: Option<Vec<u8>>, mobile_id: MobileId, message_type: MessageType) -> Packet {
+    let mut options_header = OptionsHeader::new();
+
+    options_header.set_mobile_id(Some(mobile_id));
+    options_header.set_routing(routing);
+
+    Packet::new(options_header,
+                MessageHeader::new(ServiceType::UnacknowledgedRequest, message_type, 1),
+                Message::Null(NullMessage::new()))
+}
+
+fn router() -> Router {
+    let mut router = Router::new();
+    let mut alerts = Rule::new("acme-alerts");
+    let mut acme   = Rule::new("acme");
+    let mut globex = Rule::new("globex");
+
+    alerts.set_routing(Some(vec![0x0A, 0x01]));
+    alerts.set_message_types(vec![MessageType::EventReport]);
+    acme.set_mobile_id(Some("esn:46411".to_string()));
+    globex.set_mobile_id(Some("3520".to_string()));
+
+    router.push(alerts);
+    router.push(acme);
+    router.push(globex);
+
+    router
+}
+
+#[test]
+fn router_first_match_wins() {
+    let router = router();
+    let esn    = MobileId::Esn("4641143898".to_string());
+
+    assert_eq!(router.resolve(&packet(Some(vec![0x0A, 0x01, 0x07]), esn.clone(),
+                                      MessageType::EventReport)), Some("acme-alerts"));
+    assert_eq!(router.resolve(&packet(Some(vec![0x0A, 0x01, 0x07]), esn.clone(),
+                                      MessageType::IdReport)), Some("acme"));
+    assert_eq!(router.resolve(&packet(Some(vec![0x0A]), esn.clone(), MessageType::EventReport)),
+               Some("acme"));
+    assert_eq!(router.resolve(&packet(None, esn, MessageType::Null)), Some("acme"));
+}
+
+#[test]
+fn router_mobile_id_prefix() {
+    let router = router();
+
+    // bare prefixes match any mobile ID type
+    assert_eq!(router.resolve(&packet(None, MobileId::ImeiEid("352099001761481".to_string()),
+                                      MessageType::Null)), Some("globex"));
+    assert_eq!(router.resolve(&packet(None, MobileId::Imsi("352000000000000".to_string()),
+                                      MessageType::Null)), Some("globex"));
+
+    // typed prefixes only match their type
+    assert_eq!(router.resolve(&packet(None, MobileId::Imsi("4641100000".to_string()),
+                                      MessageType::Null)), None);
+}
+
+#[test]
+fn router_default_and_metrics() {
+    let mut router = router();
+    let esn        = MobileId::Esn("4641143898".to_string());
+    let other      = MobileId::Esn("1111111111".to_string());
+
+    assert_eq!(router.route(&packet(None, other.clone(), MessageType::Null)), None);
+    assert_eq!(router.unrouted(), 1);
+
+    router.set_default(Some("unassigned".to_string()));
+
+    assert_eq!(router.route(&packet(None, other, MessageType::Null)), Some("unassigned"));
+    assert_eq!(router.route(&packet(None, esn.clone(), MessageType::EventReport)), Some("acme"));
+    assert_eq!(router.route(&packet(None, esn, MessageType::IdReport)), Some("acme"));
+
+    let acme = router.metrics("acme").unwrap();
+
+    assert_eq!(acme.packets(), 2);
+    assert_eq!(acme.message_type(MessageType::EventReport), 1);
+    assert_eq!(acme.message_type(MessageType::Null), 0);
+    assert_eq!(router.metrics("unassigned").unwrap().packets(), 1);
+    assert!(router.metrics("globex").is_none());
+    assert_eq!(router.unrouted(), 1);
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn router_from_toml() {
+    let router = Router::from_toml(r#"
+default = "unassigned"
+
+[[route]]
+destination   = "acme-alerts"
+routing       = "0a01"
+message_types = ["event_report"]
+
+[[route]]
+destination = "acme"
+mobile_id   = "esn:46411"
+"#).unwrap();
+
+    assert_eq!(*router.default_route(), Some("unassigned".to_string()));
+    assert_eq!(router.rules().len(), 2);
+    assert_eq!(*router.rules()[0].routing(), Some(vec![0x0A, 0x01]));
+    assert_eq!(router.rules()[0].message_types(), &[MessageType::EventReport]);
+    assert_eq!(router.resolve(&packet(None, MobileId::Esn("1".to_string()), MessageType::Null)),
+               Some("unassigned"));
+
+    match Router::from_toml("[[route]]\ndestination = \"x\"\nrouting = \"zz\"\n") {
+        Err(ConfigError::Invalid(_)) => {},
+        _ => panic!("Invalid routing prefix was accepted")
+    }
+
+    match Router::load("routes.yaml") {
+        Err(ConfigError::Format(path)) => assert_eq!(path, Path::new("routes.yaml")),
+        _ => panic!("Unsupported routing format was accepted")
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn router_from_json() {
+    let router = Router::from_json(r#"{
+        "route": [{"destination": "acme", "mobile_id": "esn:46411"}]
+    }"#).unwrap();
+
+    assert_eq!(*router.default_route(), None);
+    assert_eq!(router.resolve(&packet(None, MobileId::Esn("4641143898".to_string()),
+                                      MessageType::Null)), Some("acme"));
+}
@@ -0,0 +1,156 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+#![cfg(feature = "serde")]
+
+extern crate calamp;
+extern crate serde;
+extern crate serde_json;
+
+mod common;
+
+use std::fmt::Debug;
+
+use calamp::{ParseOptions, ParseWarning};
+use calamp::accumulators::{AccumulatorKind, Accumulators};
+use calamp::event_catalog::EventDefinition;
+use calamp::flags::{CommState, FixStatus, Inputs, UnitStatus};
+use calamp::gps_fix::GpsFix;
+use calamp::lmu_time::{FixTime, UpdateTime};
+use calamp::message::Message;
+use calamp::message::accumulator::AccumulatorMessage;
+use calamp::message::mini_event_report::MiniEventReportMessage;
+use calamp::message::mini_report_header::MiniReportHeader;
+use calamp::message::mini_user::MiniUserMessage;
+use calamp::message::null::NullMessage;
+use calamp::message::report_header::ReportHeader;
+use calamp::message_header::*;
+use calamp::options_header::*;
+use calamp::packet::Packet;
+use calamp::signal::{CarrierId, Rssi, SignalQuality};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// Assert that `value` survives a JSON round trip.
+fn round_trip<T: Debug + DeserializeOwned + Serialize>(value: &T) {
+    let json       = serde_json::to_string(value).unwrap();
+    let decoded: T = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(format!("{:?}", decoded), format!("{:?}", value), "{}", json);
+}
+
+#[test]
+fn serde_round_trip() {
+    let v           = common::message1();
+    let (packet, _) = Packet::parse(&v).unwrap();
+
+    let json  = serde_json::to_value(&packet).unwrap();
+    let fix   = &json["message"]["event_report"]["report_header"]["gps_fix"];
+
+    assert_eq!(json["options_header"]["mobile_id"]["esn"], "4641143898");
+    assert_eq!(json["message_header"]["message_type"], "event_report");
+    assert_eq!(json["message_header"]["service_type"], "acknowledged_request");
+    assert_eq!(json["message_header"]["sequence_number"], 1);
+    assert_eq!(fix["latitude_e7"], 331031058);
+    assert_eq!(fix["longitude_e7"], -1171834301);
+    assert_eq!(fix["fix_status"], 0x64);
+    assert_eq!(json["message"]["event_report"]["report_header"]["time_of_fix"], 1449602011);
+
+    let decoded: Packet = serde_json::from_value(json).unwrap();
+    let mut encoded     = Vec::new();
+
+    decoded.encode(&mut encoded);
+
+    assert_eq!(encoded, v);
+}
+
+#[test]
+fn serde_round_trip_corpus() {
+    for data in common::corpus() {
+        let (packet, _) = Packet::parse_with_options(&data, &ParseOptions::lenient()).unwrap();
+
+        round_trip(&packet);
+        round_trip(packet.options_header());
+        round_trip(packet.message_header());
+        round_trip(packet.message());
+    }
+}
+
+#[test]
+fn serde_round_trip_types() {
+    let fix = GpsFix::new(331031058, -1172898431, 1200, 2778, 270, 9,
+                          FixStatus::DIFFERENTIAL | FixStatus::TWO_D, 13);
+    let mut report_header = ReportHeader::new(UpdateTime::from_secs(1449602011),
+                                              FixTime::from_secs(1449602000), fix);
+
+    report_header.set_carrier(CarrierId::new(410));
+    report_header.set_rssi(Rssi::new(-91));
+    report_header.set_comm_state(CommState::AVAILABLE | CommState::CONNECTED);
+    report_header.set_inputs(Inputs::IGNITION);
+    report_header.set_unit_status(UnitStatus::GPS_TRACKING);
+
+    let mini_header = MiniReportHeader::new(UpdateTime::from_secs(1449602011), fix);
+
+    round_trip(&fix);
+    round_trip(&report_header);
+    round_trip(&mini_header);
+    round_trip(&Accumulators::new(vec![1, 2, 3]));
+    round_trip(&AccumulatorKind::Distance);
+    round_trip(&Message::UserDataAccumulators(AccumulatorMessage::new(
+        report_header.clone(), 1, 2, Accumulators::new(vec![7]), vec![1, 2, 3])));
+    round_trip(&Message::MiniEventReport(MiniEventReportMessage::new(
+        mini_header.clone(), 13, Accumulators::new(vec![7]))));
+    round_trip(&Message::MiniUser(MiniUserMessage::new(mini_header, 1, 2, vec![1, 2, 3])));
+    round_trip(&Message::Raw(vec![1, 2, 3]));
+    round_trip(&MessageType::MiniUser);
+    round_trip(&ServiceType::Response);
+    round_trip(&MobileId::Unknown(9, vec![1, 2]));
+    round_trip(&MobileId::User(vec![1, 2]));
+    round_trip(&EncryptionType::ImeiMeid);
+    round_trip(&ForwardingProtocol::from_u8(17));
+    round_trip(&ForwardingOperationType::from_u8(9));
+    round_trip(&OptionExtension::new());
+    round_trip(&Rssi::new(-91));
+    round_trip(&SignalQuality::Fair);
+    round_trip(&CarrierId::new(410));
+    round_trip(&EventDefinition::new("Ignition on", "ignition"));
+    round_trip(&ParseWarning::EncryptionType(9));
+    round_trip(&ParseWarning::VinLength(3));
+}
+
+#[test]
+fn serde_keeps_warnings() {
+    let mut options_header = OptionsHeader::new();
+    let mut extension      = OptionExtension::new();
+    let mut data           = Vec::new();
+
+    extension.set_vin(Some("1FT".to_string()));
+    options_header.set_mobile_id(Some(MobileId::Esn("4641143898".to_string())));
+    options_header.set_extension(Some(extension));
+
+    Packet::new(options_header,
+                MessageHeader::new(ServiceType::UnacknowledgedRequest, MessageType::Null, 1),
+                Message::Null(NullMessage::new())).encode(&mut data);
+
+    let (packet, _) = Packet::parse_with_options(&data, &ParseOptions::lenient()).unwrap();
+    let json        = serde_json::to_string(&packet).unwrap();
+    let decoded     = serde_json::from_str::<Packet>(&json).unwrap();
+
+    assert_eq!(packet.warnings(), [ParseWarning::VinLength(3)]);
+    assert_eq!(decoded.warnings(), packet.warnings());
+}
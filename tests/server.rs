@@ -0,0 +1,195 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+extern crate calamp;
+
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+
+use calamp::cipher::Rc4Cipher;
+use calamp::message::Message;
+use calamp::message::null::NullMessage;
+use calamp::message::acknowledgement::AcknowledgementType;
+use calamp::message_header::*;
+use calamp::options_header::*;
+use calamp::packet::Packet;
+use calamp::server::{Handler, Server};
+
+fn request(service_type: ServiceType, redirection: Option<(String, u16)>) -> Vec<u8> {
+    let mut options_header = OptionsHeader::new();
+
+    options_header.set_mobile_id(Some(MobileId::Esn("4641143898".to_string())));
+    options_header.set_redirection(redirection);
+
+    let mut buffer = Vec::new();
+
+    Packet::new(options_header,
+                MessageHeader::new(service_type, MessageType::Null, 42),
+                Message::Null(NullMessage::new())).encode(&mut buffer);
+
+    buffer
+}
+
+fn receive_ack(socket: &UdpSocket) -> Packet {
+    let mut buffer = [0; 512];
+    let (length, _) = socket.recv_from(&mut buffer).unwrap();
+
+    Packet::parse(&buffer[..length]).unwrap().0
+}
+
+#[test]
+fn server_acknowledges_request() {
+    let mut peers = Vec::new();
+    let mut server = Server::bind("127.0.0.1:0", |packet: &Packet, peer: SocketAddr| {
+        assert_eq!(packet.message_header().sequence_number(), 42);
+
+        peers.push(peer);
+
+        AcknowledgementType::Successful
+    }).unwrap();
+
+    let unit = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+    unit.send_to(&request(ServiceType::AcknowledgedRequest, None),
+                 server.local_addr().unwrap()).unwrap();
+
+    server.serve_once().unwrap();
+
+    let ack = receive_ack(&unit);
+
+    assert_eq!(*ack.message_header().service_type(), ServiceType::Response);
+    assert_eq!(*ack.message_header().message_type(), MessageType::AckNak);
+    assert_eq!(ack.message_header().sequence_number(), 42);
+
+    match *ack.options_header().mobile_id() {
+        Some(MobileId::Esn(ref esn)) => assert_eq!(esn, "4641143898"),
+        _ => panic!("ACK is missing the mobile ID")
+    }
+
+    match *ack.message() {
+        Message::AckNak(ref message) => {
+            assert_eq!(*message.message_type(), MessageType::Null);
+            assert_eq!(*message.ack(), AcknowledgementType::Successful);
+        },
+        _ => panic!("ACK body is not an ACK/NAK message")
+    }
+
+    drop(server);
+
+    assert_eq!(peers, vec![unit.local_addr().unwrap()]);
+}
+
+#[test]
+fn server_acknowledges_to_redirection() {
+    let mut server = Server::bind("127.0.0.1:0", |_: &Packet, _: SocketAddr| {
+        AcknowledgementType::FailedNoReason
+    }).unwrap();
+
+    let unit     = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let redirect = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+    unit.send_to(&request(ServiceType::AcknowledgedRequest,
+                          Some(("127.0.0.1".to_string(), redirect.local_addr().unwrap().port()))),
+                 server.local_addr().unwrap()).unwrap();
+
+    server.serve_once().unwrap();
+
+    match *receive_ack(&redirect).message() {
+        Message::AckNak(ref message) => {
+            assert_eq!(*message.ack(), AcknowledgementType::FailedNoReason);
+        },
+        _ => panic!("ACK body is not an ACK/NAK message")
+    }
+}
+
+#[test]
+fn server_survives_failed_acknowledgement() {
+    struct Counter {
+        /// Addresses that ACK/NAK messages failed to send to.
+        failed: Vec<SocketAddr>
+    }
+
+    impl Handler for Counter {
+        fn handle(&mut self, _: &Packet, _: SocketAddr) -> AcknowledgementType {
+            AcknowledgementType::Successful
+        }
+
+        fn send_failed(&mut self, _: io::Error, _: SocketAddr, address: SocketAddr) {
+            self.failed.push(address);
+        }
+    }
+
+    let mut server = Server::bind("127.0.0.1:0", Counter{ failed: Vec::new() }).unwrap();
+
+    let unit = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+    // a broadcast redirection cannot be sent to
+    unit.send_to(&request(ServiceType::AcknowledgedRequest,
+                          Some(("255.255.255.255".to_string(), 1))),
+                 server.local_addr().unwrap()).unwrap();
+
+    server.serve_once().unwrap();
+
+    assert_eq!(server.handler().failed, vec!["255.255.255.255:1".parse().unwrap()]);
+
+    unit.send_to(&request(ServiceType::AcknowledgedRequest, None),
+                 server.local_addr().unwrap()).unwrap();
+
+    server.serve_once().unwrap();
+
+    assert_eq!(receive_ack(&unit).message_header().sequence_number(), 42);
+}
+
+#[test]
+fn server_decrypts_with_cipher() {
+    let mut options_header = OptionsHeader::new();
+    let mut extension      = OptionExtension::new();
+
+    extension.set_encryption_service(Some((EncryptionType::Esn, [1, 2, 3, 4])));
+    options_header.set_mobile_id(Some(MobileId::Esn("4641143898".to_string())));
+    options_header.set_extension(Some(extension));
+
+    let mut buffer = Vec::new();
+
+    Packet::new(options_header,
+                MessageHeader::new(ServiceType::UnacknowledgedRequest, MessageType::UnitRequest, 1),
+                Message::Raw(vec![0; 4])).encode_with_cipher(&Rc4Cipher, &mut buffer).unwrap();
+
+    let mut messages = Vec::new();
+    let mut server   = Server::bind("127.0.0.1:0", |packet: &Packet, _peer: SocketAddr| {
+        messages.push(format!("{:?}", packet.message()));
+
+        AcknowledgementType::Successful
+    }).unwrap();
+
+    let unit = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+    // without a cipher the body is kept encrypted
+    unit.send_to(&buffer, server.local_addr().unwrap()).unwrap();
+    server.serve_once().unwrap();
+
+    server.set_cipher(Some(Box::new(Rc4Cipher)));
+
+    unit.send_to(&buffer, server.local_addr().unwrap()).unwrap();
+    server.serve_once().unwrap();
+
+    drop(server);
+
+    assert_ne!(messages[0], format!("{:?}", Message::Raw(vec![0; 4])));
+    assert_eq!(messages[1], format!("{:?}", Message::Raw(vec![0; 4])));
+}
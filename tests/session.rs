@@ -0,0 +1,134 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+extern crate calamp;
+
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use calamp::message::Message;
+use calamp::message_header::*;
+use calamp::options_header::*;
+use calamp::packet::Packet;
+use calamp::session::SessionTable;
+
+fn packet(mobile_id: MobileId, message_type: MessageType, sequence_number: u16, body: Vec<u8>)
+-> Packet {
+    let mut options_header = OptionsHeader::new();
+
+    options_header.set_mobile_id(Some(mobile_id));
+
+    let mut buffer = Vec::new();
+
+    Packet::new(options_header,
+                MessageHeader::new(ServiceType::AcknowledgedRequest, message_type, sequence_number),
+                Message::Raw(body)).encode(&mut buffer);
+
+    Packet::parse(&buffer).unwrap().0
+}
+
+fn id_report_body() -> Vec<u8> {
+    let mut body = vec![0x21,               // script version
+                        0, 0, 7,            // config version
+                        0x38, 0x31, 0x62,   // application version
+                        0, 0, 0, 0, 2,      // vehicle class, unit status, modem, app id, id type
+                        0, 0, 0, 0];        // query id
+
+    body.extend_from_slice(&[0x46, 0x41, 0x14, 0x38, 0x98, 0xFF, 0xFF, 0xFF]);
+    body.extend_from_slice(&[0x35, 0x91, 0x01, 0x40, 0x00, 0x00, 0x12, 0x3F]);
+    body.extend_from_slice(&[0xFF; 8]);
+    body.extend_from_slice(&[0xFF; 8]);
+    body.extend_from_slice(&[0xFF; 10]);
+
+    body
+}
+
+#[test]
+fn session_table_tracks_units() {
+    let imei  = MobileId::ImeiEid("359101400000123".to_string());
+    let esn   = MobileId::Esn("4641143898".to_string());
+    let first: SocketAddr  = "10.0.0.1:4000".parse().unwrap();
+    let second: SocketAddr = "10.0.0.2:4001".parse().unwrap();
+    let start = Instant::now();
+
+    let mut sessions = SessionTable::new(Duration::from_secs(60));
+
+    sessions.update(&packet(imei.clone(), MessageType::IdReport, 1, id_report_body()), first, start)
+            .unwrap();
+    sessions.update(&packet(imei.clone(), MessageType::Null, 2, Vec::new()), second, start)
+            .unwrap();
+    sessions.update(&packet(esn.clone(), MessageType::Null, 9, Vec::new()), first,
+                    start + Duration::from_secs(30)).unwrap();
+
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(sessions.peer(&imei), Some(second));
+
+    {
+        let session = sessions.get(&imei).unwrap();
+
+        assert_eq!(session.sequence_number(), 2);
+
+        match *session.id_report() {
+            Some(ref id_report) => {
+                assert_eq!(id_report.script_version(), 0x21);
+                assert_eq!(id_report.esn(), "4641143898");
+                assert_eq!(id_report.imei(), "359101400000123");
+                assert_eq!(id_report.imsi(), "");
+            },
+            None => panic!("Session is missing the ID report")
+        }
+    }
+
+    let expired = sessions.expire(start + Duration::from_secs(61));
+
+    assert_eq!(expired.len(), 1);
+    assert_eq!(*expired[0].mobile_id(), imei);
+    assert!(sessions.get(&imei).is_none());
+    assert_eq!(sessions.peer(&esn), Some(first));
+}
+
+#[test]
+fn session_table_evicts_least_recently_seen() {
+    let first  = MobileId::Esn("1".to_string());
+    let second = MobileId::Esn("2".to_string());
+    let third  = MobileId::Esn("3".to_string());
+    let peer: SocketAddr = "10.0.0.1:4000".parse().unwrap();
+    let start = Instant::now();
+
+    let mut sessions = SessionTable::new(Duration::from_secs(60));
+
+    sessions.set_capacity(2);
+
+    sessions.update(&packet(first.clone(), MessageType::Null, 1, Vec::new()), peer, start);
+    sessions.update(&packet(second.clone(), MessageType::Null, 1, Vec::new()), peer, start);
+    sessions.update(&packet(first.clone(), MessageType::Null, 2, Vec::new()), peer, start);
+    sessions.update(&packet(third.clone(), MessageType::Null, 1, Vec::new()), peer, start);
+
+    assert_eq!(sessions.len(), 2);
+    assert!(sessions.get(&first).is_some());
+    assert!(sessions.get(&second).is_none());
+    assert!(sessions.get(&third).is_some());
+
+    sessions.remove(&first).unwrap();
+    sessions.set_capacity(0);
+
+    assert_eq!(sessions.capacity(), 1);
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions.expire(start + Duration::from_secs(61)).len() == 1);
+    assert!(sessions.is_empty());
+}
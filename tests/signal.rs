@@ -0,0 +1,62 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+extern crate calamp;
+
+use calamp::signal::*;
+
+#[test]
+fn rssi_quality() {
+    assert_eq!(Rssi::new(-65).quality(), SignalQuality::Excellent);
+    assert_eq!(Rssi::new(-70).quality(), SignalQuality::Excellent);
+    assert_eq!(Rssi::new(-80).quality(), SignalQuality::Good);
+    assert_eq!(Rssi::new(-95).quality(), SignalQuality::Fair);
+    assert_eq!(Rssi::new(-105).quality(), SignalQuality::Poor);
+    assert_eq!(Rssi::new(-113).quality(), SignalQuality::NoSignal);
+    assert_eq!(Rssi::new(0).quality(), SignalQuality::Unknown);
+    assert_eq!(Rssi::new(-91).to_string(), "-91 dBm");
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn carrier_table() {
+    let table = CarrierTable::from_toml("# US carriers\n\
+                                         [[carrier]]\n\
+                                         id   = 410\n\
+                                         name = \"AT&T Mobility\"\n\
+                                         \n\
+                                         [[carrier]]\n\
+                                         id   = 260\n\
+                                         name = \"T-Mobile USA\"\n").unwrap();
+
+    assert_eq!(table.name(CarrierId::new(410)), Some("AT&T Mobility"));
+    assert_eq!(table.name(CarrierId::new(260)), Some("T-Mobile USA"));
+    assert_eq!(table.name(CarrierId::new(1)), None);
+    assert!(CarrierTable::from_toml("[[carrier]]\nid = 410\n").is_err());
+    assert!(CarrierTable::from_toml("[[carrier]]\nid = \"x\"\nname = \"Carrier\"\n").is_err());
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn carrier_table_json() {
+    let table = CarrierTable::from_json(r#"{"carrier": [{"id": 410, "name": "AT&T Mobility"}]}"#)
+                             .unwrap();
+
+    assert_eq!(table.name(CarrierId::new(410)), Some("AT&T Mobility"));
+    assert!(CarrierTable::from_json(r#"{"carrier": [{"id": 70000, "name": "x"}]}"#).is_err());
+}
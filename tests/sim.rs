@@ -0,0 +1,407 @@
+// +-----------------------------------------------------------------------------------------------+
+// | Copyright 2016 Sean Kerr                                                                      |
+// |                                                                                               |
+// | Licensed under the Apache License, Version 2.0 (the "License");                               |
+// | you may not use this file except in compliance with the License.                              |
+// | You may obtain a copy of the License at                                                       |
+// |                                                                                               |
+// |  http://www.apache.org/licenses/LICENSE-2.0                                                   |
+// |                                                                                               |
+// | Unless required by applicable law or agreed to in writing, software                           |
+// | distributed under the License is distributed on an "AS IS" BASIS,                             |
+// | WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.                      |
+// | See the License for the specific language governing permissions and                           |
+// | limitations under the License.                                                                |
+// +-----------------------------------------------------------------------------------------------+
+// | Author: Sean Kerr <sean@metatomic.io>                                                         |
+// +-----------------------------------------------------------------------------------------------+
+
+extern crate calamp;
+
+use std::env;
+use std::fs;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use calamp::message::Message;
+use calamp::message::acknowledgement::{AcknowledgementMessage, AcknowledgementType};
+use calamp::message_header::*;
+use calamp::options_header::*;
+use calamp::packet::Packet;
+use calamp::server::Server;
+use calamp::sim::*;
+
+fn server() -> UdpSocket {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+    socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    socket
+}
+
+fn route() -> Arc<Route> {
+    let mut first = Waypoint::new(33.1031058, -117.2898431);
+
+    first.set_altitude(12.0);
+    first.set_speed(Some(40.0));
+
+    Arc::new(Route::new(vec![first, Waypoint::new(33.2031058, -117.2898431)]))
+}
+
+fn profile() -> Profile {
+    let mut profile = Profile::new();
+
+    profile.set_report_interval(Duration::from_secs(30));
+    profile.set_keep_alive_interval(None);
+    profile.set_retry_schedule(vec![Duration::from_secs(2), Duration::from_secs(4)]);
+
+    profile
+}
+
+fn unit(server: &UdpSocket, profile: Profile) -> Unit {
+    Unit::new(MobileId::Esn("4641143898".to_string()), server.local_addr().unwrap(), route(),
+              profile).unwrap()
+}
+
+fn receive(socket: &UdpSocket) -> (Packet, SocketAddr) {
+    let mut buffer       = [0; 512];
+    let (length, sender) = socket.recv_from(&mut buffer).unwrap();
+
+    (Packet::parse(&buffer[..length]).unwrap().0, sender)
+}
+
+fn send(socket: &UdpSocket, packet: Packet, peer: SocketAddr) {
+    let mut buffer = Vec::new();
+
+    packet.encode(&mut buffer);
+
+    socket.send_to(&buffer, peer).unwrap();
+}
+
+fn ack(socket: &UdpSocket, packet: &Packet, ack: AcknowledgementType, peer: SocketAddr) {
+    send(socket, Packet::new(OptionsHeader::new(),
+                             MessageHeader::new(ServiceType::Response, MessageType::AckNak,
+                                                packet.message_header().sequence_number()),
+                             Message::AckNak(AcknowledgementMessage::new(
+                                 *packet.message_header().message_type(), ack, [0; 3]))),
+         peer);
+}
+
+/// Poll a unit at time `now` until `done` holds, giving datagrams time to arrive.
+fn poll_until<F: Fn(&Unit) -> bool>(unit: &mut Unit, now: Instant, done: F) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+
+    while !done(unit) {
+        assert!(Instant::now() < deadline, "timed out polling unit");
+
+        thread::sleep(Duration::from_millis(5));
+
+        unit.poll(now).unwrap();
+    }
+}
+
+#[test]
+fn sim_route_csv() {
+    let route = Route::from_csv("latitude,longitude,altitude,speed\n\
+                                 33.1031058,-117.2898431,12,40\n\
+                                 # no altitude or speed\n\
+                                 33.2031058,-117.2898431\n").unwrap();
+
+    assert_eq!(route.waypoints().len(), 2);
+    assert_eq!(route.waypoints()[0].altitude(), 12.0);
+    assert_eq!(route.waypoints()[0].speed(), Some(40.0));
+    assert_eq!(route.waypoints()[1].altitude(), 0.0);
+    assert_eq!(route.waypoints()[1].speed(), None);
+
+    match Route::from_csv("latitude,longitude\n# comment\n\n1.5,x\n") {
+        Err(calamp::ConfigError::Line(4, _)) => {},
+        other => panic!("unexpected result: {:?}", other.map(|route| route.waypoints().len()))
+    }
+
+    match Route::from_csv("1.5,2.5,3,4,5\n") {
+        Err(calamp::ConfigError::Line(1, _)) => {},
+        other => panic!("unexpected result: {:?}", other.map(|route| route.waypoints().len()))
+    }
+
+    match Route::from_csv("latitude,longitude\n") {
+        Err(calamp::ConfigError::Invalid(_)) => {},
+        other => panic!("unexpected result: {:?}", other.map(|route| route.waypoints().len()))
+    }
+
+    let path = env::temp_dir().join(format!("calamp-route-{}.csv", std::process::id()));
+
+    fs::write(&path, "33.1031058,-117.2898431\n33.2031058,-117.2898431,12\n").unwrap();
+
+    let loaded = Route::load(&path);
+
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded.unwrap().waypoints()[1].altitude(), 12.0);
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn sim_route_toml() {
+    let route = Route::from_toml("[[waypoint]]\n\
+                                  latitude  = 33.1031058\n\
+                                  longitude = -117.2898431\n\
+                                  altitude  = 12\n\
+                                  speed     = 40\n\
+                                  \n\
+                                  # no altitude or speed\n\
+                                  [[waypoint]]\n\
+                                  latitude  = 33.2031058\n\
+                                  longitude = -117.2898431\n").unwrap();
+
+    assert_eq!(route.waypoints().len(), 2);
+    assert_eq!(route.waypoints()[0].altitude(), 12.0);
+    assert_eq!(route.waypoints()[0].speed(), Some(40.0));
+    assert_eq!(route.waypoints()[1].altitude(), 0.0);
+    assert_eq!(route.waypoints()[1].speed(), None);
+
+    match Route::from_toml("[[waypoint]]\nlatitude = 1.5\nlongitude = \"x\"\n") {
+        Err(calamp::ConfigError::Line(3, _)) => {},
+        other => panic!("unexpected result: {:?}", other.map(|route| route.waypoints().len()))
+    }
+
+    match Route::from_toml("") {
+        Err(calamp::ConfigError::Invalid(_)) => {},
+        other => panic!("unexpected result: {:?}", other.map(|route| route.waypoints().len()))
+    }
+}
+
+#[test]
+fn sim_route_gpx() {
+    let route = Route::from_gpx(r#"<?xml version="1.0"?>
+<gpx version="1.1">
+  <trk><trkseg>
+    <trkpt lat="33.1031058" lon="-117.2898431"><ele>12.5</ele></trkpt>
+    <trkpt lon='-117.28' lat='33.11'/>
+  </trkseg></trk>
+</gpx>"#).unwrap();
+
+    assert_eq!(route.waypoints().len(), 2);
+    assert_eq!(route.waypoints()[0].latitude(), 33.1031058);
+    assert_eq!(route.waypoints()[0].altitude(), 12.5);
+    assert_eq!(route.waypoints()[1].longitude(), -117.28);
+
+    assert!(Route::from_gpx("<gpx>\n<trkpt lat=\"x\" lon=\"1\"/></gpx>").is_err());
+
+    match Route::from_gpx("<gpx><trk><trkseg></trkseg></trk></gpx>") {
+        Err(calamp::ConfigError::Invalid(_)) => {},
+        other => panic!("unexpected result: {:?}", other.map(|route| route.waypoints().len()))
+    }
+}
+
+#[test]
+fn sim_boot_and_reports() {
+    let server   = server();
+    let mut unit = unit(&server, profile());
+    let start    = Instant::now();
+
+    unit.poll(start).unwrap();
+
+    let (report, peer) = receive(&server);
+
+    assert_eq!(*report.message_header().service_type(), ServiceType::AcknowledgedRequest);
+    assert_eq!(*report.options_header().mobile_id(), Some(MobileId::Esn("4641143898".to_string())));
+
+    match *report.message() {
+        Message::IdReport(ref id_report) => {
+            assert_eq!(id_report.esn(), "4641143898");
+            assert_eq!(id_report.mobile_id_type(), 1);
+        },
+        ref other => panic!("unexpected message: {:?}", other)
+    }
+
+    ack(&server, &report, AcknowledgementType::Successful, peer);
+
+    poll_until(&mut unit, start, |unit| unit.stats().acknowledged() == 1);
+
+    // first event report at the first waypoint, heading north towards the second
+    unit.poll(start + Duration::from_secs(30)).unwrap();
+
+    let (report, _) = receive(&server);
+    let fix         = *report.message().gps_fix().unwrap();
+
+    assert_eq!(*report.message_header().message_type(), MessageType::EventReport);
+    assert_eq!(fix.latitude_raw(), 331031058);
+    assert_eq!(fix.altitude_cm(), 1200);
+    assert_eq!(fix.heading(), 0);
+    assert!((fix.speed_kmh() - 40.0).abs() < 0.01);
+    assert!(report.message_header().sequence_number() > 1);
+
+    ack(&server, &report, AcknowledgementType::Successful, peer);
+
+    poll_until(&mut unit, start, |unit| unit.pending() == 0);
+
+    // second event report at the second waypoint, heading back south at the profile speed
+    unit.poll(start + Duration::from_secs(60)).unwrap();
+
+    let fix = *receive(&server).0.message().gps_fix().unwrap();
+
+    assert_eq!(fix.latitude_raw(), 332031058);
+    assert_eq!(fix.heading(), 180);
+    assert!((fix.speed_kmh() - 50.0).abs() < 0.01);
+}
+
+#[test]
+fn sim_retries_until_acknowledged() {
+    let server   = server();
+    let mut unit = unit(&server, profile());
+    let start    = Instant::now();
+
+    unit.poll(start).unwrap();
+
+    let (first, _) = receive(&server);
+
+    // nothing is resent before the retry delay passes
+    unit.poll(start + Duration::from_secs(1)).unwrap();
+    unit.poll(start + Duration::from_secs(2)).unwrap();
+
+    let (retry, peer) = receive(&server);
+
+    assert_eq!(retry.message_header().sequence_number(), first.message_header().sequence_number());
+    assert_eq!(unit.stats().retries(), 1);
+
+    // a NAK completes the request
+    ack(&server, &retry, AcknowledgementType::FailedOperation, peer);
+
+    poll_until(&mut unit, start + Duration::from_secs(2), |unit| unit.stats().rejected() == 1);
+
+    assert_eq!(unit.pending(), 0);
+}
+
+#[test]
+fn sim_expires_after_retry_schedule() {
+    let server   = server();
+    let mut unit = unit(&server, profile());
+    let start    = Instant::now();
+
+    unit.poll(start).unwrap();
+    unit.poll(start + Duration::from_secs(2)).unwrap();
+
+    // the retry schedule runs out, and the next report is sent
+    unit.poll(start + Duration::from_secs(30)).unwrap();
+
+    assert_eq!(unit.stats().expired(), 1);
+    assert_eq!(unit.stats().retries(), 1);
+
+    let messages: Vec<MessageType> = (0..3).map(|_| {
+        *receive(&server).0.message_header().message_type()
+    }).collect();
+
+    assert_eq!(messages, vec![MessageType::IdReport, MessageType::IdReport,
+                              MessageType::EventReport]);
+}
+
+#[test]
+fn sim_answers_server_requests() {
+    let server   = server();
+    let mut unit = unit(&server, profile());
+    let start    = Instant::now();
+
+    unit.poll(start).unwrap();
+
+    let (id_report, peer) = receive(&server);
+
+    ack(&server, &id_report, AcknowledgementType::Successful, peer);
+
+    let mut options_header = OptionsHeader::new();
+
+    options_header.set_mobile_id(Some(unit.mobile_id().clone()));
+
+    send(&server, Packet::new(options_header.clone(),
+                              MessageHeader::new(ServiceType::AcknowledgedRequest,
+                                                 MessageType::ConfigurationParameter, 40),
+                              Message::Raw(vec![0x04, 0x00, 0x01, 0x02])),
+         peer);
+    send(&server, Packet::new(options_header,
+                              MessageHeader::new(ServiceType::AcknowledgedRequest,
+                                                 MessageType::UnitRequest, 41),
+                              Message::Raw(vec![LOCATE_REQUEST, 0])),
+         peer);
+
+    poll_until(&mut unit, start, |unit| unit.stats().received() == 3);
+
+    assert_eq!(unit.parameters(), &[vec![0x04, 0x00, 0x01, 0x02]]);
+
+    let mut acked = Vec::new();
+
+    for _ in 0..3 {
+        let (packet, _) = receive(&server);
+
+        match *packet.message() {
+            Message::AckNak(ref ack) => {
+                assert_eq!(*ack.ack(), AcknowledgementType::Successful);
+
+                acked.push((packet.message_header().sequence_number(), *ack.message_type()));
+            },
+            Message::LocateReport(_) => {
+                assert_eq!(*packet.message_header().service_type(),
+                           ServiceType::AcknowledgedRequest);
+            },
+            ref other => panic!("unexpected message: {:?}", other)
+        }
+    }
+
+    assert_eq!(acked, vec![(40, MessageType::ConfigurationParameter),
+                           (41, MessageType::UnitRequest)]);
+}
+
+#[test]
+fn sim_keep_alive() {
+    let server      = server();
+    let mut profile = profile();
+
+    profile.set_keep_alive_interval(Some(Duration::from_secs(10)));
+    profile.set_report_interval(Duration::from_secs(3600));
+
+    let mut unit = unit(&server, profile);
+    let start    = Instant::now();
+
+    unit.poll(start).unwrap();
+
+    let (id_report, peer) = receive(&server);
+
+    ack(&server, &id_report, AcknowledgementType::Successful, peer);
+
+    poll_until(&mut unit, start, |unit| unit.pending() == 0);
+
+    unit.poll(start + Duration::from_secs(10)).unwrap();
+
+    let (null, _) = receive(&server);
+
+    assert_eq!(*null.message_header().message_type(), MessageType::Null);
+    assert_eq!(*null.message_header().service_type(), ServiceType::UnacknowledgedRequest);
+}
+
+#[test]
+fn sim_against_server() {
+    let mut server = Server::bind("127.0.0.1:0", |_: &Packet, _: SocketAddr| {
+        AcknowledgementType::Successful
+    }).unwrap();
+
+    let address = server.local_addr().unwrap();
+
+    thread::spawn(move || server.serve());
+
+    let mut profile = profile();
+
+    profile.set_report_interval(Duration::from_millis(50));
+    profile.set_user_interval(Some(Duration::from_millis(80)));
+
+    let mut simulator = Simulator::spawn(3, 4000000000, address, route(), profile).unwrap();
+
+    simulator.run(Duration::from_millis(500), Duration::from_millis(5)).unwrap();
+
+    let stats = simulator.stats();
+
+    assert_eq!(simulator.units().len(), 3);
+    assert_eq!(*simulator.units()[2].mobile_id(), MobileId::Esn("4000000002".to_string()));
+    assert!(stats.acknowledged() >= 3 * 5, "{:?}", stats);
+    assert_eq!(stats.expired(), 0);
+    assert_eq!(stats.rejected(), 0);
+}